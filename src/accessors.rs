@@ -0,0 +1,414 @@
+//! Typed access to the values of a `QueryMap`.
+
+use std::collections::{BTreeMap, HashSet};
+use std::hash::Hash;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::time::Duration;
+
+use url::Url;
+
+use super::{QueryMap, UrlDecodingError};
+use super::values::{self, Base64Alphabet, JwtParts, ParamEnum, NumberFormat, EmailOptions,
+                    UrlOptions, Color, DurationOptions};
+use super::network::{self, IpCidr};
+use super::tracking::glob_match;
+use super::stats::ParamStats;
+use super::repopulate::Escaped;
+use super::dates::{self, DateRange};
+use super::param::Param;
+use super::patch::{self, Patch};
+use super::delta::{self, SetDelta};
+#[cfg(feature = "decimal")]
+use super::decimal::{self, Decimal};
+#[cfg(feature = "phone")]
+use super::phone;
+#[cfg(feature = "locale")]
+use super::locale::{self, Country, Language};
+#[cfg(feature = "timezone")]
+use super::timezone::{self, TimeZone};
+
+/// Accessors for parsed parameters, implemented for `QueryMap`.
+///
+/// Accessors that need a value fail with `MissingParameter` when the key is
+/// absent; wrap them in `optional` for fields the client may leave out.
+pub trait QueryMapExt {
+    /// The first value of `key`, if any.
+    fn first(&self, key: &str) -> Option<&str>;
+
+    /// The value of `key`, which must not be sent more than once.
+    fn single(&self, key: &str) -> Result<Option<&str>, UrlDecodingError>;
+
+    /// The first value of `key`, failing with `MissingParameter` if there is none.
+    fn required(&self, key: &str) -> Result<&str, UrlDecodingError>;
+
+    /// The value of `key`, which must not be sent more than once, to convert
+    /// and check in a chain: `params.param("age").required().as_u32().finish()`
+    fn param<'a>(&'a self, key: &'a str) -> Param<'a, &'a str>;
+
+    /// The change a `PATCH` form makes to `key`: `Missing` when it was left
+    /// out, `Null` when it was sent empty to clear the field.
+    fn get_patch<T: FromStr>(&self, key: &str) -> Result<Patch<T>, UrlDecodingError>;
+
+    /// The first value of `key` decoded from base64, as used for SAML messages,
+    /// webhook payloads and pagination cursors.
+    ///
+    /// Padding is optional and the decoded bytes may be at most `max_len` long.
+    fn get_base64(&self, key: &str, alphabet: Base64Alphabet, max_len: usize)
+                  -> Result<Vec<u8>, UrlDecodingError>;
+
+    /// The first value of `key` decoded from exactly `expected_len` bytes of
+    /// hex, as used for signatures, digests and fixed-size ids.
+    fn get_hex_bytes(&self, key: &str, expected_len: usize) -> Result<Vec<u8>, UrlDecodingError>;
+
+    /// The first value of `key` as a CSS hex color such as `#ff8800`.
+    fn get_color(&self, key: &str) -> Result<Color, UrlDecodingError>;
+
+    /// The first value of `key` as a JSON Web Token of at most `max_len` bytes.
+    ///
+    /// Only the structure of the token is checked, so that malformed tokens are
+    /// rejected early: its signature still has to be verified.
+    fn get_jwt(&self, key: &str, max_len: usize) -> Result<JwtParts, UrlDecodingError>;
+
+    /// The first value of `key` as one of the values of `T`. The error lists
+    /// every allowed value.
+    fn get_enum<T: ParamEnum>(&self, key: &str) -> Result<T, UrlDecodingError>;
+
+    /// The first value of `key` as a number, written as `format` describes.
+    ///
+    /// Use `NumberFormat::DecimalComma` for forms filled in with, for example,
+    /// German or French number conventions.
+    fn get_number<T: FromStr>(&self, key: &str, format: NumberFormat) -> Result<T, UrlDecodingError>;
+
+    /// The first value of `key` as an email address, checked for plausibility
+    /// and normalized as `options` requests.
+    fn get_email(&self, key: &str, options: EmailOptions) -> Result<String, UrlDecodingError>;
+
+    /// The first value of `key` as an absolute URL, restricted to the schemes
+    /// and hosts `options` allows.
+    fn get_url(&self, key: &str, options: &UrlOptions) -> Result<Url, UrlDecodingError>;
+
+    /// The dates of `from_key` and `to_key`, written `YYYY-MM-DD`, as an
+    /// inclusive range. `to_key` may not be before `from_key` and, with
+    /// `max_days`, the range may not cover more days.
+    fn get_date_range(&self, from_key: &str, to_key: &str, max_days: Option<u32>)
+                      -> Result<DateRange, UrlDecodingError>;
+
+    /// The first value of `key` as a duration such as `30s` or `1h30m`, in the
+    /// units and bounds `options` allows, for timeouts and polling windows.
+    fn get_duration(&self, key: &str, options: &DurationOptions) -> Result<Duration, UrlDecodingError>;
+
+    /// The first value of `key` as an IPv4 or IPv6 address.
+    fn get_ip(&self, key: &str) -> Result<IpAddr, UrlDecodingError>;
+
+    /// The first value of `key` as a range in CIDR notation, like `10.0.0.0/8`.
+    fn get_cidr(&self, key: &str) -> Result<IpCidr, UrlDecodingError>;
+
+    /// Every value of `key` as a set, the way a group of checkboxes arrives:
+    /// `perm=read&perm=write`.
+    ///
+    /// Since browsers send nothing when no box is ticked, an absent key is an
+    /// empty set. Values must be in `allowed`, if given, and must be unique.
+    fn get_set<T>(&self, key: &str, allowed: Option<&[&str]>) -> Result<HashSet<T>, UrlDecodingError>
+    where T: FromStr + Hash + Eq;
+
+    /// The membership changes sent in `added_key` and `removed_key`, or their
+    /// bracketed forms: `added[]=3&removed[]=2`. A value in both is refused.
+    fn get_set_delta<T>(&self, added_key: &str, removed_key: &str) -> Result<SetDelta<T>, UrlDecodingError>
+    where T: FromStr + Hash + Eq + Clone + ToString;
+
+    /// Every value of `key` as the ids of a bulk operation:
+    /// `ids=1&ids=2&ids=3`.
+    ///
+    /// Repeated ids are only kept once, in the order they first appear, and
+    /// an absent key is no ids. The error lists every value that isn't a
+    /// valid `T`, and more than `max` distinct ids are refused.
+    fn get_ids<T>(&self, key: &str, max: usize) -> Result<Vec<T>, UrlDecodingError>
+    where T: FromStr + Hash + Eq + Clone;
+
+    /// The parameters whose key starts with `prefix`, with the prefix
+    /// stripped, for forms with generated field names: `answer_12=yes` is
+    /// `12` of `collect_prefixed("answer_")`.
+    fn collect_prefixed(&self, prefix: &str) -> QueryMap;
+
+    /// Like `collect_prefixed`, for keys ending in a number, by that number.
+    /// Keys with the prefix and anything else after it are refused, as are
+    /// numbers with leading zeros, so no two keys give the same number.
+    fn collect_numbered(&self, prefix: &str) -> Result<BTreeMap<u64, Vec<String>>, UrlDecodingError>;
+
+    /// The parameters whose key matches `pattern`, where `*` stands for any
+    /// characters, as in `filter.*`, in key order.
+    fn get_matching(&self, pattern: &str) -> Vec<(&str, &[String])>;
+
+    /// How many pairs, keys and bytes the parameters have.
+    fn stats(&self) -> ParamStats;
+
+    /// The values escaped for HTML text or attributes, for templates
+    /// echoing parameters back.
+    fn escaped(&self) -> Escaped<'_>;
+
+    /// The first value of `key` as an exact decimal with at most `max_scale`
+    /// digits after the point, for amounts of money.
+    ///
+    /// Requires the `decimal` feature.
+    #[cfg(feature = "decimal")]
+    fn get_decimal(&self, key: &str, max_scale: u32) -> Result<Decimal, UrlDecodingError>;
+
+    /// The first value of `key` as a phone number in E.164 form, such as
+    /// `+14155550123`. Numbers written without a country code are read as
+    /// national numbers of `default_region`, an ISO 3166 code like `US`.
+    ///
+    /// Only the structure and length are checked, against an embedded table
+    /// of calling codes. Requires the `phone` feature.
+    #[cfg(feature = "phone")]
+    fn get_phone(&self, key: &str, default_region: &str) -> Result<String, UrlDecodingError>;
+
+    /// The first value of `key` as an ISO 3166-1 alpha-2 country code, in any
+    /// case: `fr` gives `FR`.
+    ///
+    /// Requires the `locale` feature.
+    #[cfg(feature = "locale")]
+    fn get_country(&self, key: &str) -> Result<Country, UrlDecodingError>;
+
+    /// The first value of `key` as an ISO 639-1 language code, in any case:
+    /// `FR` gives `fr`.
+    ///
+    /// Requires the `locale` feature.
+    #[cfg(feature = "locale")]
+    fn get_language(&self, key: &str) -> Result<Language, UrlDecodingError>;
+
+    /// The first value of `key` as a zone of the IANA tz database, such as
+    /// `America/New_York`, in any case. Old names such as `US/Eastern` give
+    /// the zone they link to.
+    ///
+    /// Requires the `timezone` feature.
+    #[cfg(feature = "timezone")]
+    fn get_timezone(&self, key: &str) -> Result<TimeZone, UrlDecodingError>;
+}
+
+impl QueryMapExt for QueryMap {
+    fn first(&self, key: &str) -> Option<&str> {
+        self.get(key).and_then(|values| values.first()).map(|value| &**value)
+    }
+
+    fn single(&self, key: &str) -> Result<Option<&str>, UrlDecodingError> {
+        match self.get(key).map(|values| &values[..]) {
+            None | Some([]) => Ok(None),
+            Some([value]) => Ok(Some(value)),
+            Some(_) => Err(UrlDecodingError::InvalidParameter {
+                key: key.to_owned(),
+                message: "must be sent only once".to_owned()
+            })
+        }
+    }
+
+    fn required(&self, key: &str) -> Result<&str, UrlDecodingError> {
+        self.first(key).ok_or_else(|| UrlDecodingError::MissingParameter(key.to_owned()))
+    }
+
+    fn param<'a>(&'a self, key: &'a str) -> Param<'a, &'a str> {
+        Param::new(key, self.single(key))
+    }
+
+    fn get_patch<T: FromStr>(&self, key: &str) -> Result<Patch<T>, UrlDecodingError> {
+        patch::parse_patch(self, key)
+    }
+
+    fn get_base64(&self, key: &str, alphabet: Base64Alphabet, max_len: usize)
+                  -> Result<Vec<u8>, UrlDecodingError> {
+        self.required(key).and_then(|value| values::parse_base64(key, value, alphabet, max_len))
+    }
+
+    fn get_hex_bytes(&self, key: &str, expected_len: usize) -> Result<Vec<u8>, UrlDecodingError> {
+        self.required(key).and_then(|value| values::parse_hex(key, value, expected_len))
+    }
+
+    fn get_color(&self, key: &str) -> Result<Color, UrlDecodingError> {
+        self.required(key).and_then(|value| values::parse_color(key, value))
+    }
+
+    fn get_jwt(&self, key: &str, max_len: usize) -> Result<JwtParts, UrlDecodingError> {
+        self.required(key).and_then(|value| values::parse_jwt(key, value, max_len))
+    }
+
+    fn get_enum<T: ParamEnum>(&self, key: &str) -> Result<T, UrlDecodingError> {
+        self.required(key).and_then(|value| values::parse_enum(key, value))
+    }
+
+    fn get_number<T: FromStr>(&self, key: &str, format: NumberFormat) -> Result<T, UrlDecodingError> {
+        self.required(key).and_then(|value| values::parse_number(key, value, format))
+    }
+
+    fn get_email(&self, key: &str, options: EmailOptions) -> Result<String, UrlDecodingError> {
+        self.required(key).and_then(|value| values::parse_email(key, value, options))
+    }
+
+    fn get_duration(&self, key: &str, options: &DurationOptions) -> Result<Duration, UrlDecodingError> {
+        self.required(key).and_then(|value| values::parse_duration(key, value, options))
+    }
+
+    fn get_date_range(&self, from_key: &str, to_key: &str, max_days: Option<u32>)
+                      -> Result<DateRange, UrlDecodingError> {
+        let (from, to) = (self.required(from_key)?, self.required(to_key)?);
+        dates::parse_date_range(from_key, from, to_key, to, max_days)
+    }
+
+    fn get_url(&self, key: &str, options: &UrlOptions) -> Result<Url, UrlDecodingError> {
+        self.required(key).and_then(|value| values::parse_url(key, value, options))
+    }
+
+    fn get_ip(&self, key: &str) -> Result<IpAddr, UrlDecodingError> {
+        self.required(key).and_then(|value| network::parse_ip(key, value))
+    }
+
+    fn get_cidr(&self, key: &str) -> Result<IpCidr, UrlDecodingError> {
+        self.required(key).and_then(|value| network::parse_cidr(key, value))
+    }
+
+    fn get_set<T>(&self, key: &str, allowed: Option<&[&str]>) -> Result<HashSet<T>, UrlDecodingError>
+    where T: FromStr + Hash + Eq {
+        match self.get(key) {
+            Some(values) => values::parse_set(key, values, allowed),
+            None => Ok(HashSet::new())
+        }
+    }
+
+    fn get_set_delta<T>(&self, added_key: &str, removed_key: &str) -> Result<SetDelta<T>, UrlDecodingError>
+    where T: FromStr + Hash + Eq + Clone + ToString {
+        delta::parse_set_delta(self, added_key, removed_key)
+    }
+
+    fn get_ids<T>(&self, key: &str, max: usize) -> Result<Vec<T>, UrlDecodingError>
+    where T: FromStr + Hash + Eq + Clone {
+        match self.get(key) {
+            Some(values) => values::parse_ids(key, values, max),
+            None => Ok(Vec::new())
+        }
+    }
+
+    fn collect_prefixed(&self, prefix: &str) -> QueryMap {
+        self.iter()
+            .filter_map(|(key, values)| key.strip_prefix(prefix).map(|suffix| (suffix.to_owned(), values.clone())))
+            .collect()
+    }
+
+    fn collect_numbered(&self, prefix: &str) -> Result<BTreeMap<u64, Vec<String>>, UrlDecodingError> {
+        let mut numbered = BTreeMap::new();
+        for (key, values) in self {
+            let suffix = match key.strip_prefix(prefix) {
+                Some(suffix) => suffix,
+                None => continue
+            };
+            match numeric_suffix(suffix) {
+                Some(number) => { numbered.insert(number, values.clone()); },
+                None => return Err(UrlDecodingError::InvalidParameter {
+                    key: key.clone(),
+                    message: format!("must be {} followed by a number without leading zeros", prefix)
+                })
+            }
+        }
+        Ok(numbered)
+    }
+
+    fn get_matching(&self, pattern: &str) -> Vec<(&str, &[String])> {
+        let mut matching: Vec<(&str, &[String])> = self.iter()
+            .filter(|&(key, _)| glob_match(pattern, key))
+            .map(|(key, values)| (&**key, &values[..]))
+            .collect();
+        matching.sort_by_key(|&(key, _)| key);
+        matching
+    }
+
+    fn stats(&self) -> ParamStats {
+        ParamStats::of(self)
+    }
+
+    fn escaped(&self) -> Escaped<'_> {
+        Escaped::new(self)
+    }
+
+    #[cfg(feature = "decimal")]
+    fn get_decimal(&self, key: &str, max_scale: u32) -> Result<Decimal, UrlDecodingError> {
+        self.required(key).and_then(|value| decimal::parse_decimal(key, value, max_scale))
+    }
+
+    #[cfg(feature = "phone")]
+    fn get_phone(&self, key: &str, default_region: &str) -> Result<String, UrlDecodingError> {
+        self.required(key).and_then(|value| phone::parse_phone(key, value, default_region))
+    }
+
+    #[cfg(feature = "locale")]
+    fn get_country(&self, key: &str) -> Result<Country, UrlDecodingError> {
+        self.required(key).and_then(|value| locale::parse_country(key, value))
+    }
+
+    #[cfg(feature = "locale")]
+    fn get_language(&self, key: &str) -> Result<Language, UrlDecodingError> {
+        self.required(key).and_then(|value| locale::parse_language(key, value))
+    }
+
+    #[cfg(feature = "timezone")]
+    fn get_timezone(&self, key: &str) -> Result<TimeZone, UrlDecodingError> {
+        self.required(key).and_then(|value| timezone::parse_timezone(key, value))
+    }
+}
+
+/// The number written in `suffix`, made of ASCII digits only and without
+/// leading zeros: `12` and `0` but not `+12`, ` 12`, `012` or `12a`.
+pub fn numeric_suffix(suffix: &str) -> Option<u64> {
+    if suffix.is_empty() || !suffix.bytes().all(|byte| byte.is_ascii_digit()) ||
+       (suffix.len() > 1 && suffix.starts_with('0')) {
+        return None;
+    }
+    suffix.parse().ok()
+}
+
+/// Turn a `MissingParameter` error into `Ok(None)`, keeping every other error.
+///
+/// `optional(params.required("page"))` accepts a request without a `page`, but
+/// not one with an invalid `page`.
+pub fn optional<T>(result: Result<T, UrlDecodingError>) -> Result<Option<T>, UrlDecodingError> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(UrlDecodingError::MissingParameter(_)) => Ok(None),
+        Err(err) => Err(err)
+    }
+}
+
+#[test]
+fn test_accessors() {
+    let mut map = QueryMap::new();
+    map.insert("tag".to_owned(), vec!["a".to_owned(), "b".to_owned()]);
+    map.insert("page".to_owned(), vec!["2".to_owned()]);
+
+    assert_eq!(map.first("tag"), Some("a"));
+    assert_eq!(map.single("page").unwrap(), Some("2"));
+    assert_eq!(map.single("missing").unwrap(), None);
+    assert!(map.single("tag").is_err());
+    assert_eq!(optional(map.required("page")).unwrap(), Some("2"));
+    assert_eq!(optional(map.required("missing")).unwrap(), None);
+
+    map.insert("ids".to_owned(), vec!["3".to_owned(), "1".to_owned(), "3".to_owned()]);
+    assert_eq!(map.get_ids::<u32>("ids", 2).unwrap(), vec![3, 1]);
+    assert!(map.get_ids::<u32>("ids", 1).is_err());
+    assert_eq!(map.get_ids::<u32>("missing", 2).unwrap(), Vec::<u32>::new());
+    map.insert("answer_12".to_owned(), vec!["yes".to_owned()]);
+    map.insert("answer_3".to_owned(), vec!["no".to_owned()]);
+    assert_eq!(map.collect_prefixed("answer_")["12"], vec!["yes".to_owned()]);
+    let numbered = map.collect_numbered("answer_").unwrap();
+    assert_eq!(numbered.keys().collect::<Vec<_>>(), vec![&3, &12]);
+    map.insert("answer_x".to_owned(), vec!["?".to_owned()]);
+    assert!(map.collect_numbered("answer_").is_err());
+    assert_eq!(numeric_suffix("+1"), None);
+    assert_eq!((numeric_suffix("0"), numeric_suffix("007")), (Some(0), None));
+    let mut colliding = QueryMap::new();
+    colliding.insert("answer_7".to_owned(), vec!["yes".to_owned()]);
+    colliding.insert("answer_007".to_owned(), vec!["no".to_owned()]);
+    assert!(colliding.collect_numbered("answer_").is_err());
+    let answers: Vec<&str> = map.get_matching("answer_*").into_iter().map(|(key, _)| key).collect();
+    assert_eq!(answers, vec!["answer_12", "answer_3", "answer_x"]);
+    assert_eq!(map.get_matching("*ag"), vec![("tag", &map["tag"][..])]);
+
+    let err = map.get_ids::<u32>("tag", 10).unwrap_err();
+    assert!(err.to_string().contains("`a`, `b`"), "{}", err);
+}
@@ -0,0 +1,142 @@
+//! Describing submitted forms to analytics pipelines without handing them
+//! the values: parameter names, and salted hashes of the values of chosen
+//! keys.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+#[cfg(feature = "iron")]
+use iron::prelude::*;
+use rustc_serialize::hex::ToHex;
+use rustc_serialize::json::{self, Json};
+
+use super::QueryMap;
+#[cfg(feature = "iron")]
+use super::{UrlDecodingError, form_params};
+use super::signing::Signer;
+use super::tracking::glob_match;
+
+/// A parameter as exported by an `AnalyticsExporter`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnalyticsParam {
+    /// The name of the parameter.
+    pub name: String,
+    /// How many values it was sent with.
+    pub values: usize,
+    /// How many of them were empty.
+    pub empty: usize,
+    /// The salted hashes of its values, in hex, for the hashed keys.
+    pub hashes: Option<Vec<String>>
+}
+
+/// Exports the shape of submitted forms for product analytics: the names of
+/// their parameters and how many values each had, never the values
+/// themselves. For the keys to `hash`, it adds a salted hash of every value,
+/// so pipelines can count distinct values and spot repeated ones without
+/// receiving the PII behind them.
+///
+/// Hashes are computed with `signer`, an HMAC for instance, keyed with the
+/// salt, over the name of the parameter and the value: the same value sent
+/// as two parameters hashes differently. Keep the salt secret, as values
+/// such as emails are easy to guess and check against a known salt.
+///
+/// ```ignore
+/// let exporter = AnalyticsExporter::new(hmac_sha256, SALT).hash(&["email", "utm_*"]);
+/// pipeline.send(exporter.to_json(&params));
+/// ```
+#[derive(Clone)]
+pub struct AnalyticsExporter {
+    signer: Arc<dyn Signer + Send + Sync>,
+    salt: Vec<u8>,
+    hashed: Vec<String>
+}
+
+impl AnalyticsExporter {
+    /// An exporter hashing with `signer` and `salt`, and hashing no key yet.
+    pub fn new<S>(signer: S, salt: &[u8]) -> AnalyticsExporter
+    where S: Signer + Send + Sync + 'static {
+        AnalyticsExporter { signer: Arc::new(signer), salt: salt.to_vec(), hashed: Vec::new() }
+    }
+
+    /// Hash the values of the keys matching one of `patterns`, where `*`
+    /// stands for any characters.
+    pub fn hash(mut self, patterns: &[&str]) -> AnalyticsExporter {
+        self.hashed.extend(patterns.iter().map(|pattern| (*pattern).to_owned()));
+        self
+    }
+
+    fn is_hashed(&self, name: &str) -> bool {
+        self.hashed.iter().any(|pattern| glob_match(pattern, name))
+    }
+
+    fn hash_value(&self, name: &str, value: &str) -> String {
+        self.signer.sign(&self.salt, format!("{}\n{}", name, value).as_bytes()).to_hex()
+    }
+
+    /// The exported form of `params`, sorted by name.
+    pub fn export(&self, params: &QueryMap) -> Vec<AnalyticsParam> {
+        let mut exported: Vec<AnalyticsParam> = params.iter().map(|(name, values)| AnalyticsParam {
+            name: name.clone(),
+            values: values.len(),
+            empty: values.iter().filter(|value| value.is_empty()).count(),
+            hashes: if self.is_hashed(name) {
+                Some(values.iter().map(|value| self.hash_value(name, value)).collect())
+            } else {
+                None
+            }
+        }).collect();
+        exported.sort_by(|a, b| a.name.cmp(&b.name));
+        exported
+    }
+
+    /// The exported form of `params` as a JSON array of objects with the
+    /// `name`, `values` and `empty` of each parameter, and its `hashes` when
+    /// it is hashed.
+    pub fn to_json(&self, params: &QueryMap) -> String {
+        let exported = self.export(params).into_iter().map(|param| {
+            let mut object = BTreeMap::new();
+            object.insert("name".to_owned(), Json::String(param.name));
+            object.insert("values".to_owned(), Json::U64(param.values as u64));
+            object.insert("empty".to_owned(), Json::U64(param.empty as u64));
+            if let Some(hashes) = param.hashes {
+                object.insert("hashes".to_owned(), Json::Array(hashes.into_iter().map(Json::String).collect()));
+            }
+            Json::Object(object)
+        }).collect();
+        json::encode(&Json::Array(exported)).unwrap_or_default()
+    }
+
+    /// The exported form of the submitted form: the body of
+    /// `application/x-www-form-urlencoded` requests, the query string
+    /// otherwise. Nothing is exported for an empty form.
+    #[cfg(feature = "iron")]
+    pub fn from_request(&self, req: &mut Request) -> Result<Vec<AnalyticsParam>, UrlDecodingError> {
+        match form_params(req) {
+            Ok(params) => Ok(self.export(&params)),
+            Err(UrlDecodingError::EmptyQuery) => Ok(Vec::new()),
+            Err(err) => Err(err)
+        }
+    }
+}
+
+#[test]
+fn test_analytics_exporter() {
+    use super::{parse_with, ParseOptions};
+
+    let params = parse_with("email=ann%40example.com&password=hunter2&utm_source=mail&tag=a&tag=",
+                            &ParseOptions::new()).unwrap();
+    let signer = |key: &[u8], message: &[u8]| -> Vec<u8> { [key, message].concat() };
+    let exporter = AnalyticsExporter::new(signer, b"salt").hash(&["email", "utm_*"]);
+
+    let exported = exporter.export(&params);
+    assert_eq!(exported.iter().map(|param| &*param.name).collect::<Vec<_>>(),
+               vec!["email", "password", "tag", "utm_source"]);
+    assert_eq!(exported[0].hashes, Some(vec![b"saltemail\nann@example.com".to_hex()]));
+    assert_eq!((exported[1].values, exported[1].hashes.clone()), (1, None));
+    assert_eq!((exported[2].values, exported[2].empty), (2, 1));
+    assert!(exported[3].hashes.is_some());
+
+    let json = exporter.to_json(&params);
+    assert!(json.starts_with(r#"[{"empty":0,"hashes":[""#) && json.contains(r#"{"empty":0,"name":"password","values":1}"#));
+    assert!(!json.contains("hunter2") && !json.contains("example.com"));
+}
@@ -0,0 +1,165 @@
+//! Flagging parameter values that look like attacks, for WAF-like
+//! middleware to log, alert on or block. Values are never changed.
+
+use std::fmt;
+use std::sync::Arc;
+
+#[cfg(feature = "iron")]
+use iron::prelude::*;
+#[cfg(feature = "iron")]
+use iron::BeforeMiddleware;
+#[cfg(feature = "iron")]
+use iron::typemap::Key;
+
+use super::QueryMap;
+#[cfg(feature = "iron")]
+use super::{UrlEncodedQuery, UrlEncodedBody, is_form};
+
+type Check = dyn Fn(&str) -> bool + Send + Sync;
+
+/// A value found suspicious by a `PayloadAnalyzer`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Finding {
+    /// The parameter the value was sent as.
+    pub key: String,
+    /// The name of the heuristic that matched.
+    pub heuristic: String,
+    /// The position of the value among the values of the parameter.
+    pub index: usize
+}
+
+/// The findings of the `PayloadAnalyzer` middleware on a request, in
+/// `req.extensions`: `req.extensions.get::<Findings>()`
+#[cfg(feature = "iron")]
+pub struct Findings;
+
+#[cfg(feature = "iron")]
+impl Key for Findings {
+    type Value = Vec<Finding>;
+}
+
+#[derive(Clone)]
+struct Heuristic {
+    name: String,
+    check: Arc<Check>
+}
+
+/// Runs heuristics over every parameter value, reporting the values that
+/// match as `Finding`s.
+///
+/// The heuristics are deliberately simple substring checks, meant to surface
+/// candidates for a closer look rather than to prove an attack: they have
+/// false positives (an `O'Brien` in a name looks like SQL) and are easy to
+/// evade. Validating and escaping values remains the defense.
+///
+/// As middleware, it analyzes the query and form body of each request and
+/// stores the findings as `Findings`, never failing the request.
+///
+/// ```ignore
+/// chain.link_before(PayloadAnalyzer::new().sql().script().path_traversal()
+///     .pattern("template", &["{{", "${"]));
+/// // Later:
+/// if let Some(findings) = req.extensions.get::<Findings>() { ... }
+/// ```
+#[derive(Clone, Default)]
+pub struct PayloadAnalyzer {
+    heuristics: Vec<Heuristic>
+}
+
+impl fmt::Debug for PayloadAnalyzer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.heuristics.iter().map(|heuristic| &heuristic.name)).finish()
+    }
+}
+
+impl PayloadAnalyzer {
+    /// An analyzer without heuristics.
+    pub fn new() -> PayloadAnalyzer {
+        PayloadAnalyzer::default()
+    }
+
+    /// Flag values for which `check` is true, as `name`.
+    pub fn custom<F>(mut self, name: &str, check: F) -> PayloadAnalyzer
+    where F: Fn(&str) -> bool + Send + Sync + 'static {
+        self.heuristics.push(Heuristic { name: name.to_owned(), check: Arc::new(check) });
+        self
+    }
+
+    /// Flag values containing one of `needles`, ignoring ASCII case, as `name`.
+    pub fn pattern(self, name: &str, needles: &[&str]) -> PayloadAnalyzer {
+        let needles: Vec<String> = needles.iter().map(|needle| needle.to_ascii_lowercase()).collect();
+        self.custom(name, move |value| {
+            let value = value.to_ascii_lowercase();
+            needles.iter().any(|needle| value.contains(&**needle))
+        })
+    }
+
+    /// Flag SQL meta-characters and keywords, as `sql`: quotes followed by
+    /// comments or boolean tricks, `union select` and the like.
+    pub fn sql(self) -> PayloadAnalyzer {
+        self.pattern("sql", &["'--", "' or ", "\" or ", "' and ", "';", "/*", "*/", "union select", "union all select",
+                              "; drop ", "xp_cmdshell", "sleep(", "benchmark("])
+    }
+
+    /// Flag HTML script injection, as `script`: `<script`, `javascript:`
+    /// URLs and inline event handlers.
+    pub fn script(self) -> PayloadAnalyzer {
+        self.pattern("script", &["<script", "</script", "javascript:", "vbscript:", "onerror=", "onload=",
+                                 "<iframe", "<svg"])
+    }
+
+    /// Flag path traversal sequences, as `path_traversal`, including once
+    /// more percent-encoded ones.
+    pub fn path_traversal(self) -> PayloadAnalyzer {
+        self.pattern("path_traversal", &["../", "..\\", "%2e%2e", "..%2f", "..%5c", "/etc/passwd", "\0"])
+    }
+
+    /// The suspicious values of `params`, by key and then by heuristic.
+    pub fn analyze(&self, params: &QueryMap) -> Vec<Finding> {
+        let mut keys: Vec<&String> = params.keys().collect();
+        keys.sort();
+
+        let mut findings = Vec::new();
+        for key in keys {
+            for (index, value) in params[key].iter().enumerate() {
+                for heuristic in self.heuristics.iter().filter(|heuristic| (heuristic.check)(value)) {
+                    findings.push(Finding { key: key.clone(), heuristic: heuristic.name.clone(), index });
+                }
+            }
+        }
+        findings
+    }
+}
+
+#[cfg(feature = "iron")]
+impl BeforeMiddleware for PayloadAnalyzer {
+    fn before(&self, req: &mut Request) -> IronResult<()> {
+        let mut findings = req.get::<UrlEncodedQuery>().map(|params| self.analyze(&params)).unwrap_or_default();
+        if is_form(req) {
+            if let Ok(params) = req.get::<UrlEncodedBody>() {
+                findings.extend(self.analyze(&params));
+            }
+        }
+        req.extensions.insert::<Findings>(findings);
+        Ok(())
+    }
+}
+
+#[test]
+fn test_payload_analyzer() {
+    use super::{parse_with, ParseOptions};
+
+    let analyzer = PayloadAnalyzer::new().sql().script().path_traversal().pattern("template", &["{{"]);
+    let params = parse_with("q=1%27+OR+%271%27%3D%271&file=..%2F..%2Fetc%2Fpasswd&name=Ann\
+                             &bio=%3CScript%3Ealert(1)%3C%2Fscript%3E&t=ok&t=%7B%7B7*7%7D%7D",
+                            &ParseOptions::new()).unwrap();
+
+    let found: Vec<String> = analyzer.analyze(&params).iter()
+        .map(|finding| format!("{}:{}:{}", finding.key, finding.heuristic, finding.index))
+        .collect();
+    assert_eq!(found, vec!["bio:script:0", "file:path_traversal:0", "q:sql:0", "t:template:1"]);
+
+    let custom = PayloadAnalyzer::new().custom("long", |value| value.len() > 5);
+    assert_eq!(custom.analyze(&params).len(), 4);
+    assert!(PayloadAnalyzer::new().analyze(&params).is_empty());
+}
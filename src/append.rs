@@ -0,0 +1,88 @@
+//! Adding parameters to the query of an existing URL, such as a redirect
+//! target or a pagination link, without getting `?` and `&` wrong.
+
+#[cfg(feature = "iron")]
+use iron::Url;
+
+use super::QueryMap;
+use super::encode::{EncodeOptions, encode_pairs_with, decode_component};
+
+/// What `append_query` does with parameters the query already has.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Duplicates {
+    /// Add the new values after the existing ones.
+    #[default]
+    Append,
+    /// Remove the existing values of the keys being added.
+    Replace,
+    /// Leave the keys the query already has alone, and only add the others.
+    KeepExisting
+}
+
+/// Add `params` to `query`, encoded with `options`. The pairs of `query` are
+/// kept as they were written, in their order, and the new ones follow.
+pub fn append_query(query: &str, params: &QueryMap, duplicates: Duplicates, options: &EncodeOptions) -> String {
+    let existing_key = |piece: &str| decode_component(piece.split('=').next().unwrap_or(""));
+    let mut pieces: Vec<String> = query.split('&')
+        .filter(|piece| !piece.is_empty())
+        .filter(|piece| duplicates != Duplicates::Replace || !params.contains_key(&existing_key(piece)))
+        .map(|piece| piece.to_owned())
+        .collect();
+
+    let keys: Vec<String> = query.split('&').map(existing_key).collect();
+    let added = params.iter()
+        .filter(|&(key, _)| duplicates != Duplicates::KeepExisting || !keys.contains(key))
+        .flat_map(|(key, values)| values.iter().map(move |value| (&**key, &**value)));
+    let added = encode_pairs_with(added, options);
+    if !added.is_empty() {
+        pieces.push(added);
+    }
+    pieces.join("&")
+}
+
+/// Add `params` to the query of `url` like `encode` would write them,
+/// after its existing parameters.
+///
+/// ```ignore
+/// let mut next = req.url.clone();
+/// append_params(&mut next, &page_params);
+/// ```
+#[cfg(feature = "iron")]
+pub fn append_params(url: &mut Url, params: &QueryMap) {
+    append_params_with(url, params, Duplicates::Append, &EncodeOptions::new())
+}
+
+/// Add `params` to the query of `url`, with the given policy for the keys it
+/// already has and encoded with `options`. A query left empty is removed.
+#[cfg(feature = "iron")]
+pub fn append_params_with(url: &mut Url, params: &QueryMap, duplicates: Duplicates, options: &EncodeOptions) {
+    let query = append_query(url.query.as_deref().unwrap_or(""), params, duplicates, options);
+    url.query = if query.is_empty() { None } else { Some(query) };
+}
+
+#[test]
+fn test_append_query() {
+    let mut params = QueryMap::new();
+    params.insert("page".to_owned(), vec!["2".to_owned()]);
+    params.insert("q".to_owned(), vec!["a b".to_owned()]);
+    let options = EncodeOptions::new();
+
+    assert_eq!(append_query("", &params, Duplicates::Append, &options), "page=2&q=a+b");
+    assert_eq!(append_query("page=1&sort=new&", &params, Duplicates::Append, &options),
+               "page=1&sort=new&page=2&q=a+b");
+    assert_eq!(append_query("p%61ge=1&sort=new", &params, Duplicates::Replace, &options), "sort=new&page=2&q=a+b");
+    assert_eq!(append_query("page=1&q", &params, Duplicates::KeepExisting, &options), "page=1&q");
+
+    #[cfg(feature = "iron")]
+    {
+        let mut url = Url::parse("http://example.com/list?sort=new#top").unwrap();
+        append_params(&mut url, &params);
+        assert_eq!(url.query.as_deref(), Some("sort=new&page=2&q=a+b"));
+        append_params_with(&mut url, &params, Duplicates::Replace, &options);
+        assert_eq!(url.to_string(), "http://example.com:80/list?sort=new&page=2&q=a+b#top");
+
+        let mut url = Url::parse("http://example.com/?page=1").unwrap();
+        append_params_with(&mut url, &QueryMap::new(), Duplicates::Replace, &options);
+        assert_eq!(url.query.as_deref(), Some("page=1"));
+    }
+}
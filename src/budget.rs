@@ -0,0 +1,260 @@
+//! A budget on the work parsing a form takes, for forms that stay within the
+//! size limits yet are pathologically expensive, and the reporting of the
+//! forms that come close to it.
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::UrlDecodingError;
+
+/// A resource a `ParseBudget` limits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BudgetResource {
+    /// The time spent parsing.
+    Time,
+    /// The bytes of the decoded keys and values.
+    DecodedBytes,
+    /// The estimated number of allocations.
+    Allocations
+}
+
+impl fmt::Display for BudgetResource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            BudgetResource::Time => "time",
+            BudgetResource::DecodedBytes => "decoded bytes",
+            BudgetResource::Allocations => "allocations"
+        })
+    }
+}
+
+/// What parsing a form cost, as passed to the hook of a `ParseBudget`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParseCost {
+    /// The pairs decoded.
+    pub pairs: usize,
+    /// The bytes of the decoded keys and values.
+    pub decoded_bytes: usize,
+    /// An estimate of the allocations made: one for each key and value, and
+    /// those growing the list of pairs.
+    pub allocations: usize,
+    /// The time spent parsing.
+    pub elapsed: Duration,
+    /// The resource whose limit was exceeded, when parsing was stopped.
+    pub exceeded: Option<BudgetResource>
+}
+
+type Report = dyn Fn(&ParseCost) + Send + Sync;
+
+/// Limits on the work of parsing one form, checked as pairs are decoded,
+/// and a hook to report the forms coming close to them.
+///
+/// The budget covers urlencoded data and the fields of multipart bodies, whose
+/// files are limited by `MultipartOptions` instead. Parsing stops with
+/// `ParseBudgetExceeded` as soon as a limit is passed.
+/// The hook is called with the cost of every form that used more than a
+/// given fraction of one of the limits, and of every form that was stopped,
+/// so operators can find the clients sending them. For reporting alone, set
+/// generous limits and a small fraction.
+///
+/// ```ignore
+/// let budget = ParseBudget::new()
+///     .max_time(Duration::from_millis(20))
+///     .max_allocations(10_000)
+///     .report_above(0.5, |cost| warn!("expensive form: {:?}", cost));
+/// let options = ParseOptions::new().budget(budget);
+/// ```
+#[derive(Clone, Default)]
+pub struct ParseBudget {
+    max_time: Option<Duration>,
+    max_decoded_bytes: Option<usize>,
+    max_allocations: Option<usize>,
+    report: Option<(f64, Arc<Report>)>
+}
+
+impl ParseBudget {
+    /// A budget without limits or reporting.
+    pub fn new() -> ParseBudget {
+        ParseBudget::default()
+    }
+
+    /// Stop parsing once it took more than `max`.
+    ///
+    /// Not available on `wasm32-unknown-unknown`, which has no clock.
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub fn max_time(mut self, max: Duration) -> ParseBudget {
+        self.max_time = Some(max);
+        self
+    }
+
+    /// Stop parsing once the decoded keys and values take more than `max`
+    /// bytes.
+    pub fn max_decoded_bytes(mut self, max: usize) -> ParseBudget {
+        self.max_decoded_bytes = Some(max);
+        self
+    }
+
+    /// Stop parsing once it made an estimated `max` allocations.
+    pub fn max_allocations(mut self, max: usize) -> ParseBudget {
+        self.max_allocations = Some(max);
+        self
+    }
+
+    /// Call `hook` with the cost of the forms using more than `fraction` of
+    /// one of the limits, such as `0.5` for half, and of the forms that
+    /// exceeded one.
+    pub fn report_above<F>(mut self, fraction: f64, hook: F) -> ParseBudget
+    where F: Fn(&ParseCost) + Send + Sync + 'static {
+        self.report = Some((fraction, Arc::new(hook)));
+        self
+    }
+
+    fn is_active(&self) -> bool {
+        self.max_time.is_some() || self.max_decoded_bytes.is_some() || self.max_allocations.is_some()
+    }
+
+    /// The first resource of `cost` above `fraction` of its limit.
+    fn above(&self, cost: &ParseCost, fraction: f64) -> Option<BudgetResource> {
+        let time = self.max_time.is_some_and(|max| cost.elapsed.as_secs_f64() > max.as_secs_f64() * fraction);
+        let bytes = self.max_decoded_bytes.is_some_and(|max| cost.decoded_bytes as f64 > max as f64 * fraction);
+        let allocations = self.max_allocations.is_some_and(|max| cost.allocations as f64 > max as f64 * fraction);
+        match (time, bytes, allocations) {
+            (true, _, _) => Some(BudgetResource::Time),
+            (_, true, _) => Some(BudgetResource::DecodedBytes),
+            (_, _, true) => Some(BudgetResource::Allocations),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Debug for ParseBudget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ParseBudget")
+            .field("max_time", &self.max_time)
+            .field("max_decoded_bytes", &self.max_decoded_bytes)
+            .field("max_allocations", &self.max_allocations)
+            .field("report_above", &self.report.as_ref().map(|report| report.0))
+            .finish()
+    }
+}
+
+/// Budgets are equal with the same limits and the same hook.
+impl PartialEq for ParseBudget {
+    fn eq(&self, other: &ParseBudget) -> bool {
+        let same_report = match (&self.report, &other.report) {
+            (Some((fraction, hook)), Some((other_fraction, other_hook))) =>
+                fraction == other_fraction && Arc::ptr_eq(hook, other_hook),
+            (None, None) => true,
+            _ => false
+        };
+        self.max_time == other.max_time && self.max_decoded_bytes == other.max_decoded_bytes &&
+            self.max_allocations == other.max_allocations && same_report
+    }
+}
+
+/// The cost of one parse so far, checked against its budget as pairs are
+/// decoded. The time counts while the meter runs: from its creation, or
+/// between `resume` and `pause` for parsers fed over time. The clock is only
+/// read for budgets with a `max_time`.
+#[derive(Clone, Debug)]
+pub struct Meter {
+    budget: ParseBudget,
+    cost: ParseCost,
+    running: Option<Instant>
+}
+
+impl Meter {
+    /// A running meter for `budget`.
+    pub fn new(budget: &ParseBudget) -> Meter {
+        let running = if budget.max_time.is_some() { Some(Instant::now()) } else { None };
+        Meter { budget: budget.clone(), cost: ParseCost::default(), running }
+    }
+
+    /// Start counting time again.
+    pub fn resume(&mut self) {
+        if self.budget.max_time.is_some() {
+            self.running = Some(Instant::now());
+        }
+    }
+
+    /// Stop counting time, until the next `resume`.
+    pub fn pause(&mut self) {
+        if let Some(start) = self.running.take() {
+            self.cost.elapsed += start.elapsed();
+        }
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.cost.elapsed + self.running.map_or(Duration::from_secs(0), |start| start.elapsed())
+    }
+
+    /// Count a decoded pair, failing once the budget is exceeded.
+    pub fn charge(&mut self, key: &str, value: &str) -> Result<(), UrlDecodingError> {
+        if !self.budget.is_active() {
+            return Ok(());
+        }
+
+        self.cost.pairs += 1;
+        self.cost.decoded_bytes += key.len() + value.len();
+        // The key, the value, and the list of pairs doubling in size.
+        self.cost.allocations += if self.cost.pairs.is_power_of_two() { 3 } else { 2 };
+
+        let mut cost = ParseCost { elapsed: self.elapsed(), ..self.cost.clone() };
+        match self.budget.above(&cost, 1.0) {
+            Some(resource) => {
+                cost.exceeded = Some(resource);
+                if let Some((_, ref hook)) = self.budget.report {
+                    hook(&cost);
+                }
+                Err(UrlDecodingError::ParseBudgetExceeded(resource))
+            },
+            None => Ok(())
+        }
+    }
+
+    /// Report the cost of a parse that completed, if it was above the
+    /// fraction of the hook.
+    pub fn finish(&mut self) {
+        self.pause();
+        if let Some((fraction, ref hook)) = self.budget.report {
+            if self.budget.above(&self.cost, fraction).is_some() {
+                hook(&self.cost);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_parse_budget() {
+    use std::sync::Mutex;
+    use super::{parse_with, FormParser, ParseOptions};
+
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let sink = reports.clone();
+    let budget = ParseBudget::new()
+        .max_decoded_bytes(20)
+        .max_allocations(12)
+        .report_above(0.5, move |cost: &ParseCost| sink.lock().unwrap().push(cost.clone()));
+    let options = ParseOptions::new().budget(budget.clone());
+
+    assert!(parse_with("a=1&b=2", &options).is_ok());
+    assert!(reports.lock().unwrap().is_empty());
+
+    assert!(parse_with("name=Ada+Lovelace", &options).is_ok());
+    assert_eq!(reports.lock().unwrap().pop().map(|cost| (cost.decoded_bytes, cost.exceeded)), Some((16, None)));
+
+    assert!(matches!(parse_with("a&b&c&d&e&f", &options),
+                     Err(UrlDecodingError::ParseBudgetExceeded(BudgetResource::Allocations))));
+    let stopped = reports.lock().unwrap().pop().unwrap();
+    assert_eq!((stopped.pairs, stopped.exceeded), (5, Some(BudgetResource::Allocations)));
+
+    let mut parser = FormParser::new(options);
+    assert!(parser.feed(b"note=0123456789&").is_ok());
+    assert!(matches!(parser.feed(b"more=0123456789&"),
+                     Err(UrlDecodingError::ParseBudgetExceeded(BudgetResource::DecodedBytes))));
+    assert!(Meter::new(&budget).running.is_none());
+    assert!(Meter::new(&ParseBudget::new().max_time(Duration::from_secs(1))).running.is_some());
+    assert_eq!(budget, budget.clone());
+    assert!(budget != ParseBudget::new());
+}
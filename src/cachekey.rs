@@ -0,0 +1,102 @@
+//! Cache keys and ETags for responses depending on some parameters only.
+
+#[cfg(feature = "iron")]
+use iron::prelude::*;
+#[cfg(feature = "iron")]
+use plugin::Pluggable;
+
+use super::QueryMap;
+#[cfg(feature = "iron")]
+use super::{UrlDecodingError, UrlEncodedQuery};
+use super::encode::{EncodeOptions, EncodeSet, Spaces, encode_pairs_with};
+use super::ratelimit::fnv1a;
+
+/// Derives a cache key, or the value of an `ETag`, from the parameters a
+/// response depends on, ignoring the others.
+///
+/// The parameters are canonicalized first, as `canonical_url` does: they are
+/// decoded, sorted by key and encoded with `rfc3986_encode`, so requests
+/// only differing in how their query was written share a key. The values of a
+/// key keep their order, unless they are sorted too.
+///
+/// ```ignore
+/// let listing = CacheKey::new(&["page", "sort"]);
+/// let etag = listing.etag(req.get_ref::<UrlEncodedQuery>()?);
+/// ```
+#[derive(Clone, Debug)]
+pub struct CacheKey {
+    params: Vec<String>,
+    sort_values: bool
+}
+
+impl CacheKey {
+    /// Keys from the values of `params`.
+    pub fn new(params: &[&str]) -> CacheKey {
+        let mut params: Vec<String> = params.iter().map(|param| (*param).to_owned()).collect();
+        params.sort();
+        params.dedup();
+        CacheKey { params, sort_values: false }
+    }
+
+    /// Also sort the values of each parameter, when their order doesn't
+    /// change the response.
+    pub fn sort_values(mut self, enabled: bool) -> CacheKey {
+        self.sort_values = enabled;
+        self
+    }
+
+    /// The configured parameters of `params` in their canonical form, which
+    /// the key is the hash of.
+    pub fn canonical(&self, params: &QueryMap) -> String {
+        let mut pairs: Vec<(&str, &str)> = Vec::new();
+        for param in &self.params {
+            let start = pairs.len();
+            pairs.extend(params.get(param).into_iter().flatten().map(|value| (&**param, &**value)));
+            if self.sort_values {
+                pairs[start..].sort();
+            }
+        }
+        let options = EncodeOptions::new().encode_set(EncodeSet::rfc3986()).spaces(Spaces::Percent);
+        encode_pairs_with(pairs, &options)
+    }
+
+    /// The key of `params`, as 16 hex digits of a 64-bit FNV-1a hash.
+    pub fn derive(&self, params: &QueryMap) -> String {
+        format!("{:016x}", fnv1a(self.canonical(params).as_bytes()))
+    }
+
+    /// The key of `params` as a strong entity tag, quoted as the `ETag`
+    /// header wants it.
+    pub fn etag(&self, params: &QueryMap) -> String {
+        format!("\"{}\"", self.derive(params))
+    }
+
+    /// The key of the query string of `req`. Requests without a query have
+    /// the key of no parameters.
+    #[cfg(feature = "iron")]
+    pub fn from_request(&self, req: &mut Request) -> Result<String, UrlDecodingError> {
+        match req.get_ref::<UrlEncodedQuery>() {
+            Ok(params) => Ok(self.derive(params)),
+            Err(UrlDecodingError::EmptyQuery) => Ok(self.derive(&QueryMap::new())),
+            Err(err) => Err(err)
+        }
+    }
+}
+
+#[test]
+fn test_cache_key() {
+    use super::{parse_with, ParseOptions};
+
+    let parse = |data: &str| parse_with(data, &ParseOptions::new()).unwrap();
+    let listing = CacheKey::new(&["sort", "page", "tag"]);
+
+    assert_eq!(listing.canonical(&parse("tag=b&page=2&utm_source=x&tag=a+c")), "page=2&tag=b&tag=a%20c");
+    let key = listing.derive(&parse("page=2&sort=new&session=1"));
+    assert_eq!(key.len(), 16);
+    assert_eq!(listing.derive(&parse("sort=n%65w&page=2&session=2")), key);
+    assert!(listing.derive(&parse("page=3&sort=new")) != key);
+    assert_eq!(listing.etag(&parse("page=2&sort=new")), format!("\"{}\"", key));
+
+    let unordered = listing.sort_values(true);
+    assert_eq!(unordered.derive(&parse("tag=b&tag=a")), unordered.derive(&parse("tag=a&tag=b")));
+}
@@ -0,0 +1,165 @@
+//! Canonical forms of parameters, used wherever two differently written but
+//! equivalent parameter lists must produce the same string.
+
+#[cfg(feature = "iron")]
+use iron::prelude::*;
+
+use super::ParseOptions;
+use super::encode::{EncodeOptions, EncodeSet, Spaces, encode_pairs_with};
+use super::parse::decode_pairs;
+use super::tracking::{TRACKING_PARAMS, glob_match};
+
+/// Percent-encode everything but the RFC 3986 unreserved characters
+/// (`A-Z a-z 0-9 - . _ ~`). Spaces become `%20`.
+pub fn rfc3986_encode(input: &str) -> String {
+    let mut encoded = String::with_capacity(input.len());
+
+    for &byte in input.as_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' =>
+                encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte))
+        }
+    }
+
+    encoded
+}
+
+/// Encode every pair with `rfc3986_encode`, sort them by key and then by value,
+/// and join them as `k=v&k2=v2`.
+pub fn canonical_query<'a, I>(pairs: I) -> String
+where I: IntoIterator<Item = (&'a str, &'a str)> {
+    let mut encoded: Vec<(String, String)> = pairs.into_iter()
+        .map(|(key, value)| (rfc3986_encode(key), rfc3986_encode(value)))
+        .collect();
+    encoded.sort();
+
+    encoded.iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// How `canonical_url` normalizes the query of a URL.
+#[derive(Clone, Debug)]
+pub struct CanonicalRules {
+    strip: Vec<String>,
+    only: Option<Vec<String>>,
+    drop_empty: bool,
+    sort_values: bool,
+    encoding: EncodeOptions
+}
+
+impl Default for CanonicalRules {
+    fn default() -> CanonicalRules {
+        CanonicalRules {
+            strip: TRACKING_PARAMS.iter().map(|pattern| (*pattern).to_owned()).collect(),
+            only: None,
+            drop_empty: false,
+            sort_values: false,
+            encoding: EncodeOptions::new().encode_set(EncodeSet::rfc3986()).spaces(Spaces::Percent)
+        }
+    }
+}
+
+impl CanonicalRules {
+    /// Remove `TRACKING_PARAMS`, sort the keys, keep the values of a key in
+    /// their order and encode everything with `rfc3986_encode`.
+    pub fn new() -> CanonicalRules {
+        CanonicalRules::default()
+    }
+
+    /// Remove the keys matching `patterns` instead of `TRACKING_PARAMS`.
+    pub fn strip(mut self, patterns: &[&str]) -> CanonicalRules {
+        self.strip = patterns.iter().map(|pattern| (*pattern).to_owned()).collect();
+        self
+    }
+
+    /// Also remove the keys matching `patterns`.
+    pub fn also_strip(mut self, patterns: &[&str]) -> CanonicalRules {
+        self.strip.extend(patterns.iter().map(|pattern| (*pattern).to_owned()));
+        self
+    }
+
+    /// Remove every key but `keys`, for pages only depending on a few
+    /// parameters.
+    pub fn only(mut self, keys: &[&str]) -> CanonicalRules {
+        self.only = Some(keys.iter().map(|key| (*key).to_owned()).collect());
+        self
+    }
+
+    /// Remove pairs with an empty value, so `?q=` and no query are the same.
+    pub fn drop_empty(mut self, enabled: bool) -> CanonicalRules {
+        self.drop_empty = enabled;
+        self
+    }
+
+    /// Also sort the values of each key, when their order doesn't matter.
+    pub fn sort_values(mut self, enabled: bool) -> CanonicalRules {
+        self.sort_values = enabled;
+        self
+    }
+
+    /// Encode with `options` instead of `rfc3986_encode`. Keys are still
+    /// sorted, unless `options` has a `KeyOrder::Custom`.
+    pub fn encoding(mut self, options: EncodeOptions) -> CanonicalRules {
+        self.encoding = options;
+        self
+    }
+
+    fn keeps(&self, key: &str, value: &str) -> bool {
+        !self.strip.iter().any(|pattern| glob_match(pattern, key))
+            && self.only.as_ref().is_none_or(|only| only.iter().any(|kept| kept == key))
+            && !(self.drop_empty && value.is_empty())
+    }
+}
+
+/// The canonical form of an encoded `query` under `rules`: the same string
+/// for queries only differing in tracking parameters, key order or encoding.
+pub fn canonical_query_with(query: &str, rules: &CanonicalRules) -> String {
+    // The default options have no limits, so decoding can't fail.
+    let mut pairs = decode_pairs(query, &ParseOptions::new()).unwrap_or_default();
+    pairs.retain(|(key, value)| rules.keeps(key, value));
+    if rules.sort_values {
+        pairs.sort();
+    } else {
+        pairs.sort_by(|a, b| a.0.cmp(&b.0));
+    }
+    encode_pairs_with(pairs.iter().map(|(key, value)| (&**key, &**value)), &rules.encoding)
+}
+
+/// A stable string for the URL of `req`, for HTTP cache and deduplication
+/// keys: the scheme and host in lowercase, without the default port and the
+/// fragment, and the query in its canonical form under `rules`.
+#[cfg(feature = "iron")]
+pub fn canonical_url(req: &Request, rules: &CanonicalRules) -> String {
+    let url = &req.url;
+    let scheme = url.scheme.to_lowercase();
+    let mut canonical = format!("{}://{}", scheme, url.host.to_string().to_lowercase());
+    if !matches!((&*scheme, url.port), ("http", 80) | ("https", 443)) {
+        canonical.push_str(&format!(":{}", url.port));
+    }
+    canonical.push('/');
+    canonical.push_str(&url.path.join("/"));
+
+    let query = canonical_query_with(url.query.as_deref().unwrap_or(""), rules);
+    if !query.is_empty() {
+        canonical.push('?');
+        canonical.push_str(&query);
+    }
+    canonical
+}
+
+#[test]
+fn test_canonical_query() {
+    assert_eq!(rfc3986_encode("a b+c~/é"), "a%20b%2Bc~%2F%C3%A9");
+
+    let pairs = vec![("b", "2"), ("a", "z"), ("a", "y y")];
+    assert_eq!(canonical_query(pairs), "a=y%20y&a=z&b=2");
+
+    let rules = CanonicalRules::new();
+    assert_eq!(canonical_query_with("q=a+b&utm_source=x&page=2&q=%61", &rules), "page=2&q=a%20b&q=a");
+    let rules = rules.only(&["q"]).sort_values(true).drop_empty(true);
+    assert_eq!(canonical_query_with("q=b&page=2&q=a&q=", &rules), "q=a&q=b");
+    assert_eq!(canonical_query_with("q=", &rules), "");
+}
@@ -0,0 +1,77 @@
+//! An opt-in pass turning the strings of a nested tree into typed, JSON-like
+//! values, as Rails params and qs-style parsers do.
+
+use rustc_serialize::json::Json;
+
+use super::nested::{NestedValue, NestedQueryMap};
+
+/// Convert a nested tree into a `Json` object, inferring the type of every
+/// value with `coerce_value`.
+///
+/// The tree itself stays stringly typed; coercion only happens on request,
+/// since guessing types loses information a strict handler may need.
+pub fn coerce(tree: NestedQueryMap) -> Json {
+    Json::Object(tree.into_iter().map(|(key, value)| (key, coerce_node(value))).collect())
+}
+
+fn coerce_node(value: NestedValue) -> Json {
+    match value {
+        NestedValue::Value(value) => coerce_value(&value),
+        NestedValue::List(items) => Json::Array(items.into_iter().map(coerce_node).collect()),
+        NestedValue::Map(entries) => coerce(entries)
+    }
+}
+
+/// Infer the type of a single value:
+///
+/// - `""` is `null`,
+/// - `true` and `false` are booleans,
+/// - integers such as `42` or `-7` that fit in an `i64` are integers,
+/// - decimals such as `3.14` are floats,
+/// - everything else stays a string.
+///
+/// Numbers with leading zeros, exponents or a leading `+`, such as zip codes
+/// or phone numbers, stay strings so no digits are lost.
+pub fn coerce_value(value: &str) -> Json {
+    match value {
+        "" => return Json::Null,
+        "true" => return Json::Boolean(true),
+        "false" => return Json::Boolean(false),
+        _ => ()
+    }
+
+    let unsigned = value.strip_prefix('-').unwrap_or(value);
+    let (integer, fraction) = match unsigned.find('.') {
+        Some(position) => (&unsigned[..position], Some(&unsigned[position + 1..])),
+        None => (unsigned, None)
+    };
+    let is_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    let canonical_integer = is_digits(integer) && (integer == "0" || !integer.starts_with('0'));
+
+    match fraction {
+        None if canonical_integer => value.parse().map(Json::I64).unwrap_or_else(|_| Json::String(value.to_owned())),
+        Some(fraction) if canonical_integer && is_digits(fraction) =>
+            value.parse().map(Json::F64).unwrap_or_else(|_| Json::String(value.to_owned())),
+        _ => Json::String(value.to_owned())
+    }
+}
+
+#[test]
+fn test_coerce() {
+    use super::{parse_nested, ParseOptions, NestedOptions};
+
+    assert_eq!(coerce_value(""), Json::Null);
+    assert_eq!(coerce_value("true"), Json::Boolean(true));
+    assert_eq!(coerce_value("42"), Json::I64(42));
+    assert_eq!(coerce_value("-7"), Json::I64(-7));
+    assert_eq!(coerce_value("2.5"), Json::F64(2.5));
+    assert_eq!(coerce_value("007"), Json::String("007".to_owned()));
+    assert_eq!(coerce_value("1e5"), Json::String("1e5".to_owned()));
+    assert_eq!(coerce_value("1."), Json::String("1.".to_owned()));
+    assert_eq!(coerce_value("99999999999999999999"), Json::String("99999999999999999999".to_owned()));
+
+    let tree = parse_nested("user[age]=42&user[admin]=false&tags[]=a&tags[]=&zip=01234",
+                            &ParseOptions::new(), &NestedOptions::new()).unwrap();
+    assert_eq!(coerce(tree).to_string(),
+               "{\"tags\":[\"a\",null],\"user\":{\"admin\":false,\"age\":42},\"zip\":\"01234\"}");
+}
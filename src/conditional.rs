@@ -0,0 +1,106 @@
+//! Answering conditional GETs of search and filter pages, whose content only
+//! depends on their query and on the data they list.
+
+use std::fmt;
+use std::sync::Arc;
+
+use iron::prelude::*;
+use iron::AfterMiddleware;
+use iron::headers::{ETag, EntityTag, IfNoneMatch};
+use iron::method::Method;
+use iron::status;
+
+use super::canonical::{CanonicalRules, canonical_query_with};
+use super::ratelimit::fnv1a;
+
+type Version = dyn Fn(&Request) -> String + Send + Sync;
+
+/// `AfterMiddleware` giving the successful responses to `GET` and `HEAD`
+/// requests an `ETag` derived from their canonical query and a version of
+/// the content, and answering `304 Not Modified` when the client already has
+/// that tag in `If-None-Match`.
+///
+/// The version tells the content apart for a same query: a timestamp or
+/// counter of the last change of the data listed, computed for each request.
+/// The handler still runs, so this saves bandwidth rather than work.
+///
+/// ```ignore
+/// let mut chain = Chain::new(search);
+/// chain.link_after(ConditionalGet::new(move |_| catalog.revision().to_string()));
+/// ```
+#[derive(Clone)]
+pub struct ConditionalGet {
+    version: Arc<Version>,
+    rules: CanonicalRules
+}
+
+impl fmt::Debug for ConditionalGet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ConditionalGet").field("rules", &self.rules).finish()
+    }
+}
+
+impl ConditionalGet {
+    /// Tag responses with their canonical query, under the default
+    /// `CanonicalRules`, and the version `version` computes for the request.
+    pub fn new<F>(version: F) -> ConditionalGet
+    where F: Fn(&Request) -> String + Send + Sync + 'static {
+        ConditionalGet { version: Arc::new(version), rules: CanonicalRules::new() }
+    }
+
+    /// Canonicalize queries with `rules`, for instance to only keep the
+    /// parameters the page depends on.
+    pub fn rules(mut self, rules: CanonicalRules) -> ConditionalGet {
+        self.rules = rules;
+        self
+    }
+
+    /// The strong tag of a query for a version of the content.
+    fn etag(&self, query: &str, version: &str) -> EntityTag {
+        let canonical = canonical_query_with(query, &self.rules);
+        EntityTag::strong(format!("{:016x}", fnv1a(format!("{}\n{}", version, canonical).as_bytes())))
+    }
+}
+
+/// Whether the client already has `etag`, comparing weakly as RFC 7232 asks
+/// for `If-None-Match`.
+fn is_fresh(if_none_match: Option<&IfNoneMatch>, etag: &EntityTag) -> bool {
+    match if_none_match {
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(etag)),
+        None => false
+    }
+}
+
+impl AfterMiddleware for ConditionalGet {
+    fn after(&self, req: &mut Request, mut res: Response) -> IronResult<Response> {
+        let cacheable = matches!(req.method, Method::Get | Method::Head)
+            && res.status.unwrap_or(status::Ok) == status::Ok;
+        if !cacheable {
+            return Ok(res);
+        }
+
+        let etag = self.etag(req.url.query.as_deref().unwrap_or(""), &(self.version)(req));
+        if is_fresh(req.headers.get::<IfNoneMatch>(), &etag) {
+            res.status = Some(status::NotModified);
+            res.body = None;
+        }
+        res.headers.set(ETag(etag));
+        Ok(res)
+    }
+}
+
+#[test]
+fn test_conditional_get() {
+    let conditional = ConditionalGet::new(|_| String::new());
+    let etag = conditional.etag("q=rust&page=2&utm_source=news", "v1");
+    assert_eq!(conditional.etag("page=2&q=r%75st", "v1"), etag);
+    assert!(conditional.etag("page=2&q=rust", "v2") != etag);
+    assert!(conditional.etag("page=3&q=rust", "v1") != etag);
+
+    let weak = EntityTag::weak(etag.tag().to_owned());
+    assert!(is_fresh(Some(&IfNoneMatch::Items(vec![EntityTag::strong("x".to_owned()), weak])), &etag));
+    assert!(is_fresh(Some(&IfNoneMatch::Any), &etag));
+    assert!(!is_fresh(Some(&IfNoneMatch::Items(vec![EntityTag::strong("x".to_owned())])), &etag));
+    assert!(!is_fresh(None, &etag));
+}
@@ -0,0 +1,250 @@
+//! Configuration for the urlencoded plugins.
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use iron::prelude::*;
+use iron::method::Method;
+use iron::method::Method::*;
+use iron::typemap::Key;
+use iron::BeforeMiddleware;
+
+use std::io::Read;
+
+use super::{ParseOptions, QueryResult, UrlDecodingError, parse_with};
+use super::parse::parse_piecewise;
+use super::multipart::{MultipartForm, MultipartOptions, parse_multipart_with};
+use super::transform::{Transform, Transforms};
+use super::messages::English;
+use super::problem::Problem;
+
+/// Which request methods `UrlEncodedBody` will read a form body for.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub enum BodyMethods {
+    /// Parse a body whatever the request method is. This is the default.
+    #[default]
+    Any,
+    /// Only parse bodies sent with one of these methods.
+    Only(Vec<Method>)
+}
+
+impl BodyMethods {
+    /// `POST`, `PUT`, `PATCH` and `DELETE`: every method that can carry a form
+    /// body, leaving out `GET` and `HEAD`.
+    pub fn with_body() -> BodyMethods {
+        BodyMethods::Only(vec![Post, Put, Patch, Delete])
+    }
+
+    /// Whether a body sent with `method` should be parsed.
+    pub fn allows(&self, method: &Method) -> bool {
+        match *self {
+            BodyMethods::Any => true,
+            BodyMethods::Only(ref methods) => methods.contains(method)
+        }
+    }
+}
+
+type Responder = dyn Fn(&UrlDecodingError) -> Response + Send + Sync;
+
+#[derive(Clone)]
+struct ErrorResponder(Arc<Responder>);
+
+impl fmt::Debug for ErrorResponder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ErrorResponder")
+    }
+}
+
+/// Configuration for `UrlEncodedQuery` and `UrlEncodedBody`.
+///
+/// Link it into your chain before your handler and the plugins will pick it up:
+///
+/// ```ignore
+/// chain.link_before(UrlEncodedConfig::new().body_methods(BodyMethods::with_body()));
+/// ```
+///
+/// Requests that never went through the middleware use the default configuration.
+#[derive(Clone, Debug, Default)]
+pub struct UrlEncodedConfig {
+    body_methods: BodyMethods,
+    options: ParseOptions,
+    transforms: Transforms,
+    multipart: MultipartOptions,
+    read_timeout: Option<Duration>,
+    spool_threshold: Option<usize>,
+    error_response: Option<ErrorResponder>
+}
+
+impl UrlEncodedConfig {
+    /// Create the default configuration.
+    pub fn new() -> UrlEncodedConfig {
+        UrlEncodedConfig::default()
+    }
+
+    /// Set which request methods `UrlEncodedBody` reads bodies for.
+    pub fn body_methods(mut self, methods: BodyMethods) -> UrlEncodedConfig {
+        self.body_methods = methods;
+        self
+    }
+
+    /// Set the limits both plugins parse with.
+    pub fn parse_options(mut self, options: ParseOptions) -> UrlEncodedConfig {
+        self.options = options;
+        self
+    }
+
+    /// Set the limits and storage of `multipart/form-data` bodies.
+    pub fn multipart_options(mut self, options: MultipartOptions) -> UrlEncodedConfig {
+        self.multipart = options;
+        self
+    }
+
+    /// Fail with `BodyReadTimeout` when reading a body takes longer than
+    /// `timeout`, so clients trickling their body in can't hold a handler
+    /// thread for long.
+    pub fn read_timeout(mut self, timeout: Duration) -> UrlEncodedConfig {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Spool urlencoded bodies bigger than `threshold` bytes to a file in the
+    /// multipart spool directory, and parse them from there one pair at a
+    /// time, for endpoints taking huge form posts such as bulk imports.
+    ///
+    /// The parse options still apply, `max_pairs` first of all, since every
+    /// pair ends up in memory. Spooled bodies aren't kept as `RawFormBody`.
+    pub fn spool_bodies(mut self, threshold: usize) -> UrlEncodedConfig {
+        self.spool_threshold = Some(threshold);
+        self
+    }
+
+    /// Normalize every value of `key` with `transform` as it is parsed, after
+    /// the transforms already registered for that key.
+    pub fn transform(mut self, key: &str, transform: Transform) -> UrlEncodedConfig {
+        self.transforms.add(key, transform);
+        self
+    }
+
+    /// Normalize every value of every key with `transform` as it is parsed,
+    /// before the transforms of each key: `Transform::Trim` and
+    /// `Transform::CollapseWhitespace` for forms made of text fields. The
+    /// values as sent remain in `RawQuery` and `RawFormBody`.
+    pub fn transform_all(mut self, transform: Transform) -> UrlEncodedConfig {
+        self.transforms.add_all(transform);
+        self
+    }
+
+    /// Build the response sent for decoding errors with `responder`, for
+    /// example to render an HTML error page or a custom JSON shape.
+    ///
+    /// It is used by `ParseEagerly` and `UrlDecodingError::into_iron_error`.
+    /// Without one, errors are answered with a `Problem` in English.
+    pub fn error_response<F>(mut self, responder: F) -> UrlEncodedConfig
+    where F: Fn(&UrlDecodingError) -> Response + Send + Sync + 'static {
+        self.error_response = Some(ErrorResponder(Arc::new(responder)));
+        self
+    }
+
+    /// The response for `error`, as built by the configured responder.
+    pub fn response_for(&self, error: &UrlDecodingError) -> Response {
+        match self.error_response {
+            Some(ref responder) => (responder.0)(error),
+            None => Problem::new(error, &English).into_response()
+        }
+    }
+
+    /// Turn `error` into an `IronError` answered with `response_for`.
+    pub fn iron_error(&self, error: UrlDecodingError) -> IronError {
+        let response = self.response_for(&error);
+        IronError { error: Box::new(error), response }
+    }
+
+    /// The methods `UrlEncodedBody` reads bodies for.
+    pub fn allowed_body_methods(&self) -> &BodyMethods {
+        &self.body_methods
+    }
+
+    /// The limits both plugins parse with.
+    pub fn options(&self) -> &ParseOptions {
+        &self.options
+    }
+
+    /// How long reading a body may take, if it is limited.
+    pub fn body_read_timeout(&self) -> Option<Duration> {
+        self.read_timeout
+    }
+
+    /// The size above which urlencoded bodies are spooled to disk, if they are.
+    pub fn body_spool_threshold(&self) -> Option<usize> {
+        self.spool_threshold
+    }
+
+    /// The limits and storage of `multipart/form-data` bodies.
+    pub fn multipart(&self) -> &MultipartOptions {
+        &self.multipart
+    }
+
+    /// Parse data arriving in pieces with `parse_piecewise`, then the
+    /// configured transforms.
+    pub fn parse_pieces<I>(&self, pieces: I) -> QueryResult
+    where I: IntoIterator<Item = Result<String, UrlDecodingError>> {
+        let mut params = parse_piecewise(pieces, &self.options)?;
+        self.transforms.apply(&mut params);
+        Ok(params)
+    }
+
+    /// Parse a `multipart/form-data` body the way `MultipartBody` does: with
+    /// the configured multipart options, the configured `ParseOptions` on the
+    /// fields, then the configured transforms on them.
+    pub fn parse_multipart<R: Read>(&self, body: R, boundary: &str) -> Result<MultipartForm, UrlDecodingError> {
+        let mut form = parse_multipart_with(body, boundary, &self.multipart, &self.options)?;
+        self.transforms.apply(&mut form.fields);
+        Ok(form)
+    }
+
+    /// Parse `data` the way the plugins do: with the configured options, then
+    /// the configured transforms.
+    pub fn parse(&self, data: &str) -> QueryResult {
+        let mut params = parse_with(data, &self.options)?;
+        self.transforms.apply(&mut params);
+        Ok(params)
+    }
+}
+
+impl Key for UrlEncodedConfig {
+    type Value = UrlEncodedConfig;
+}
+
+impl BeforeMiddleware for UrlEncodedConfig {
+    fn before(&self, req: &mut Request) -> IronResult<()> {
+        req.extensions.insert::<UrlEncodedConfig>(self.clone());
+        Ok(())
+    }
+}
+
+#[test]
+fn test_body_methods() {
+    assert!(BodyMethods::Any.allows(&Get));
+    assert!(BodyMethods::with_body().allows(&Patch));
+    assert!(BodyMethods::with_body().allows(&Delete));
+    assert!(!BodyMethods::with_body().allows(&Get));
+    assert!(!BodyMethods::with_body().allows(&Head));
+    assert!(!BodyMethods::Only(vec![Post]).allows(&Put));
+}
+
+#[test]
+fn test_error_response() {
+    use iron::status;
+
+    let default = UrlEncodedConfig::new();
+    assert_eq!(default.response_for(&UrlDecodingError::EmptyQuery).status, Some(status::BadRequest));
+
+    let custom = UrlEncodedConfig::new().error_response(|error| match *error {
+        UrlDecodingError::TooManyPairs(_) => Response::with((status::PayloadTooLarge, "Too much")),
+        _ => Response::with(status::BadRequest)
+    });
+    let error = custom.iron_error(UrlDecodingError::TooManyPairs(10));
+    assert_eq!(error.response.status, Some(status::PayloadTooLarge));
+    assert_eq!(error.error.to_string(), "Expected at most 10 parameters.");
+}
@@ -0,0 +1,150 @@
+//! Calendar dates written as ISO 8601 `YYYY-MM-DD`, as sent by
+//! `<input type="date">`, and the ranges of reporting endpoints.
+
+use std::fmt;
+
+use super::UrlDecodingError;
+
+/// A day of the proleptic Gregorian calendar, between years 1 and 9999.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Date {
+    year: u16,
+    month: u8,
+    day: u8
+}
+
+impl Date {
+    /// The date `year-month-day`, if it exists.
+    pub fn new(year: u16, month: u8, day: u8) -> Option<Date> {
+        let valid = (1..=9999).contains(&year) && (1..=12).contains(&month) &&
+            day >= 1 && day <= days_in_month(year, month);
+        if valid { Some(Date { year, month, day }) } else { None }
+    }
+
+    /// The year.
+    pub fn year(&self) -> u16 {
+        self.year
+    }
+
+    /// The month, from 1 to 12.
+    pub fn month(&self) -> u8 {
+        self.month
+    }
+
+    /// The day of the month, from 1.
+    pub fn day(&self) -> u8 {
+        self.day
+    }
+
+    /// The number of days since 0001-01-01.
+    fn ordinal(&self) -> i64 {
+        let year = i64::from(self.year) - 1;
+        let leap_days = year / 4 - year / 100 + year / 400;
+        let before_month: i64 = (1..self.month).map(|month| i64::from(days_in_month(self.year, month))).sum();
+        year * 365 + leap_days + before_month + i64::from(self.day) - 1
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31
+    }
+}
+
+/// An inclusive range of dates, `from` never after `to`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DateRange {
+    /// The first day of the range.
+    pub from: Date,
+    /// The last day of the range.
+    pub to: Date
+}
+
+impl DateRange {
+    /// The number of days in the range, counting both ends.
+    pub fn days(&self) -> u32 {
+        (self.to.ordinal() - self.from.ordinal() + 1) as u32
+    }
+
+    /// Whether `date` falls within the range.
+    pub fn contains(&self, date: Date) -> bool {
+        self.from <= date && date <= self.to
+    }
+}
+
+/// Parse a date written `YYYY-MM-DD`, the only form HTML date inputs send.
+pub fn parse_date(key: &str, value: &str) -> Result<Date, UrlDecodingError> {
+    let invalid = || UrlDecodingError::InvalidParameter {
+        key: key.to_owned(),
+        message: "is not a date such as 2016-01-31".to_owned()
+    };
+
+    let bytes = value.as_bytes();
+    if bytes.len() != 10 || bytes[4] != b'-' || bytes[7] != b'-' ||
+       !bytes.iter().enumerate().all(|(at, byte)| at == 4 || at == 7 || byte.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let (year, month, day) = (value[..4].parse().ok(), value[5..7].parse().ok(), value[8..].parse().ok());
+    match (year, month, day) {
+        (Some(year), Some(month), Some(day)) => Date::new(year, month, day).ok_or_else(invalid),
+        _ => Err(invalid())
+    }
+}
+
+/// Check that the dates of `from_key` and `to_key` are in order and, with
+/// `max_days`, that the range covers at most that many days.
+pub fn parse_date_range(from_key: &str, from: &str, to_key: &str, to: &str, max_days: Option<u32>)
+                        -> Result<DateRange, UrlDecodingError> {
+    let range = DateRange { from: parse_date(from_key, from)?, to: parse_date(to_key, to)? };
+    if range.to < range.from {
+        return Err(UrlDecodingError::InvalidParameter {
+            key: to_key.to_owned(),
+            message: format!("must not be before `{}`", from_key)
+        });
+    }
+    if let Some(max_days) = max_days {
+        if range.days() > max_days {
+            return Err(UrlDecodingError::InvalidParameter {
+                key: to_key.to_owned(),
+                message: format!("must be at most {} days after `{}`", max_days.saturating_sub(1), from_key)
+            });
+        }
+    }
+    Ok(range)
+}
+
+#[test]
+fn test_parse_date_range() {
+    assert_eq!(parse_date("day", "2016-02-29").unwrap().to_string(), "2016-02-29");
+    assert!(parse_date("day", "2015-02-29").is_err());
+    assert!(parse_date("day", "2016-13-01").is_err());
+    assert!(parse_date("day", "2016-1-01").is_err());
+    assert!(parse_date("day", "+016-01-01").is_err());
+    assert!(parse_date("day", "0000-01-01").is_err());
+
+    let range = parse_date_range("from", "2015-12-30", "to", "2016-03-01", None).unwrap();
+    assert_eq!(range.days(), 63);
+    assert!(range.contains(Date::new(2016, 2, 29).unwrap()));
+    assert!(!range.contains(Date::new(2016, 3, 2).unwrap()));
+    assert_eq!(parse_date_range("from", "2016-01-31", "to", "2016-01-31", Some(1)).unwrap().days(), 1);
+
+    let message = |result: Result<DateRange, UrlDecodingError>| match result {
+        Err(UrlDecodingError::InvalidParameter { key, message }) => format!("{} {}", key, message),
+        other => panic!("unexpected {:?}", other)
+    };
+    assert_eq!(message(parse_date_range("from", "2016-02-01", "to", "2016-01-31", None)),
+               "to must not be before `from`");
+    assert_eq!(message(parse_date_range("from", "2016-01-01", "to", "2016-01-31", Some(30))),
+               "to must be at most 29 days after `from`");
+    assert_eq!(message(parse_date_range("from", "2016-01-32", "to", "2016-01-31", None)),
+               "from is not a date such as 2016-01-31");
+}
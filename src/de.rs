@@ -0,0 +1,366 @@
+//! A `serde::Deserializer` over the nested tree, so nested parameters can be
+//! read straight into typed structs.
+
+use std::collections::{BTreeMap, btree_map};
+use std::error::Error as StdError;
+use std::fmt;
+use std::vec;
+
+use serde::de::{self, Deserialize, Visitor, Type};
+
+use super::{ParseOptions, UrlDecodingError};
+use super::nested::{NestedValue, NestedQueryMap, NestedOptions, parse_nested};
+use super::schema::{FieldError, FieldErrorKind};
+
+/// An error deserializing a nested tree.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeserializeError(String);
+
+impl fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl StdError for DeserializeError {
+    fn description(&self) -> &str {
+        &self.0
+    }
+}
+
+impl de::Error for DeserializeError {
+    fn custom<T: Into<String>>(msg: T) -> DeserializeError {
+        DeserializeError(msg.into())
+    }
+
+    fn end_of_stream() -> DeserializeError {
+        DeserializeError("Unexpected end of parameters.".to_owned())
+    }
+}
+
+/// Deserialize a nested parse result into `T`:
+///
+/// ```ignore
+/// #[derive(Deserialize)]
+/// struct Address { city: String, zip: Option<u32> }
+/// #[derive(Deserialize)]
+/// struct User { name: String, address: Address, tags: Vec<String> }
+///
+/// let tree = parse_nested("name=Ann&address[city]=Paris&tags[]=a", &ParseOptions::new(),
+///                         &NestedOptions::new())?;
+/// let user: User = from_nested(tree)?;
+/// ```
+pub fn from_nested<T: Deserialize>(map: NestedQueryMap) -> Result<T, DeserializeError> {
+    T::deserialize(&mut NestedDeserializer::new(NestedValue::Map(map)))
+}
+
+/// Deserialize every row of a bulk form, such as the items of a cart sent as
+/// `item[3][qty]=2&item[3][sku]=A1&item[7][qty]=1`, into a `T`, by row index.
+///
+/// The rows of `key` may be a map of indices, as `parse_rows` reads them, or
+/// a list, whose indices are then positions. Every row that fails is reported
+/// in a `ValidationFailed` error, as a field named after it, like `item[3]`.
+/// An absent `key` is no rows.
+pub fn from_rows<T: Deserialize>(tree: &NestedQueryMap, key: &str) -> Result<BTreeMap<usize, T>, UrlDecodingError> {
+    let rows: Vec<(String, &NestedValue)> = match tree.get(key) {
+        None => return Ok(BTreeMap::new()),
+        Some(NestedValue::Map(rows)) => rows.iter().map(|(index, row)| (index.clone(), row)).collect(),
+        Some(NestedValue::List(rows)) => rows.iter().enumerate().map(|(index, row)| (index.to_string(), row)).collect(),
+        Some(NestedValue::Value(_)) => return Err(UrlDecodingError::InvalidParameter {
+            key: key.to_owned(),
+            message: "must be a list of rows".to_owned()
+        })
+    };
+
+    let mut parsed = BTreeMap::new();
+    let mut errors = Vec::new();
+    for (index, row) in rows {
+        let field = format!("{}[{}]", key, index);
+        let message = match index.parse::<usize>() {
+            Ok(position) => match T::deserialize(&mut NestedDeserializer::new(row.clone())) {
+                Ok(value) => {
+                    parsed.insert(position, value);
+                    continue;
+                },
+                Err(err) => format!("is invalid: {}", err)
+            },
+            Err(_) => "is not a row index".to_owned()
+        };
+        errors.push(FieldError::new(&field, FieldErrorKind::Custom(message)));
+    }
+
+    if errors.is_empty() {
+        Ok(parsed)
+    } else {
+        Err(UrlDecodingError::ValidationFailed(errors))
+    }
+}
+
+/// Parse `data` and deserialize the rows of `key` with `from_rows`, under
+/// the limits of `options` and `nested`.
+///
+/// Digits in brackets are read as map keys, not list indices, whatever
+/// `nested` says, so the rows keep the indices the form gave them: the nested
+/// lists of a row must be written with `[]`.
+pub fn parse_rows<T: Deserialize>(data: &str, key: &str, options: &ParseOptions, nested: &NestedOptions)
+                                  -> Result<BTreeMap<usize, T>, UrlDecodingError> {
+    let tree = parse_nested(data, options, &nested.clone().numeric_map_keys(true))?;
+    from_rows(&tree, key)
+}
+
+/// A `serde::Deserializer` for one value of the nested tree.
+///
+/// Every plain value is a string, parsed when the target type asks for a
+/// number or a boolean. Booleans accept `true`/`false`, `on`/`off`, `yes`/`no`
+/// and `1`/`0`. Empty values and absent fields deserialize to `None`, and a
+/// single value is a one-element sequence, so `tags=a` fills a `Vec` just
+/// like `tags[]=a`. Enums deserialize from the name of a unit variant.
+pub struct NestedDeserializer {
+    value: Option<NestedValue>
+}
+
+impl NestedDeserializer {
+    /// A deserializer for `value`.
+    pub fn new(value: NestedValue) -> NestedDeserializer {
+        NestedDeserializer { value: Some(value) }
+    }
+
+    fn take(&mut self) -> Result<NestedValue, DeserializeError> {
+        self.value.take().ok_or_else(de::Error::end_of_stream)
+    }
+
+    /// The plain value, or an error expecting `expected`.
+    fn take_str(&mut self, expected: Type) -> Result<String, DeserializeError> {
+        match self.take()? {
+            NestedValue::Value(value) => Ok(value),
+            _ => Err(de::Error::invalid_type(expected))
+        }
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident, $ty:expr;)*) => {
+        $(
+            fn $method<V: Visitor>(&mut self, mut visitor: V) -> Result<V::Value, DeserializeError> {
+                let value = self.take_str($ty)?;
+                match value.trim().parse() {
+                    Ok(parsed) => visitor.$visit(parsed),
+                    Err(_) => Err(de::Error::invalid_value(&format!("`{}` is not a valid {:?}", value, $ty)))
+                }
+            }
+        )*
+    }
+}
+
+impl de::Deserializer for NestedDeserializer {
+    type Error = DeserializeError;
+
+    fn deserialize<V: Visitor>(&mut self, mut visitor: V) -> Result<V::Value, DeserializeError> {
+        match self.take()? {
+            NestedValue::Value(value) => visitor.visit_string(value),
+            NestedValue::List(items) => visitor.visit_seq(SeqAccess { items: items.into_iter() }),
+            NestedValue::Map(entries) => visitor.visit_map(MapAccess { entries: entries.into_iter(), value: None })
+        }
+    }
+
+    fn deserialize_bool<V: Visitor>(&mut self, mut visitor: V) -> Result<V::Value, DeserializeError> {
+        let value = self.take_str(Type::Bool)?;
+        match &*value.trim().to_lowercase() {
+            "true" | "on" | "yes" | "1" => visitor.visit_bool(true),
+            "false" | "off" | "no" | "0" => visitor.visit_bool(false),
+            _ => Err(de::Error::invalid_value(&format!("`{}` is not a valid Bool", value)))
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_usize => visit_usize, Type::Usize;
+        deserialize_u8 => visit_u8, Type::U8;
+        deserialize_u16 => visit_u16, Type::U16;
+        deserialize_u32 => visit_u32, Type::U32;
+        deserialize_u64 => visit_u64, Type::U64;
+        deserialize_isize => visit_isize, Type::Isize;
+        deserialize_i8 => visit_i8, Type::I8;
+        deserialize_i16 => visit_i16, Type::I16;
+        deserialize_i32 => visit_i32, Type::I32;
+        deserialize_i64 => visit_i64, Type::I64;
+        deserialize_f32 => visit_f32, Type::F32;
+        deserialize_f64 => visit_f64, Type::F64;
+        deserialize_char => visit_char, Type::Char;
+    }
+
+    fn deserialize_option<V: Visitor>(&mut self, mut visitor: V) -> Result<V::Value, DeserializeError> {
+        match self.value {
+            Some(NestedValue::Value(ref value)) if value.is_empty() => visitor.visit_none(),
+            None => visitor.visit_none(),
+            _ => visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_seq<V: Visitor>(&mut self, mut visitor: V) -> Result<V::Value, DeserializeError> {
+        match self.take()? {
+            NestedValue::List(items) => visitor.visit_seq(SeqAccess { items: items.into_iter() }),
+            value @ NestedValue::Value(_) => visitor.visit_seq(SeqAccess { items: vec![value].into_iter() }),
+            NestedValue::Map(_) => Err(de::Error::invalid_type(Type::Seq))
+        }
+    }
+
+    fn deserialize_map<V: Visitor>(&mut self, mut visitor: V) -> Result<V::Value, DeserializeError> {
+        match self.take()? {
+            NestedValue::Map(entries) => visitor.visit_map(MapAccess { entries: entries.into_iter(), value: None }),
+            _ => Err(de::Error::invalid_type(Type::Map))
+        }
+    }
+
+    fn deserialize_struct<V: Visitor>(&mut self, _name: &'static str, _fields: &'static [&'static str],
+                                      visitor: V) -> Result<V::Value, DeserializeError> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V: de::EnumVisitor>(&mut self, _name: &'static str,
+                                            _variants: &'static [&'static str],
+                                            mut visitor: V) -> Result<V::Value, DeserializeError> {
+        let variant = self.take_str(Type::Enum)?;
+        visitor.visit(UnitVariant { name: Some(variant) })
+    }
+}
+
+struct SeqAccess {
+    items: vec::IntoIter<NestedValue>
+}
+
+impl de::SeqVisitor for SeqAccess {
+    type Error = DeserializeError;
+
+    fn visit<T: Deserialize>(&mut self) -> Result<Option<T>, DeserializeError> {
+        match self.items.next() {
+            Some(item) => T::deserialize(&mut NestedDeserializer::new(item)).map(Some),
+            None => Ok(None)
+        }
+    }
+
+    fn end(&mut self) -> Result<(), DeserializeError> {
+        match self.items.len() {
+            0 => Ok(()),
+            remaining => Err(de::Error::invalid_length(remaining))
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.items.size_hint()
+    }
+}
+
+struct MapAccess {
+    entries: btree_map::IntoIter<String, NestedValue>,
+    value: Option<NestedValue>
+}
+
+impl de::MapVisitor for MapAccess {
+    type Error = DeserializeError;
+
+    fn visit_key<K: Deserialize>(&mut self) -> Result<Option<K>, DeserializeError> {
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                K::deserialize(&mut NestedDeserializer::new(NestedValue::Value(key))).map(Some)
+            },
+            None => Ok(None)
+        }
+    }
+
+    fn visit_value<V: Deserialize>(&mut self) -> Result<V, DeserializeError> {
+        let value = self.value.take().ok_or_else(de::Error::end_of_stream)?;
+        V::deserialize(&mut NestedDeserializer::new(value))
+    }
+
+    fn end(&mut self) -> Result<(), DeserializeError> {
+        Ok(())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+
+    /// Absent fields are `None` for `Option` fields and an error otherwise.
+    fn missing_field<V: Deserialize>(&mut self, field: &'static str) -> Result<V, DeserializeError> {
+        V::deserialize(&mut Missing(field))
+    }
+}
+
+/// Stands in for an absent struct field.
+struct Missing(&'static str);
+
+impl de::Deserializer for Missing {
+    type Error = DeserializeError;
+
+    fn deserialize<V: Visitor>(&mut self, _visitor: V) -> Result<V::Value, DeserializeError> {
+        Err(de::Error::missing_field(self.0))
+    }
+
+    fn deserialize_option<V: Visitor>(&mut self, mut visitor: V) -> Result<V::Value, DeserializeError> {
+        visitor.visit_none()
+    }
+}
+
+struct UnitVariant {
+    name: Option<String>
+}
+
+impl de::VariantVisitor for UnitVariant {
+    type Error = DeserializeError;
+
+    fn visit_variant<V: Deserialize>(&mut self) -> Result<V, DeserializeError> {
+        let name = self.name.take().ok_or_else(de::Error::end_of_stream)?;
+        V::deserialize(&mut NestedDeserializer::new(NestedValue::Value(name)))
+    }
+
+    fn visit_unit(&mut self) -> Result<(), DeserializeError> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_from_nested() {
+    use std::collections::BTreeMap;
+    use super::{parse_nested, ParseOptions, NestedOptions};
+
+    let parse = |data| parse_nested(data, &ParseOptions::new(), &NestedOptions::new()).unwrap();
+
+    let users: BTreeMap<String, BTreeMap<String, BTreeMap<String, String>>> =
+        from_nested(parse("user[address][city]=Paris&user[address][zip]=75001")).unwrap();
+    assert_eq!(users["user"]["address"]["city"], "Paris");
+
+    let ids: BTreeMap<String, Vec<u32>> = from_nested(parse("ids[]=1&ids[]=2&one=3")).unwrap();
+    assert_eq!(ids["ids"], vec![1, 2]);
+    assert_eq!(ids["one"], vec![3]);
+
+    let options: BTreeMap<String, Option<u8>> = from_nested(parse("a=&b=2")).unwrap();
+    assert_eq!(options["a"], None);
+    assert_eq!(options["b"], Some(2));
+
+    let flags: BTreeMap<String, (bool, f64)> = from_nested(parse("x[]=on&x[]=1.5")).unwrap();
+    assert_eq!(flags["x"], (true, 1.5));
+
+    let error = from_nested::<BTreeMap<String, u8>>(parse("n=300")).unwrap_err();
+    assert_eq!(error.to_string(), "Invalid value: `300` is not a valid U8");
+
+    let rows: BTreeMap<usize, BTreeMap<String, u32>> =
+        parse_rows("item[3][qty]=2&item[3][id]=9&item[7][qty]=1&other=x", "item", &ParseOptions::new(),
+                   &NestedOptions::new()).unwrap();
+    assert_eq!(rows.keys().collect::<Vec<_>>(), vec![&3, &7]);
+    assert_eq!(rows[&3]["qty"], 2);
+    let listed: BTreeMap<usize, BTreeMap<String, u32>> = from_rows(&parse("item[][qty]=5"), "item").unwrap();
+    assert_eq!(listed[&0]["qty"], 5);
+
+    match parse_rows::<BTreeMap<String, u32>>("item[1][qty]=x&item[2][qty]=1&item[a][qty]=1", "item",
+                                              &ParseOptions::new(), &NestedOptions::new()) {
+        Err(UrlDecodingError::ValidationFailed(errors)) => {
+            let fields: Vec<&str> = errors.iter().map(|error| &*error.field).collect();
+            assert_eq!(fields, vec!["item[1]", "item[a]"]);
+        },
+        other => panic!("unexpected {:?}", other.map(|rows| rows.len()))
+    }
+    assert!(matches!(parse_rows::<BTreeMap<String, u32>>("item[1][a][b]=1", "item", &ParseOptions::new(),
+                                                         &NestedOptions::new().max_depth(2)),
+                     Err(UrlDecodingError::NestingTooDeep(2))));
+}
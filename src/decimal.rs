@@ -0,0 +1,97 @@
+//! A fixed-point decimal type for amounts that must not go through `f64`.
+
+use std::fmt;
+
+use super::UrlDecodingError;
+
+/// A decimal number stored as an integer `mantissa` and a `scale`: the value is
+/// `mantissa / 10^scale`, so `12.30` is `Decimal { mantissa: 1230, scale: 2 }`.
+///
+/// Parsing keeps the scale as written, which matters for amounts: `12.30` and
+/// `12.3` compare unequal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Decimal {
+    mantissa: i64,
+    scale: u32
+}
+
+impl Decimal {
+    /// The decimal `mantissa / 10^scale`.
+    pub fn new(mantissa: i64, scale: u32) -> Decimal {
+        Decimal { mantissa, scale }
+    }
+
+    /// The value as an integer count of the smallest unit, such as cents.
+    pub fn mantissa(&self) -> i64 {
+        self.mantissa
+    }
+
+    /// The number of digits after the decimal point.
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+        let digits = format!("{:0width$}", self.mantissa.unsigned_abs(), width = self.scale as usize + 1);
+        let (integer, fraction) = digits.split_at(digits.len() - self.scale as usize);
+
+        if fraction.is_empty() {
+            write!(f, "{}{}", sign, integer)
+        } else {
+            write!(f, "{}{}.{}", sign, integer, fraction)
+        }
+    }
+}
+
+/// Parse a plain decimal such as `-12.30` with at most `max_scale` digits after
+/// the point. Exponents and grouping separators are rejected.
+pub fn parse_decimal(key: &str, value: &str, max_scale: u32) -> Result<Decimal, UrlDecodingError> {
+    let invalid = |message: String| UrlDecodingError::InvalidParameter {
+        key: key.to_owned(),
+        message
+    };
+
+    let (negative, unsigned) = match value.chars().next() {
+        Some('-') => (true, &value[1..]),
+        Some('+') => (false, &value[1..]),
+        _ => (false, value)
+    };
+    let (integer, fraction) = match unsigned.find('.') {
+        Some(position) => (&unsigned[..position], &unsigned[position + 1..]),
+        None => (unsigned, "")
+    };
+
+    let digits_only = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+    if integer.is_empty() || !digits_only(integer) || !digits_only(fraction) ||
+       (unsigned.contains('.') && fraction.is_empty()) {
+        return Err(invalid("is not a valid decimal number".to_owned()));
+    }
+    if fraction.len() > max_scale as usize {
+        return Err(invalid(format!("may have at most {} decimal places", max_scale)));
+    }
+
+    let mantissa = integer.bytes().chain(fraction.bytes())
+        .try_fold(0i64, |acc, digit| {
+            acc.checked_mul(10).and_then(|acc| acc.checked_add(i64::from(digit - b'0')))
+        })
+        .ok_or_else(|| invalid("is too large".to_owned()))?;
+
+    Ok(Decimal::new(if negative { -mantissa } else { mantissa }, fraction.len() as u32))
+}
+
+#[test]
+fn test_parse_decimal() {
+    let amount = parse_decimal("amount", "12.30", 2).unwrap();
+    assert_eq!((amount.mantissa(), amount.scale()), (1230, 2));
+    assert_eq!(amount.to_string(), "12.30");
+    assert_eq!(parse_decimal("amount", "-0.05", 2).unwrap().to_string(), "-0.05");
+    assert_eq!(parse_decimal("amount", "7", 2).unwrap().to_string(), "7");
+    assert!(parse_decimal("amount", "0.001", 2).is_err());
+    assert!(parse_decimal("amount", "1e3", 2).is_err());
+    assert!(parse_decimal("amount", "1.", 2).is_err());
+    assert!(parse_decimal("amount", ".5", 2).is_err());
+    assert!(parse_decimal("amount", "99999999999999999999", 2).is_err());
+}
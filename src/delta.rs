@@ -0,0 +1,90 @@
+//! Membership changes submitted as two lists, such as the members added to
+//! and removed from a group: `added[]=3&added[]=5&removed[]=2`.
+
+use std::hash::Hash;
+use std::str::FromStr;
+
+use super::{QueryMap, UrlDecodingError};
+use super::values::parse_ids;
+
+/// The values to add to and remove from a set, each kept once in the order
+/// they were first sent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SetDelta<T> {
+    /// The values to add.
+    pub added: Vec<T>,
+    /// The values to remove.
+    pub removed: Vec<T>
+}
+
+impl<T: PartialEq + Clone> SetDelta<T> {
+    /// Whether there is nothing to change.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+
+    /// Apply the changes to `set`: remove the removed values, then append the
+    /// added ones it doesn't have yet.
+    pub fn apply(&self, set: &mut Vec<T>) {
+        set.retain(|value| !self.removed.contains(value));
+        for value in &self.added {
+            if !set.contains(value) {
+                set.push(value.clone());
+            }
+        }
+    }
+}
+
+/// The values of `key`, written either as `key` or as `key[]`.
+fn list_values(params: &QueryMap, key: &str) -> Vec<String> {
+    let bracketed = format!("{}[]", key);
+    [key, &*bracketed].iter()
+        .filter_map(|key| params.get(*key))
+        .flat_map(|values| values.iter().cloned())
+        .collect()
+}
+
+/// Parse the values of `added_key` and `removed_key` into a `SetDelta`.
+///
+/// Bracketed keys such as `added[]` are read as well. Values sent twice in a
+/// list are kept once, and a value in both lists is refused, since adding and
+/// removing it at once is ambiguous.
+pub fn parse_set_delta<T>(params: &QueryMap, added_key: &str, removed_key: &str)
+                          -> Result<SetDelta<T>, UrlDecodingError>
+where T: FromStr + Hash + Eq + Clone + ToString {
+    let added: Vec<T> = parse_ids(added_key, &list_values(params, added_key), usize::MAX)?;
+    let removed: Vec<T> = parse_ids(removed_key, &list_values(params, removed_key), usize::MAX)?;
+
+    let conflicts: Vec<String> = added.iter()
+        .filter(|value| removed.contains(value))
+        .map(|value| format!("`{}`", value.to_string()))
+        .collect();
+    if !conflicts.is_empty() {
+        return Err(UrlDecodingError::InvalidParameter {
+            key: removed_key.to_owned(),
+            message: format!("may not repeat values of `{}`: {}", added_key, conflicts.join(", "))
+        });
+    }
+    Ok(SetDelta { added, removed })
+}
+
+#[test]
+fn test_parse_set_delta() {
+    use super::{parse_with, ParseOptions};
+
+    let parse = |data: &str| parse_with(data, &ParseOptions::new()).unwrap();
+    let delta: SetDelta<u32> = parse_set_delta(&parse("added[]=3&added[]=5&added=3&removed%5B%5D=2"),
+                                               "added", "removed").unwrap();
+    assert_eq!(delta, SetDelta { added: vec![3, 5], removed: vec![2] });
+
+    let mut members = vec![1, 2, 3];
+    delta.apply(&mut members);
+    assert_eq!(members, vec![1, 3, 5]);
+
+    let empty: SetDelta<u32> = parse_set_delta(&parse("page=1"), "added", "removed").unwrap();
+    assert!(empty.is_empty());
+    assert_eq!(parse_set_delta::<u32>(&parse("added[]=3&removed[]=3&removed[]=4"), "added", "removed")
+                   .unwrap_err().to_string(),
+               "`removed` may not repeat values of `added`: `3`.");
+    assert!(parse_set_delta::<u32>(&parse("added[]=x"), "added", "removed").is_err());
+}
@@ -0,0 +1,73 @@
+//! Middleware that parses the request parameters before the handler runs.
+
+use iron::prelude::*;
+use iron::BeforeMiddleware;
+
+use super::{UrlDecodingError, UrlEncodedQuery, UrlEncodedBody, is_form};
+
+/// `BeforeMiddleware` that parses the query string and form bodies up front,
+/// so malformed or oversized parameters are rejected before any handler runs.
+///
+/// Failures are answered with the response of the `UrlEncodedConfig`, which
+/// must be linked before this middleware to apply. Requests without a query,
+/// or without a body, go through. Bodies are only parsed for
+/// `application/x-www-form-urlencoded` requests.
+///
+/// ```ignore
+/// chain.link_before(UrlEncodedConfig::new().parse_options(ParseOptions::new().max_pairs(100)));
+/// chain.link_before(ParseEagerly::new());
+/// ```
+#[derive(Clone, Debug)]
+pub struct ParseEagerly {
+    query: bool,
+    body: bool
+}
+
+impl Default for ParseEagerly {
+    fn default() -> ParseEagerly {
+        ParseEagerly { query: true, body: true }
+    }
+}
+
+impl ParseEagerly {
+    /// Parse both the query string and the body.
+    pub fn new() -> ParseEagerly {
+        ParseEagerly::default()
+    }
+
+    /// Whether to parse the query string.
+    pub fn query(mut self, enabled: bool) -> ParseEagerly {
+        self.query = enabled;
+        self
+    }
+
+    /// Whether to parse form bodies.
+    pub fn body(mut self, enabled: bool) -> ParseEagerly {
+        self.body = enabled;
+        self
+    }
+}
+
+impl BeforeMiddleware for ParseEagerly {
+    fn before(&self, req: &mut Request) -> IronResult<()> {
+        if self.query {
+            let result = req.get_ref::<UrlEncodedQuery>().map(|_| ());
+            check(req, result)?;
+        }
+
+        if self.body && is_form(req) {
+            let result = req.get_ref::<UrlEncodedBody>().map(|_| ());
+            check(req, result)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Let empty data through and turn every other error into an `IronError`.
+fn check(req: &Request, result: Result<(), UrlDecodingError>) -> IronResult<()> {
+    match result {
+        Ok(()) | Err(UrlDecodingError::EmptyQuery) => Ok(()),
+        Err(err) => Err(err.into_iron_error(req))
+    }
+}
@@ -0,0 +1,354 @@
+//! Serializing parameters back into urlencoded data.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::sync::Arc;
+
+use url::form_urlencoded;
+use url::percent_encoding::{utf8_percent_encode, FORM_URLENCODED_ENCODE_SET};
+
+use super::{QueryMap, ParseOptions};
+use super::parse::decode;
+use super::nested::{NestedValue, NestedQueryMap};
+
+/// Serialize a `QueryMap` as `application/x-www-form-urlencoded` data.
+///
+/// Keys are written in lexicographic order, so that equal maps always encode to
+/// the same string, and the values of a key keep their order.
+pub fn encode(params: &QueryMap) -> String {
+    let mut keys: Vec<&String> = params.keys().collect();
+    keys.sort();
+
+    let pairs = keys.into_iter()
+        .flat_map(|key| params[key].iter().map(move |value| (&**key, &**value)));
+    form_urlencoded::serialize(pairs.collect::<Vec<_>>())
+}
+
+/// The characters `EncodeOptions` writes as they are, every other byte being
+/// percent-encoded. Only ASCII characters can be kept.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EncodeSet {
+    literal: [bool; 128]
+}
+
+impl Default for EncodeSet {
+    fn default() -> EncodeSet {
+        EncodeSet::form()
+    }
+}
+
+impl EncodeSet {
+    /// What `encode` keeps: `A-Z a-z 0-9 * - . _`.
+    pub fn form() -> EncodeSet {
+        EncodeSet::alphanumeric().keep("*-._")
+    }
+
+    /// The RFC 3986 unreserved characters: `A-Z a-z 0-9 - . _ ~`, as
+    /// `rfc3986_encode` keeps.
+    pub fn rfc3986() -> EncodeSet {
+        EncodeSet::alphanumeric().keep("-._~")
+    }
+
+    fn alphanumeric() -> EncodeSet {
+        let mut literal = [false; 128];
+        for byte in (b'0'..=b'9').chain(b'A'..=b'Z').chain(b'a'..=b'z') {
+            literal[byte as usize] = true;
+        }
+        EncodeSet { literal }
+    }
+
+    /// Also keep the ASCII characters of `chars`, such as `:,` for readable
+    /// links.
+    pub fn keep(mut self, chars: &str) -> EncodeSet {
+        for byte in chars.bytes().filter(|byte| byte.is_ascii()) {
+            self.literal[byte as usize] = true;
+        }
+        self
+    }
+
+    /// Percent-encode the characters of `chars`, such as `'` for values
+    /// embedded in HTML attributes, even if the set kept them.
+    pub fn escape(mut self, chars: &str) -> EncodeSet {
+        for byte in chars.bytes().filter(|byte| byte.is_ascii()) {
+            self.literal[byte as usize] = false;
+        }
+        self
+    }
+
+    /// Whether `byte` is written as it is.
+    pub fn keeps(&self, byte: u8) -> bool {
+        byte.is_ascii() && self.literal[byte as usize]
+    }
+}
+
+/// How spaces are written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Spaces {
+    /// As `+`, as forms do.
+    #[default]
+    Plus,
+    /// As `%20`, as RFC 3986 and most signature schemes do.
+    Percent
+}
+
+type Compare = dyn Fn(&str, &str) -> Ordering + Send + Sync;
+
+/// The order `encode_with` writes keys in. The values of a key always keep
+/// their order.
+#[derive(Clone, Default)]
+pub enum KeyOrder {
+    /// Lexicographic order, as `encode` writes them.
+    #[default]
+    Sorted,
+    /// The order the pairs are given in, for `encode_pairs_with`. A `QueryMap`
+    /// doesn't remember the order of its keys, so they are sorted there.
+    Input,
+    /// The order of a comparator of keys.
+    Custom(Arc<Compare>)
+}
+
+impl KeyOrder {
+    /// Order keys with `f`.
+    pub fn custom<F>(f: F) -> KeyOrder
+    where F: Fn(&str, &str) -> Ordering + Send + Sync + 'static {
+        KeyOrder::Custom(Arc::new(f))
+    }
+}
+
+impl fmt::Debug for KeyOrder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            KeyOrder::Sorted => "Sorted",
+            KeyOrder::Input => "Input",
+            KeyOrder::Custom(_) => "Custom"
+        })
+    }
+}
+
+/// How `encode_with` writes parameters, for contexts needing a different
+/// strictness than `encode`'s.
+///
+/// ```ignore
+/// let options = EncodeOptions::new().encode_set(EncodeSet::form().keep(":,").escape("*"));
+/// let href = format!("/search?{}", encode_with(&params, &options));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct EncodeOptions {
+    set: EncodeSet,
+    spaces: Spaces,
+    order: KeyOrder
+}
+
+impl EncodeOptions {
+    /// The options of `encode`.
+    pub fn new() -> EncodeOptions {
+        EncodeOptions::default()
+    }
+
+    /// Keep the characters of `set` instead of `EncodeSet::form()`'s.
+    pub fn encode_set(mut self, set: EncodeSet) -> EncodeOptions {
+        self.set = set;
+        self
+    }
+
+    /// Write spaces as `+` or `%20`, unless the set keeps them.
+    pub fn spaces(mut self, spaces: Spaces) -> EncodeOptions {
+        self.spaces = spaces;
+        self
+    }
+
+    /// Percent-encode `~`, or keep it, whatever the set says.
+    pub fn encode_tilde(mut self, enabled: bool) -> EncodeOptions {
+        self.set = if enabled { self.set.escape("~") } else { self.set.keep("~") };
+        self
+    }
+
+    /// Percent-encode `*`, or keep it, whatever the set says.
+    pub fn encode_star(mut self, enabled: bool) -> EncodeOptions {
+        self.set = if enabled { self.set.escape("*") } else { self.set.keep("*") };
+        self
+    }
+
+    /// Write keys in `order` instead of lexicographic order.
+    pub fn order(mut self, order: KeyOrder) -> EncodeOptions {
+        self.order = order;
+        self
+    }
+
+    /// Encode a single key or value.
+    pub fn encode_component(&self, component: &str) -> String {
+        let mut encoded = String::with_capacity(component.len());
+        for byte in component.bytes() {
+            match byte {
+                _ if self.set.keeps(byte) => encoded.push(byte as char),
+                b' ' if self.spaces == Spaces::Plus => encoded.push('+'),
+                _ => encoded.push_str(&format!("%{:02X}", byte))
+            }
+        }
+        encoded
+    }
+}
+
+/// Serialize a `QueryMap` like `encode`, with the characters and key order of
+/// `options`.
+pub fn encode_with(params: &QueryMap, options: &EncodeOptions) -> String {
+    let mut keys: Vec<&String> = params.keys().collect();
+    keys.sort();
+
+    let pairs = keys.into_iter()
+        .flat_map(|key| params[key].iter().map(move |value| (&**key, &**value)));
+    encode_pairs_with(pairs, options)
+}
+
+/// Serialize `(key, value)` pairs, such as the ones of `decode_pairs`, with
+/// `options`. With `KeyOrder::Input`, they are written in the order given.
+pub fn encode_pairs_with<'a, I>(pairs: I, options: &EncodeOptions) -> String
+where I: IntoIterator<Item = (&'a str, &'a str)> {
+    let mut pairs: Vec<(&str, &str)> = pairs.into_iter().collect();
+    match options.order {
+        KeyOrder::Sorted => pairs.sort_by(|a, b| a.0.cmp(b.0)),
+        KeyOrder::Input => (),
+        KeyOrder::Custom(ref compare) => pairs.sort_by(|a, b| compare(a.0, b.0))
+    }
+
+    pairs.into_iter()
+        .map(|(key, value)| format!("{}={}", options.encode_component(key), options.encode_component(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Serialize a nested tree with bracket syntax, the inverse of `parse_nested`
+/// with the default `NestedOptions`.
+///
+/// Map entries are written in key order and list elements with explicit
+/// indices, as in `user[tags][0]=a`, so lists of maps come back intact.
+/// Parsing the output reproduces the tree exactly, as long as it holds no
+/// empty lists or maps, which produce no pairs, and its map keys are neither
+/// empty nor contain brackets. Below the top level, keys made only of digits
+/// are read back as list indices.
+pub fn encode_nested(tree: &NestedQueryMap) -> String {
+    let mut pairs = Vec::new();
+    for (key, value) in tree {
+        flatten(encode_component(key), value, &mut pairs);
+    }
+    pairs.join("&")
+}
+
+/// Add the `key=value` pairs of `value`, found at the encoded key `prefix`.
+fn flatten(prefix: String, value: &NestedValue, pairs: &mut Vec<String>) {
+    match *value {
+        NestedValue::Value(ref value) => pairs.push(format!("{}={}", prefix, encode_component(value))),
+        NestedValue::List(ref items) => for (index, item) in items.iter().enumerate() {
+            flatten(format!("{}[{}]", prefix, index), item, pairs);
+        },
+        NestedValue::Map(ref entries) => for (key, entry) in entries {
+            flatten(format!("{}[{}]", prefix, encode_component(key)), entry, pairs);
+        }
+    }
+}
+
+/// Encode a single key or value exactly as `encode` does: everything but
+/// `A-Z a-z 0-9 * - . _` is percent-encoded, and spaces become `+`. For
+/// building links and hidden fields by hand.
+pub fn encode_component(component: &str) -> String {
+    utf8_percent_encode(component, FORM_URLENCODED_ENCODE_SET).replace("%20", "+")
+}
+
+/// Decode a single key or value exactly as the parser does with the default
+/// `ParseOptions`: `+` is a space and invalid UTF-8 is replaced with `U+FFFD`.
+pub fn decode_component(component: &str) -> String {
+    decode(component, &ParseOptions::new())
+}
+
+/// Encode a `key=value` pair with `encode_component`.
+pub fn encode_pair(key: &str, value: &str) -> String {
+    format!("{}={}", encode_component(key), encode_component(value))
+}
+
+#[test]
+fn test_encode() {
+    use super::{parse_with, ParseOptions};
+
+    let data = "b=%26+%3D&a=1&a=%C3%A9";
+    let params = parse_with(data, &ParseOptions::new()).unwrap();
+    assert_eq!(encode(&params), "a=1&a=%C3%A9&b=%26+%3D");
+    assert_eq!(parse_with(&encode(&params), &ParseOptions::new()).unwrap(), params);
+
+    assert_eq!(encode_pair("a b", "&=é*~"), "a+b=%26%3D%C3%A9*%7E");
+    assert_eq!(decode_component("a+b%2B%C3%A9%FF"), "a b+é\u{FFFD}");
+    for component in &["", "a b", "&=+%", "é~*'"] {
+        assert_eq!(decode_component(&encode_component(component)), *component);
+    }
+}
+
+#[test]
+fn test_encode_with() {
+    use super::{parse_with, ParseOptions};
+
+    let params = parse_with("t=12%3A30%2C13%3A00&q=it%27s+a*b~", &ParseOptions::new()).unwrap();
+    assert_eq!(encode_with(&params, &EncodeOptions::new()), encode(&params));
+
+    let readable = EncodeOptions::new().encode_set(EncodeSet::form().keep(":,").escape("*"));
+    assert_eq!(encode_with(&params, &readable), "q=it%27s+a%2Ab%7E&t=12:30,13:00");
+    let strict = EncodeOptions::new().encode_set(EncodeSet::rfc3986().keep("'"));
+    assert_eq!(strict.encode_component("it's a~é"), "it's+a~%C3%A9");
+    assert!(!EncodeSet::form().keep("é").keeps(0xC3));
+
+    let signed = EncodeOptions::new().spaces(Spaces::Percent).encode_tilde(false).encode_star(true);
+    assert_eq!(signed.encode_component("a b~*"), "a%20b~%2A");
+    assert_eq!(signed.encode_set(EncodeSet::rfc3986()).encode_component("a b"), "a%20b");
+
+    let pairs = vec![("b", "1"), ("a", "2"), ("b", "0")];
+    assert_eq!(encode_pairs_with(pairs.clone(), &EncodeOptions::new()), "a=2&b=1&b=0");
+    assert_eq!(encode_pairs_with(pairs.clone(), &EncodeOptions::new().order(KeyOrder::Input)), "b=1&a=2&b=0");
+    let reverse = EncodeOptions::new().order(KeyOrder::custom(|a, b| b.cmp(a)));
+    assert_eq!(encode_pairs_with(pairs, &reverse), "b=1&b=0&a=2");
+    assert_eq!(encode_with(&params, &reverse.order(KeyOrder::Input)), encode(&params));
+}
+
+#[test]
+fn test_encode_nested_round_trip() {
+    use super::{parse_nested, ParseOptions, NestedOptions};
+
+    // A small xorshift generator, so the property holds for arbitrary trees
+    // without pulling in a property testing crate.
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: u64) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 % n
+        }
+
+        fn text(&mut self, alphabet: &[&str]) -> String {
+            (0..self.below(4)).map(|_| alphabet[self.below(alphabet.len() as u64) as usize]).collect()
+        }
+
+        fn value(&mut self, depth: u32) -> NestedValue {
+            match if depth == 0 { 0 } else { self.below(3) } {
+                0 => NestedValue::Value(self.text(&["a", "1", " ", "&", "=", "[", "]", "%", "+", "é"])),
+                1 => NestedValue::List((0..1 + self.below(3)).map(|_| self.value(depth - 1)).collect()),
+                _ => NestedValue::Map(self.map(depth - 1))
+            }
+        }
+
+        fn map(&mut self, depth: u32) -> NestedQueryMap {
+            (0..1 + self.below(3)).map(|_| (format!("k{}", self.text(&["x", "y", "_", "é"])), self.value(depth)))
+                .collect()
+        }
+    }
+
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    for _ in 0..500 {
+        let tree = rng.map(3);
+        let encoded = encode_nested(&tree);
+        assert_eq!(parse_nested(&encoded, &ParseOptions::new(), &NestedOptions::new()).unwrap(), tree,
+                   "{} did not round-trip", encoded);
+    }
+
+    let tree = parse_nested("user[tags][]=a&user[tags][]=b+c&user[name]=Ann",
+                            &ParseOptions::new(), &NestedOptions::new()).unwrap();
+    assert_eq!(encode_nested(&tree), "user[name]=Ann&user[tags][0]=a&user[tags][1]=b+c");
+}
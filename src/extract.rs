@@ -0,0 +1,142 @@
+//! Extracting several typed parameters at once, reporting every field that
+//! failed rather than the first one.
+
+use std::str::FromStr;
+
+use super::{QueryMap, UrlDecodingError};
+use super::patch::Patch;
+use super::schema::{FieldError, FieldErrorKind};
+
+/// Collects typed values from a `QueryMap` along with the errors of every
+/// field, which `finish` returns together as `ValidationFailed`.
+///
+/// The `extract!` macro is the short way to use it; the methods suit fields
+/// extracted conditionally.
+///
+/// ```ignore
+/// let mut extractor = Extractor::new(&params);
+/// let name: Option<String> = extractor.required("name");
+/// let age: Option<Option<u32>> = extractor.optional("age");
+/// extractor.finish()?;
+/// ```
+#[derive(Debug)]
+pub struct Extractor<'a> {
+    params: &'a QueryMap,
+    errors: Vec<FieldError>
+}
+
+impl<'a> Extractor<'a> {
+    /// Extract values from `params`.
+    pub fn new(params: &'a QueryMap) -> Extractor<'a> {
+        Extractor { params, errors: Vec::new() }
+    }
+
+    fn fail<T>(&mut self, key: &str, kind: FieldErrorKind) -> Option<T> {
+        self.errors.push(FieldError::new(key, kind));
+        None
+    }
+
+    fn parse<T: FromStr>(&mut self, key: &str, value: &str) -> Option<T> {
+        match value.parse() {
+            Ok(parsed) => Some(parsed),
+            Err(_) => self.fail(key, FieldErrorKind::Custom(format!("has an invalid value `{}`", value)))
+        }
+    }
+
+    /// The single, non-empty value of `key`. `None` when it failed, with the
+    /// error recorded.
+    pub fn required<T: FromStr>(&mut self, key: &str) -> Option<T> {
+        match self.optional(key) {
+            Some(Some(value)) => Some(value),
+            Some(None) => self.fail(key, FieldErrorKind::Required),
+            None => None
+        }
+    }
+
+    /// The value of `key`, which may be absent or empty but not sent more
+    /// than once. `None` when it failed, with the error recorded.
+    pub fn optional<T: FromStr>(&mut self, key: &str) -> Option<Option<T>> {
+        let params = self.params;
+        match params.get(key).map(|values| &values[..]) {
+            None | Some([]) => Some(None),
+            Some([value]) if value.is_empty() => Some(None),
+            Some([value]) => self.parse(key, value).map(Some),
+            Some(_) => self.fail(key, FieldErrorKind::Repeated)
+        }
+    }
+
+    /// Every value of `key`, possibly none. `None` when one of them failed to
+    /// parse, with the error recorded.
+    pub fn multi<T: FromStr>(&mut self, key: &str) -> Option<Vec<T>> {
+        let params = self.params;
+        let values = params.get(key).map_or(&[][..], |values| &values[..]);
+        values.iter().map(|value| self.parse(key, value)).collect::<Vec<_>>().into_iter().collect()
+    }
+
+    /// The change a `PATCH` form makes to `key`, telling a field left out
+    /// from one sent empty. `None` when it failed, with the error recorded.
+    pub fn patch<T: FromStr>(&mut self, key: &str) -> Option<Patch<T>> {
+        let params = self.params;
+        match params.get(key).map(|values| &values[..]) {
+            None | Some([]) => Some(Patch::Missing),
+            Some([value]) if value.trim().is_empty() => Some(Patch::Null),
+            Some([value]) => self.parse(key, value).map(Patch::Value),
+            Some(_) => self.fail(key, FieldErrorKind::Repeated)
+        }
+    }
+
+    /// The errors of every field, if any failed.
+    pub fn finish(self) -> Result<(), UrlDecodingError> {
+        if self.errors.is_empty() { Ok(()) } else { Err(UrlDecodingError::ValidationFailed(self.errors)) }
+    }
+}
+
+/// Extract several parameters of a `QueryMap` at once as a tuple, named
+/// after their keys, or fail with `ValidationFailed` listing every field in
+/// error.
+///
+/// Fields are `required`, `optional` (giving an `Option`), `multi` (with a
+/// `Vec` type, taking every value of the key) or `patch` (giving a `Patch`).
+/// Values are parsed with `FromStr`.
+///
+/// ```ignore
+/// let (name, age, tags) = extract!(params, required name: String, optional age: u32,
+///                                  multi tags: Vec<String>)?;
+/// ```
+#[macro_export]
+macro_rules! extract {
+    (@type optional $ty:ty) => { Option<$ty> };
+    (@type patch $ty:ty) => { $crate::Patch<$ty> };
+    (@type $mode:ident $ty:ty) => { $ty };
+    ($params:expr, $($mode:ident $name:ident : $ty:ty),+ $(,)*) => {{
+        let mut extractor = $crate::Extractor::new(&$params);
+        $(let $name: Option<extract!(@type $mode $ty)> = extractor.$mode(stringify!($name));)+
+        extractor.finish().map(|()| ($($name.expect("extracted field"),)+))
+    }};
+}
+
+#[test]
+fn test_extract() {
+    use super::{parse_with, ParseOptions};
+
+    let params = parse_with("name=Ada&age=36&tags=a&tags=b", &ParseOptions::new()).unwrap();
+    let (name, age, tags) = extract!(params, required name: String, optional age: u32,
+                                     multi tags: Vec<String>).unwrap();
+    assert_eq!((name, age, tags), ("Ada".to_owned(), Some(36), vec!["a".to_owned(), "b".to_owned()]));
+
+    let params = parse_with("nickname=&age=37", &ParseOptions::new()).unwrap();
+    let (nickname, age, bio) = extract!(params, patch nickname: String, patch age: u32, patch bio: String).unwrap();
+    assert_eq!((nickname, age, bio), (Patch::Null, Patch::Value(37), Patch::Missing));
+
+    let params = parse_with("name=&age=old&ids=1&ids=x&page=1&page=2", &ParseOptions::new()).unwrap();
+    let fields = match extract!(params, required name: String, optional age: u32, multi ids: Vec<u64>,
+                                optional page: u32, patch lang: String) {
+        Err(UrlDecodingError::ValidationFailed(errors)) => errors,
+        other => panic!("unexpected {:?}", other)
+    };
+    let kinds: Vec<(&str, &FieldErrorKind)> = fields.iter().map(|error| (&*error.field, &error.kind)).collect();
+    assert_eq!(kinds, vec![("name", &FieldErrorKind::Required),
+                           ("age", &FieldErrorKind::Custom("has an invalid value `old`".to_owned())),
+                           ("ids", &FieldErrorKind::Custom("has an invalid value `x`".to_owned())),
+                           ("page", &FieldErrorKind::Repeated)]);
+}
@@ -0,0 +1,114 @@
+//! Recognizing a form posted twice, such as by a double click or a browser
+//! retrying, from the values it was submitted with.
+
+use std::time::Duration;
+
+#[cfg(feature = "iron")]
+use iron::prelude::*;
+
+use super::QueryMap;
+#[cfg(feature = "iron")]
+use super::{UrlDecodingError, form_params};
+use super::encode::{EncodeOptions, EncodeSet, Spaces, encode_pairs_with};
+use super::ratelimit::fnv1a;
+
+/// A stable digest of the values of `params`, but for `exclude_keys`, such
+/// as a CSRF token changing with every page: 16 hex digits of a 64-bit FNV-1a
+/// hash of the parameters sorted by key and encoded with `rfc3986_encode`.
+///
+/// Equal forms always have the same fingerprint, however their keys were
+/// ordered or their values encoded; the values of a key keep their order.
+pub fn form_fingerprint(params: &QueryMap, exclude_keys: &[&str]) -> String {
+    let pairs = params.iter()
+        .filter(|&(key, _)| !exclude_keys.contains(&&**key))
+        .flat_map(|(key, values)| values.iter().map(move |value| (&**key, &**value)));
+    let options = EncodeOptions::new().encode_set(EncodeSet::rfc3986()).spaces(Spaces::Percent);
+    format!("{:016x}", fnv1a(encode_pairs_with(pairs, &options).as_bytes()))
+}
+
+/// Where recent fingerprints are kept, implemented by the application on a
+/// store shared by its servers, such as Redis or memcached.
+pub trait SubmissionStore {
+    /// Record that `fingerprint` was just submitted, for `window`, and tell
+    /// whether it had already been within the window before.
+    fn check_and_record(&self, fingerprint: &str, window: Duration) -> bool;
+}
+
+/// Detects forms submitted again with the same values within a time window.
+///
+/// ```ignore
+/// let orders = DuplicateSubmissions::new("orders", RedisStore::new(pool), Duration::from_secs(10))
+///     .exclude(&["csrf_token"]);
+/// if orders.from_request(req)? {
+///     return Ok(Response::with((status::Conflict, "This order was already placed.")));
+/// }
+/// ```
+pub struct DuplicateSubmissions<S: SubmissionStore> {
+    scope: String,
+    store: S,
+    window: Duration,
+    exclude: Vec<String>
+}
+
+impl<S: SubmissionStore> DuplicateSubmissions<S> {
+    /// Remember the forms of `scope`, so other forms don't share fingerprints
+    /// with them, in `store` for `window`.
+    pub fn new(scope: &str, store: S, window: Duration) -> DuplicateSubmissions<S> {
+        DuplicateSubmissions { scope: scope.to_owned(), store, window, exclude: Vec::new() }
+    }
+
+    /// Leave `keys` out of the fingerprint.
+    pub fn exclude(mut self, keys: &[&str]) -> DuplicateSubmissions<S> {
+        self.exclude.extend(keys.iter().map(|key| (*key).to_owned()));
+        self
+    }
+
+    /// The fingerprint of `params` in the store, as `<scope>:<fingerprint>`.
+    pub fn fingerprint(&self, params: &QueryMap) -> String {
+        let exclude: Vec<&str> = self.exclude.iter().map(|key| &**key).collect();
+        format!("{}:{}", self.scope, form_fingerprint(params, &exclude))
+    }
+
+    /// Record the submission of `params`, and tell whether the same form was
+    /// already submitted within the window.
+    pub fn is_duplicate(&self, params: &QueryMap) -> bool {
+        self.store.check_and_record(&self.fingerprint(params), self.window)
+    }
+
+    /// Like `is_duplicate`, for the submitted form of `req`: the body of
+    /// `application/x-www-form-urlencoded` requests, the query string
+    /// otherwise.
+    #[cfg(feature = "iron")]
+    pub fn from_request(&self, req: &mut Request) -> Result<bool, UrlDecodingError> {
+        form_params(req).map(|params| self.is_duplicate(&params))
+    }
+}
+
+#[test]
+fn test_form_fingerprint() {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::time::Instant;
+    use super::{parse_with, ParseOptions};
+
+    let parse = |data: &str| parse_with(data, &ParseOptions::new()).unwrap();
+    let fingerprint = form_fingerprint(&parse("item=7&qty=1&csrf=a"), &["csrf"]);
+    assert_eq!(form_fingerprint(&parse("qty=1&csrf=b&item=%37"), &["csrf"]), fingerprint);
+    assert!(form_fingerprint(&parse("item=7&qty=2"), &[]) != fingerprint);
+
+    struct Memory(RefCell<HashMap<String, Instant>>);
+    impl SubmissionStore for Memory {
+        fn check_and_record(&self, fingerprint: &str, window: Duration) -> bool {
+            let now = Instant::now();
+            let previous = self.0.borrow_mut().insert(fingerprint.to_owned(), now);
+            previous.is_some_and(|at| now.duration_since(at) < window)
+        }
+    }
+
+    let orders = DuplicateSubmissions::new("orders", Memory(RefCell::new(HashMap::new())), Duration::from_secs(60))
+        .exclude(&["csrf"]);
+    assert!(orders.fingerprint(&parse("item=7")).starts_with("orders:"));
+    assert!(!orders.is_duplicate(&parse("item=7&csrf=a")));
+    assert!(orders.is_duplicate(&parse("item=7&csrf=b")));
+    assert!(!orders.is_duplicate(&parse("item=8")));
+}
@@ -0,0 +1,191 @@
+//! Feature-flag and A/B test overrides passed as parameters, such as
+//! `ff_new_checkout=1`.
+
+use std::collections::BTreeMap;
+use std::collections::btree_map;
+use std::sync::Arc;
+
+use rustc_serialize::base64::{self, ToBase64, FromBase64};
+
+use super::{QueryMap, UrlDecodingError};
+use super::accessors::QueryMapExt;
+use super::encode::encode;
+use super::signing::{Signer, constant_time_eq};
+
+/// The flags a request overrides, by name and without their prefix.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FlagSet(BTreeMap<String, bool>);
+
+impl FlagSet {
+    /// No overrides.
+    pub fn new() -> FlagSet {
+        FlagSet::default()
+    }
+
+    /// Override `name` with `enabled`.
+    pub fn set(&mut self, name: &str, enabled: bool) {
+        self.0.insert(name.to_owned(), enabled);
+    }
+
+    /// The override of `name`, if the request has one.
+    pub fn get(&self, name: &str) -> Option<bool> {
+        self.0.get(name).cloned()
+    }
+
+    /// Whether `name` is enabled, falling back to `default` without an
+    /// override.
+    pub fn is_enabled(&self, name: &str, default: bool) -> bool {
+        self.get(name).unwrap_or(default)
+    }
+
+    /// Whether the request overrides no flag.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The overrides, in name order.
+    pub fn iter(&self) -> btree_map::Iter<'_, String, bool> {
+        self.0.iter()
+    }
+}
+
+/// Reads feature-flag overrides out of the parameters of a request.
+///
+/// Only the flags of the allowlist can be overridden, each with a parameter
+/// named after it with the `ff_` prefix, set to `1`, `true` or `on` to enable
+/// the flag and to `0`, `false` or `off` to disable it. Other parameters with
+/// the prefix are ignored.
+///
+/// When a signer is configured, overrides are only honored along with a valid
+/// `ff_sig`, so links made by internal tools can't be edited by hand:
+///
+/// ```ignore
+/// let flags = FeatureFlags::new(&["new_checkout", "dark_mode"]).signed(hmac_sha256, SECRET);
+/// // In the internal tool:
+/// let link = format!("/cart?{}", flags.encode(&overrides));
+/// // In the handler:
+/// if flags.extract(&params)?.is_enabled("new_checkout", false) { ... }
+/// ```
+#[derive(Clone)]
+pub struct FeatureFlags {
+    prefix: String,
+    allowed: Vec<String>,
+    signing: Option<(Arc<dyn Signer + Send + Sync>, Vec<u8>)>
+}
+
+impl FeatureFlags {
+    /// Unsigned overrides of the flags in `allowed`, prefixed with `ff_`.
+    pub fn new(allowed: &[&str]) -> FeatureFlags {
+        FeatureFlags {
+            prefix: "ff_".to_owned(),
+            allowed: allowed.iter().map(|name| (*name).to_owned()).collect(),
+            signing: None
+        }
+    }
+
+    /// Prefix the override parameters with `prefix` instead of `ff_`. The
+    /// signature is in `<prefix>sig`.
+    pub fn prefix(mut self, prefix: &str) -> FeatureFlags {
+        self.prefix = prefix.to_owned();
+        self
+    }
+
+    /// Only honor overrides signed with `signer` and `key`.
+    pub fn signed<S>(mut self, signer: S, key: &[u8]) -> FeatureFlags
+    where S: Signer + Send + Sync + 'static {
+        self.signing = Some((Arc::new(signer), key.to_vec()));
+        self
+    }
+
+    fn signature_param(&self) -> String {
+        format!("{}sig", self.prefix)
+    }
+
+    /// The parameters of `params` overriding a flag of the allowlist.
+    fn overrides(&self, params: &QueryMap) -> QueryMap {
+        self.allowed.iter()
+            .map(|name| format!("{}{}", self.prefix, name))
+            .filter_map(|key| params.get(&key).map(|values| (key, values.clone())))
+            .collect()
+    }
+
+    fn signature(&self, overrides: &QueryMap) -> Option<Vec<u8>> {
+        self.signing.as_ref().map(|(signer, key)| signer.sign(key, encode(overrides).as_bytes()))
+    }
+
+    /// Encode `flags` as query string parameters, with their signature when a
+    /// signer is configured. Flags outside the allowlist are left out.
+    pub fn encode(&self, flags: &FlagSet) -> String {
+        let mut params = QueryMap::new();
+        for (name, &enabled) in flags.iter().filter(|&(name, _)| self.allowed.contains(name)) {
+            params.insert(format!("{}{}", self.prefix, name), vec![if enabled { "1" } else { "0" }.to_owned()]);
+        }
+
+        if let Some(signature) = self.signature(&params) {
+            params.insert(self.signature_param(), vec![signature.to_base64(base64::URL_SAFE)]);
+        }
+        encode(&params)
+    }
+
+    /// The overrides in `params`.
+    ///
+    /// Fails when an override is sent twice or isn't a boolean, and when the
+    /// overrides of a signed `FeatureFlags` aren't signed properly.
+    pub fn extract(&self, params: &QueryMap) -> Result<FlagSet, UrlDecodingError> {
+        let overrides = self.overrides(params);
+        if overrides.is_empty() {
+            return Ok(FlagSet::new());
+        }
+
+        if let Some(expected) = self.signature(&overrides) {
+            let signature = params.first(&self.signature_param())
+                .and_then(|signature| signature.from_base64().ok());
+            match signature {
+                Some(ref signature) if constant_time_eq(signature, &expected) => (),
+                _ => return Err(UrlDecodingError::InvalidParameter {
+                    key: self.signature_param(),
+                    message: "is missing or doesn't match the overrides".to_owned()
+                })
+            }
+        }
+
+        let mut flags = FlagSet::new();
+        for name in &self.allowed {
+            let key = format!("{}{}", self.prefix, name);
+            let enabled = match overrides.single(&key)? {
+                Some("1") | Some("true") | Some("on") => true,
+                Some("0") | Some("false") | Some("off") => false,
+                Some(_) => return Err(UrlDecodingError::InvalidParameter {
+                    key,
+                    message: "must be 1 or 0".to_owned()
+                }),
+                None => continue
+            };
+            flags.set(name, enabled);
+        }
+        Ok(flags)
+    }
+}
+
+#[test]
+fn test_feature_flags() {
+    use super::{parse_with, ParseOptions};
+
+    let parse = |data: &str| parse_with(data, &ParseOptions::new()).unwrap();
+
+    let flags = FeatureFlags::new(&["new_checkout", "dark_mode"]);
+    let overrides = flags.extract(&parse("ff_new_checkout=1&ff_dark_mode=off&ff_admin=1&page=2")).unwrap();
+    assert_eq!(overrides.get("new_checkout"), Some(true));
+    assert_eq!(overrides.get("dark_mode"), Some(false));
+    assert_eq!(overrides.get("admin"), None);
+    assert!(overrides.is_enabled("beta", true));
+    assert!(flags.extract(&parse("ff_dark_mode=maybe")).is_err());
+    assert!(flags.extract(&parse("page=2")).unwrap().is_empty());
+
+    let signer = super::signing::test_signer;
+    let signed = flags.signed(signer, b"secret");
+    let link = signed.encode(&overrides);
+    assert_eq!(signed.extract(&parse(&link)).unwrap(), overrides);
+    assert!(signed.extract(&parse(&link.replace("ff_new_checkout=1", "ff_new_checkout=0"))).is_err());
+    assert!(signed.extract(&parse("ff_new_checkout=1")).is_err());
+}
@@ -0,0 +1,150 @@
+//! Carrying a few parameters, such as validation errors and old input, from a
+//! form submission to the page it redirects to (Post/Redirect/Get).
+
+use std::sync::Arc;
+
+use rustc_serialize::base64::{self, ToBase64, FromBase64};
+
+use super::{QueryMap, UrlDecodingError, ParseOptions, parse_with};
+use super::encode::encode;
+use super::signing::{Signer, constant_time_eq};
+
+/// Encodes flash parameters into a redirect's query string, and reads them
+/// back on the next request.
+///
+/// The flash is stored as a single parameter, `_flash` by default. When a
+/// signer is configured, a `_flash_sig` parameter protects it from tampering.
+///
+/// ```ignore
+/// let flash = Flash::new().signed(hmac_sha256, SECRET);
+/// let location = format!("/signup?{}", flash.encode(&errors)?);
+/// // On the next request:
+/// let errors = flash.take(&mut params)?;
+/// ```
+#[derive(Clone)]
+pub struct Flash {
+    param: String,
+    max_len: usize,
+    signing: Option<(Arc<dyn Signer + Send + Sync>, Vec<u8>)>
+}
+
+impl Default for Flash {
+    fn default() -> Flash {
+        Flash { param: "_flash".to_owned(), max_len: 2048, signing: None }
+    }
+}
+
+impl Flash {
+    /// An unsigned flash in `_flash`, of at most 2048 encoded bytes.
+    pub fn new() -> Flash {
+        Flash::default()
+    }
+
+    /// Store the flash in `param` instead of `_flash`.
+    pub fn param(mut self, param: &str) -> Flash {
+        self.param = param.to_owned();
+        self
+    }
+
+    /// Refuse to encode or decode flashes longer than `max_len` encoded bytes.
+    pub fn max_len(mut self, max_len: usize) -> Flash {
+        self.max_len = max_len;
+        self
+    }
+
+    /// Sign the flash with `signer` and `key`, and reject unsigned or tampered
+    /// flashes when reading them back.
+    pub fn signed<S>(mut self, signer: S, key: &[u8]) -> Flash
+    where S: Signer + Send + Sync + 'static {
+        self.signing = Some((Arc::new(signer), key.to_vec()));
+        self
+    }
+
+    fn signature_param(&self) -> String {
+        format!("{}_sig", self.param)
+    }
+
+    fn too_long(&self) -> UrlDecodingError {
+        UrlDecodingError::ValueTooLong { key: self.param.clone(), limit: self.max_len }
+    }
+
+    /// Encode `flash` as query string parameters to append to a redirect URL.
+    pub fn encode(&self, flash: &QueryMap) -> Result<String, UrlDecodingError> {
+        let payload = encode(flash);
+        if payload.len() > self.max_len {
+            return Err(self.too_long());
+        }
+
+        let mut carrier = QueryMap::new();
+        carrier.insert(self.param.clone(), vec![payload.clone()]);
+        if let Some((ref signer, ref key)) = self.signing {
+            let signature = signer.sign(key, payload.as_bytes()).to_base64(base64::URL_SAFE);
+            carrier.insert(self.signature_param(), vec![signature]);
+        }
+
+        Ok(encode(&carrier))
+    }
+
+    /// Read the flash out of the parameters of a request, if there is one.
+    pub fn decode(&self, params: &QueryMap) -> Result<Option<QueryMap>, UrlDecodingError> {
+        let payload = match params.get(&self.param).and_then(|values| values.first()) {
+            Some(payload) => payload,
+            None => return Ok(None)
+        };
+        if payload.len() > self.max_len {
+            return Err(self.too_long());
+        }
+
+        if let Some((ref signer, ref key)) = self.signing {
+            let signature = params.get(&self.signature_param())
+                .and_then(|values| values.first())
+                .and_then(|signature| signature.from_base64().ok());
+            let expected = signer.sign(key, payload.as_bytes());
+            match signature {
+                Some(ref signature) if constant_time_eq(signature, &expected) => (),
+                _ => return Err(UrlDecodingError::InvalidParameter {
+                    key: self.param.clone(),
+                    message: "has a missing or invalid signature".to_owned()
+                })
+            }
+        }
+
+        match parse_with(payload, &ParseOptions::new()) {
+            Ok(flash) => Ok(Some(flash)),
+            Err(UrlDecodingError::EmptyQuery) => Ok(Some(QueryMap::new())),
+            Err(err) => Err(err)
+        }
+    }
+
+    /// Like `decode`, but also remove the flash parameters so that the rest of
+    /// the handler only sees the regular ones.
+    pub fn take(&self, params: &mut QueryMap) -> Result<Option<QueryMap>, UrlDecodingError> {
+        let flash = self.decode(params);
+        params.remove(&self.param);
+        params.remove(&self.signature_param());
+        flash
+    }
+}
+
+#[test]
+fn test_flash_round_trip() {
+    let mut errors = QueryMap::new();
+    errors.insert("email".to_owned(), vec!["is already taken".to_owned()]);
+
+    let signer = super::signing::test_signer;
+    let flash = Flash::new().signed(signer, b"secret");
+
+    let query = flash.encode(&errors).unwrap();
+    let mut params = parse_with(&format!("page=2&{}", query), &ParseOptions::new()).unwrap();
+    assert_eq!(flash.take(&mut params).unwrap(), Some(errors.clone()));
+    assert_eq!(params.keys().collect::<Vec<_>>(), vec!["page"]);
+
+    let tampered = query.replace("taken", "free");
+    let params = parse_with(&tampered, &ParseOptions::new()).unwrap();
+    assert!(flash.decode(&params).is_err());
+    assert!(Flash::new().signed(signer, b"other").decode(
+        &parse_with(&query, &ParseOptions::new()).unwrap()).is_err());
+
+    assert!(Flash::new().max_len(10).encode(&errors).is_err());
+    assert_eq!(Flash::new().decode(&QueryMap::new()).unwrap(), None);
+}
@@ -0,0 +1,116 @@
+//! Parsing bodies without blocking the calling thread, for async I/O stacks.
+//!
+//! `ParseFuture` is a `std::future::Future`, the trait the `futures` crate
+//! re-exports, so it can be awaited or combined on any executor.
+
+use std::future::Future;
+use std::io::Read;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+use super::{QueryResult, UrlEncodedConfig};
+use super::reader::parse_reader;
+
+struct Shared {
+    result: Option<QueryResult>,
+    waker: Option<Waker>
+}
+
+/// The work of reading and parsing a body, which blocks until the body is
+/// read, handed to the `spawn` function of `parse_body_future_with`.
+pub type ParseJob = Box<dyn FnOnce() + Send>;
+
+/// The result of parsing a body read on a thread of its own, so that a slow
+/// client only ever holds that thread.
+pub struct ParseFuture {
+    shared: Arc<Mutex<Shared>>
+}
+
+/// Read a body of `content_length` bytes from `body` and parse it with
+/// `config`, like `parse_reader` does, on a new thread. The returned future
+/// completes once the body is parsed.
+///
+/// Every call starts an OS thread, which lives as long as the client takes
+/// to send the body, so a burst of requests is a burst of threads. Servers
+/// exposed to many slow clients should run the parsing on a bounded pool
+/// with `parse_body_future_with`.
+///
+/// ```ignore
+/// let params = parse_body_future(socket, content_length, 64 * 1024, config).await?;
+/// ```
+pub fn parse_body_future<R>(body: R, content_length: u64, limit: usize, config: UrlEncodedConfig) -> ParseFuture
+where R: Read + Send + 'static {
+    parse_body_future_with(body, content_length, limit, config, |job| { thread::spawn(job); })
+}
+
+/// Like `parse_body_future`, running the parsing with `spawn`, such as on
+/// the blocking pool of an executor or a thread pool of fixed size.
+///
+/// ```ignore
+/// let params = parse_body_future_with(socket, content_length, 64 * 1024, config,
+///                                     |job| pool.execute(job)).await?;
+/// ```
+pub fn parse_body_future_with<R, S>(body: R, content_length: u64, limit: usize, config: UrlEncodedConfig,
+                                    spawn: S) -> ParseFuture
+where R: Read + Send + 'static, S: FnOnce(ParseJob) {
+    let shared = Arc::new(Mutex::new(Shared { result: None, waker: None }));
+    let thread_shared = shared.clone();
+    spawn(Box::new(move || {
+        let result = parse_reader(body, content_length, limit, &config);
+        let mut shared = thread_shared.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        shared.result = Some(result);
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+    }));
+    ParseFuture { shared }
+}
+
+impl Future for ParseFuture {
+    type Output = QueryResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<QueryResult> {
+        let mut shared = self.shared.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        match shared.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[test]
+fn test_parse_body_future() {
+    use std::task::Wake;
+
+    struct Unpark(thread::Thread);
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let block_on = |mut future: ParseFuture| {
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match Pin::new(&mut future).poll(&mut cx) {
+                Poll::Ready(result) => return result,
+                Poll::Pending => thread::park()
+            }
+        }
+    };
+
+    let body: &'static [u8] = b"a=1&b=2";
+    let params = block_on(parse_body_future(body, 7, 1024, UrlEncodedConfig::new())).unwrap();
+    assert_eq!(params["b"], vec!["2".to_owned()]);
+    assert!(block_on(parse_body_future(body, 7, 3, UrlEncodedConfig::new())).is_err());
+
+    // Running the job right away is an executor too, if a blocking one.
+    let inline = parse_body_future_with(body, 7, 1024, UrlEncodedConfig::new(), |job: ParseJob| job());
+    assert_eq!(block_on(inline).unwrap()["a"], vec!["1".to_owned()]);
+}
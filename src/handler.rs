@@ -0,0 +1,98 @@
+//! Handlers receiving the submitted form already converted to a type of the
+//! application.
+
+use std::marker::PhantomData;
+
+use iron::prelude::*;
+use iron::Handler;
+
+use super::{QueryMap, QueryResult, UrlDecodingError, form_params};
+
+/// A type built from the parameters of a form, for `with_form`.
+///
+/// ```ignore
+/// impl FromForm for Signup {
+///     fn from_form(params: &QueryMap) -> Result<Signup, UrlDecodingError> {
+///         let (email, age) = extract!(params, required email: String, optional age: u32)?;
+///         Ok(Signup { email, age })
+///     }
+/// }
+/// ```
+pub trait FromForm: Sized {
+    /// Convert or validate `params`, failing with the error to answer with.
+    fn from_form(params: &QueryMap) -> Result<Self, UrlDecodingError>;
+}
+
+impl FromForm for QueryMap {
+    fn from_form(params: &QueryMap) -> Result<QueryMap, UrlDecodingError> {
+        Ok(params.clone())
+    }
+}
+
+/// A `Handler` converting the submitted form to `T` before calling the
+/// handler it wraps, built by `with_form`.
+pub struct FormHandler<T, H> {
+    handler: H,
+    marker: PhantomData<fn() -> T>
+}
+
+/// Wrap `handler` so it receives the submitted form as a `T`: the body of
+/// `application/x-www-form-urlencoded` and `multipart/form-data` requests,
+/// the query string otherwise. Forms that fail to parse or convert are
+/// answered with the response of the `UrlEncodedConfig`, without calling
+/// `handler`.
+///
+/// ```ignore
+/// let signup = with_form(|req: &mut Request, form: Signup| {
+///     Ok(Response::with((status::Ok, format!("Welcome, {}", form.email))))
+/// });
+/// ```
+pub fn with_form<T, H>(handler: H) -> FormHandler<T, H>
+where T: FromForm + 'static, H: Fn(&mut Request, T) -> IronResult<Response> + Send + Sync + 'static {
+    FormHandler { handler, marker: PhantomData }
+}
+
+/// The form in `result` as a `T`, an empty form standing for no parameters.
+fn convert<T: FromForm>(result: QueryResult) -> Result<T, UrlDecodingError> {
+    match result {
+        Ok(params) => T::from_form(&params),
+        Err(UrlDecodingError::EmptyQuery) => T::from_form(&QueryMap::new()),
+        Err(err) => Err(err)
+    }
+}
+
+impl<T, H> Handler for FormHandler<T, H>
+where T: FromForm + 'static, H: Fn(&mut Request, T) -> IronResult<Response> + Send + Sync + 'static {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        let result = form_params(req);
+        match convert(result) {
+            Ok(form) => (self.handler)(req, form),
+            Err(err) => Err(err.into_iron_error(req))
+        }
+    }
+}
+
+#[test]
+fn test_with_form() {
+    use iron::status;
+    use super::{parse_with, ParseOptions};
+
+    #[derive(Debug, PartialEq)]
+    struct Search { q: String, page: Option<u32> }
+    impl FromForm for Search {
+        fn from_form(params: &QueryMap) -> Result<Search, UrlDecodingError> {
+            let (q, page) = extract!(params, required q: String, optional page: u32)?;
+            Ok(Search { q, page })
+        }
+    }
+
+    let parse = |data: &str| parse_with(data, &ParseOptions::new());
+    assert_eq!(convert::<Search>(parse("q=rust&page=2")).unwrap(), Search { q: "rust".to_owned(), page: Some(2) });
+    assert!(matches!(convert::<Search>(parse("")), Err(UrlDecodingError::ValidationFailed(_))));
+    assert!(matches!(convert::<Search>(Err(UrlDecodingError::TooManyPairs(100))),
+                     Err(UrlDecodingError::TooManyPairs(100))));
+    assert_eq!(convert::<QueryMap>(parse("a=1")).unwrap().len(), 1);
+
+    fn is_handler<T: Handler>(_: &T) {}
+    is_handler(&with_form(|_: &mut Request, search: Search| Ok(Response::with((status::Ok, search.q)))));
+}
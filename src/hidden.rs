@@ -0,0 +1,157 @@
+//! Server state carried through the client in a signed hidden field, such as
+//! the choices of the previous steps of a multi-step form.
+
+use std::sync::Arc;
+
+use rustc_serialize::base64::{self, ToBase64, FromBase64};
+
+use super::{QueryMap, UrlDecodingError, ParseOptions, parse_with};
+use super::encode::encode;
+use super::repopulate::escape_html;
+use super::signing::{Signer, constant_time_eq};
+
+/// State that can be written to parameters and read back from them, for
+/// `SignedFields`.
+pub trait HiddenState: Sized {
+    /// The state as parameters.
+    fn to_params(&self) -> QueryMap;
+
+    /// The state the parameters of `to_params` describe.
+    fn from_params(params: &QueryMap) -> Result<Self, UrlDecodingError>;
+}
+
+impl HiddenState for QueryMap {
+    fn to_params(&self) -> QueryMap {
+        self.clone()
+    }
+
+    fn from_params(params: &QueryMap) -> Result<QueryMap, UrlDecodingError> {
+        Ok(params.clone())
+    }
+}
+
+/// Writes state into a hidden field, `_state` by default, signed so that
+/// the client can't change it, and verifies it when the form comes back.
+///
+/// The field holds the state encoded as a query string, in base64, and its
+/// signature. The signature also covers the name of the field, so the value
+/// of one field can't be replayed into another. The state is readable by the
+/// client: keep secrets out of it.
+///
+/// ```ignore
+/// let fields = SignedFields::new(hmac_sha256, SECRET).param("_checkout");
+/// let input = fields.hidden_input(&cart)?;
+/// // When the form is submitted:
+/// let cart: Cart = fields.decode(&params)?;
+/// ```
+#[derive(Clone)]
+pub struct SignedFields {
+    param: String,
+    max_len: usize,
+    signer: Arc<dyn Signer + Send + Sync>,
+    key: Vec<u8>
+}
+
+impl SignedFields {
+    /// Fields signed with `signer` and `key`, stored in `_state` and of at
+    /// most 4096 encoded bytes.
+    pub fn new<S>(signer: S, key: &[u8]) -> SignedFields
+    where S: Signer + Send + Sync + 'static {
+        SignedFields { param: "_state".to_owned(), max_len: 4096, signer: Arc::new(signer), key: key.to_vec() }
+    }
+
+    /// Store the state in `param` instead of `_state`.
+    pub fn param(mut self, param: &str) -> SignedFields {
+        self.param = param.to_owned();
+        self
+    }
+
+    /// Refuse to encode or decode fields longer than `max_len` bytes.
+    pub fn max_len(mut self, max_len: usize) -> SignedFields {
+        self.max_len = max_len;
+        self
+    }
+
+    fn signature(&self, payload: &str) -> Vec<u8> {
+        self.signer.sign(&self.key, format!("{}\n{}", self.param, payload).as_bytes())
+    }
+
+    fn too_long(&self) -> UrlDecodingError {
+        UrlDecodingError::ValueTooLong { key: self.param.clone(), limit: self.max_len }
+    }
+
+    /// The value of the hidden field holding `state`.
+    pub fn encode<T: HiddenState>(&self, state: &T) -> Result<String, UrlDecodingError> {
+        let payload = encode(&state.to_params()).as_bytes().to_base64(base64::URL_SAFE);
+        let value = format!("{}.{}", payload, self.signature(&payload).to_base64(base64::URL_SAFE));
+        if value.len() > self.max_len {
+            return Err(self.too_long());
+        }
+        Ok(value)
+    }
+
+    /// An `<input type="hidden">` holding `state`, to put in the form.
+    pub fn hidden_input<T: HiddenState>(&self, state: &T) -> Result<String, UrlDecodingError> {
+        let value = self.encode(state)?;
+        Ok(format!("<input type=\"hidden\" name=\"{}\" value=\"{}\">", escape_html(&self.param), value))
+    }
+
+    /// The state in the hidden field of a submitted form. A missing field is
+    /// a `MissingParameter` error, a changed one an `InvalidParameter` error.
+    pub fn decode<T: HiddenState>(&self, params: &QueryMap) -> Result<T, UrlDecodingError> {
+        let tampered = || UrlDecodingError::InvalidParameter {
+            key: self.param.clone(),
+            message: "has a missing or invalid signature".to_owned()
+        };
+
+        let value = match params.get(&self.param).map(|values| &values[..]) {
+            None | Some([]) => return Err(UrlDecodingError::MissingParameter(self.param.clone())),
+            Some([value]) => value,
+            Some(_) => return Err(UrlDecodingError::ParameterPollution(self.param.clone()))
+        };
+        if value.len() > self.max_len {
+            return Err(self.too_long());
+        }
+
+        let (payload, signature) = match value.find('.') {
+            Some(position) => (&value[..position], &value[position + 1..]),
+            None => return Err(tampered())
+        };
+        match signature.from_base64() {
+            Ok(ref signature) if constant_time_eq(signature, &self.signature(payload)) => (),
+            _ => return Err(tampered())
+        }
+
+        let query = payload.from_base64().ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .ok_or_else(tampered)?;
+        match parse_with(&query, &ParseOptions::new()) {
+            Ok(state) => T::from_params(&state),
+            Err(UrlDecodingError::EmptyQuery) => T::from_params(&QueryMap::new()),
+            Err(err) => Err(err)
+        }
+    }
+}
+
+#[test]
+fn test_signed_fields() {
+    let signer = super::signing::test_signer;
+    let fields = SignedFields::new(signer, b"secret");
+    let parse = |data: &str| parse_with(data, &ParseOptions::new()).unwrap();
+
+    let state = parse("plan=pro&seats=3");
+    let value = fields.encode(&state).unwrap();
+    let submitted = parse(&format!("_state={}&step=2", value));
+    assert_eq!(fields.decode::<QueryMap>(&submitted).unwrap(), state);
+
+    let forged = fields.encode(&parse("plan=enterprise&seats=3")).unwrap();
+    let swapped = format!("_state={}.{}", forged.split('.').next().unwrap(), value.split('.').nth(1).unwrap());
+    assert!(matches!(fields.decode::<QueryMap>(&parse(&swapped)), Err(UrlDecodingError::InvalidParameter { .. })));
+    assert!(fields.clone().param("_other").decode::<QueryMap>(&parse(&format!("_other={}", value))).is_err());
+    assert!(SignedFields::new(signer, b"other").decode::<QueryMap>(&submitted).is_err());
+    assert!(matches!(fields.decode::<QueryMap>(&parse("step=2")), Err(UrlDecodingError::MissingParameter(_))));
+    assert!(fields.clone().max_len(10).encode(&state).is_err());
+
+    let input = fields.hidden_input(&state).unwrap();
+    assert!(input.starts_with("<input type=\"hidden\" name=\"_state\" value=\"") && input.contains(&value));
+}
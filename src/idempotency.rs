@@ -0,0 +1,157 @@
+//! Idempotency keys sent as a parameter, which payment-style APIs use to
+//! recognize a retried request and answer it with the original response.
+
+#[cfg(feature = "iron")]
+use iron::prelude::*;
+#[cfg(feature = "iron")]
+use iron::BeforeMiddleware;
+#[cfg(feature = "iron")]
+use iron::typemap::Key;
+
+use super::{QueryMap, UrlDecodingError};
+#[cfg(feature = "iron")]
+use super::form_params;
+
+/// The idempotency key of a request, stored in `req.extensions` by the
+/// `IdempotencyKey` middleware when the request has one:
+/// `req.extensions.get::<RequestIdempotencyKey>()`
+#[cfg(feature = "iron")]
+pub struct RequestIdempotencyKey;
+
+#[cfg(feature = "iron")]
+impl Key for RequestIdempotencyKey {
+    type Value = String;
+}
+
+/// Reads and validates the idempotency key of a request, by default the
+/// `idempotency_key` parameter of 8 to 255 ASCII letters, digits, `-` and
+/// `_`, which fits UUIDs and most random tokens.
+///
+/// Keys sent more than once are refused, so a request can't carry two keys
+/// that the application and a proxy would disagree on.
+///
+/// ```ignore
+/// chain.link_before(IdempotencyKey::new().required(true));
+/// // In the handler:
+/// let key = req.extensions.get::<RequestIdempotencyKey>();
+/// ```
+#[derive(Clone, Debug)]
+pub struct IdempotencyKey {
+    param: String,
+    min_length: usize,
+    max_length: usize,
+    allowed: String,
+    required: bool
+}
+
+impl Default for IdempotencyKey {
+    fn default() -> IdempotencyKey {
+        IdempotencyKey {
+            param: "idempotency_key".to_owned(),
+            min_length: 8,
+            max_length: 255,
+            allowed: "-_".to_owned(),
+            required: false
+        }
+    }
+}
+
+impl IdempotencyKey {
+    /// An optional key in `idempotency_key`.
+    pub fn new() -> IdempotencyKey {
+        IdempotencyKey::default()
+    }
+
+    /// Read the key from `param` instead of `idempotency_key`.
+    pub fn param(mut self, param: &str) -> IdempotencyKey {
+        self.param = param.to_owned();
+        self
+    }
+
+    /// Accept keys of `min` to `max` characters instead of 8 to 255.
+    pub fn length(mut self, min: usize, max: usize) -> IdempotencyKey {
+        self.min_length = min;
+        self.max_length = max;
+        self
+    }
+
+    /// Accept the ASCII punctuation in `chars`, such as `.:`, besides letters,
+    /// digits, `-` and `_`.
+    pub fn allow(mut self, chars: &str) -> IdempotencyKey {
+        self.allowed.push_str(chars);
+        self
+    }
+
+    /// Refuse requests without a key.
+    pub fn required(mut self, enabled: bool) -> IdempotencyKey {
+        self.required = enabled;
+        self
+    }
+
+    /// The key in `params`, if it has one.
+    pub fn extract(&self, params: &QueryMap) -> Result<Option<String>, UrlDecodingError> {
+        let invalid = |message: String| UrlDecodingError::InvalidParameter { key: self.param.clone(), message };
+        let key = match params.get(&self.param).map(|values| &values[..]) {
+            None | Some([]) if self.required => return Err(UrlDecodingError::MissingParameter(self.param.clone())),
+            None | Some([]) => return Ok(None),
+            Some([key]) => key,
+            Some(_) => return Err(UrlDecodingError::ParameterPollution(self.param.clone()))
+        };
+
+        if key.len() < self.min_length || key.len() > self.max_length {
+            return Err(invalid(format!("must be {} to {} characters long", self.min_length, self.max_length)));
+        }
+        if !key.chars().all(|c| c.is_ascii_alphanumeric() || (c.is_ascii() && self.allowed.contains(c))) {
+            return Err(invalid(format!("may only contain letters, digits and {}", self.allowed)));
+        }
+        Ok(Some(key.clone()))
+    }
+
+    /// The key of the submitted form of `req`: the body of
+    /// `application/x-www-form-urlencoded` requests, the query string
+    /// otherwise.
+    #[cfg(feature = "iron")]
+    pub fn from_request(&self, req: &mut Request) -> Result<Option<String>, UrlDecodingError> {
+        match form_params(req) {
+            Ok(params) => self.extract(&params),
+            Err(UrlDecodingError::EmptyQuery) => self.extract(&QueryMap::new()),
+            Err(err) => Err(err)
+        }
+    }
+}
+
+/// Stores the key of every request as `RequestIdempotencyKey`, answering
+/// invalid or missing ones with the response of the `UrlEncodedConfig`.
+#[cfg(feature = "iron")]
+impl BeforeMiddleware for IdempotencyKey {
+    fn before(&self, req: &mut Request) -> IronResult<()> {
+        match self.from_request(req) {
+            Ok(Some(key)) => { req.extensions.insert::<RequestIdempotencyKey>(key); },
+            Ok(None) => (),
+            Err(err) => return Err(err.into_iron_error(req))
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_idempotency_key() {
+    use super::{parse_with, ParseOptions};
+
+    let parse = |data: &str| parse_with(data, &ParseOptions::new()).unwrap();
+    let keys = IdempotencyKey::new();
+    let uuid = "5f0c6b8e-2d7a-4c1e-9b3f-8a6d2e4f1c0b";
+
+    assert_eq!(keys.extract(&parse(&format!("idempotency_key={}&amount=5", uuid))).unwrap(), Some(uuid.to_owned()));
+    assert_eq!(keys.extract(&parse("amount=5")).unwrap(), None);
+    assert!(matches!(keys.clone().required(true).extract(&parse("amount=5")),
+                     Err(UrlDecodingError::MissingParameter(_))));
+    assert!(matches!(keys.extract(&parse("idempotency_key=short")), Err(UrlDecodingError::InvalidParameter { .. })));
+    assert!(matches!(keys.extract(&parse("idempotency_key=abcdefgh.1")), Err(UrlDecodingError::InvalidParameter { .. })));
+    assert!(matches!(keys.extract(&parse("idempotency_key=abcdefgh&idempotency_key=abcdefgi")),
+                     Err(UrlDecodingError::ParameterPollution(_))));
+
+    let custom = IdempotencyKey::new().param("request_id").length(4, 8).allow(".");
+    assert_eq!(custom.extract(&parse("request_id=ab.1")).unwrap(), Some("ab.1".to_owned()));
+    assert!(custom.extract(&parse("request_id=abcdefghi")).is_err());
+}
@@ -1,67 +1,464 @@
 //! URL Encoded Plugin for Iron.
 //!
 //! Parses "url encoded" data from client requests.
-//! Capable of parsing both URL query strings and POST request bodies.
+//! Capable of parsing both URL query strings and POST request bodies,
+//! as well as matrix parameters in URL path segments.
+//!
+//! The Iron plugins and middleware are behind the default `iron` feature.
+//! Without it, the parsing, encoding and validation core builds on its own.
 
+#[cfg(feature = "iron")]
 extern crate iron;
+#[cfg(feature = "iron")]
 extern crate bodyparser;
 extern crate url;
+#[cfg(feature = "iron")]
 extern crate plugin;
+#[cfg(feature = "persistent")]
+extern crate persistent;
+extern crate rustc_serialize;
+extern crate serde;
+#[cfg(feature = "iron")]
+extern crate unicode_normalization;
+#[cfg(feature = "multimap")]
+extern crate multimap;
 
+#[cfg(feature = "iron")]
 use iron::prelude::*;
+#[cfg(feature = "iron")]
 use iron::typemap::Key;
 
+#[cfg(feature = "iron")]
+use iron::method::Method;
+#[cfg(feature = "iron")]
+use iron::headers::ContentType;
+#[cfg(feature = "iron")]
+use iron::mime::{Mime, TopLevel, SubLevel};
+
+#[cfg(feature = "iron")]
 use plugin::Pluggable;
 
-use url::form_urlencoded;
 use std::collections::HashMap;
 use std::collections::hash_map::Entry::*;
 use std::fmt;
 use std::error::Error as StdError;
 
+#[cfg(feature = "iron")]
+pub use config::{UrlEncodedConfig, BodyMethods};
+#[cfg(feature = "iron")]
+pub use matrix::MatrixParams;
+pub use matrix::{MatrixSegment, parse_matrix_segment};
+pub use parse::{ParseOptions, parse_with, parse_fragment, parse_header_params,
+                parse_cookie_pairs, parse_piecewise, FormParser, decode_pairs, group_pairs,
+                Grouping, decode_spanned_pairs, SpannedPair};
+#[cfg(feature = "iron")]
+pub use referer::RefererQuery;
+pub use signing::{Signer, constant_time_eq};
+pub use canonical::{rfc3986_encode, canonical_query, canonical_query_with, CanonicalRules};
+#[cfg(feature = "iron")]
+pub use canonical::canonical_url;
+#[cfg(feature = "iron")]
+pub use oauth::OAuthRequest;
+pub use accessors::{QueryMapExt, optional, numeric_suffix};
+pub use oauth2::OAuthCallback;
+pub use values::{Base64Alphabet, JwtParts, ParamEnum, NumberFormat, EmailOptions, UrlOptions, Color,
+                 DurationOptions, fold_digits};
+pub use network::IpCidr;
+pub use repopulate::{FormValues, Escaped, escape_html, escape_html_text};
+pub use encode::{encode, encode_nested, encode_component, decode_component, encode_pair, encode_with,
+                 encode_pairs_with, EncodeOptions, EncodeSet, Spaces, KeyOrder};
+pub use flash::Flash;
+pub use transform::{Transform, Transforms};
+pub use schema::{FormSchema, Field, FieldType, FieldError, FieldErrorKind, Rule, ParamValidator,
+                 Value, FormData, Schema};
+#[cfg(feature = "iron")]
+pub use schema::SchemaForm;
+pub use messages::{MessageCatalog, English};
+#[cfg(feature = "iron")]
+pub use problem::{Problem, ProblemField};
+#[cfg(feature = "iron")]
+pub use eager::ParseEagerly;
+#[cfg(feature = "iron")]
+pub use rewrite::RewriteQuery;
+#[cfg(feature = "iron")]
+pub use audit::{AuditLog, AuditSink, AuditEntry, AuditParam};
+#[cfg(feature = "iron")]
+pub use route::{ParamRouter, route_on_param};
+pub use flags::{FeatureFlags, FlagSet};
+pub use ratelimit::RateLimitKey;
+pub use webhook::{WebhookVerifier, WebhookPayload, SignatureEncoding};
+#[cfg(feature = "iron")]
+pub use reader::{parse_reader, parse_stream};
+#[cfg(feature = "futures")]
+pub use future::{ParseFuture, ParseJob, parse_body_future, parse_body_future_with};
+#[cfg(feature = "iron")]
+pub use timeout::TimedReader;
+#[cfg(feature = "iron")]
+pub use old_input::{OldInput, OldInputStore};
+#[cfg(feature = "iron")]
+pub use multipart::{MultipartBody, MultipartForm, MultipartOptions, FilePart, FileData, SpooledFile,
+                    parse_multipart, parse_multipart_with, sanitize_filename};
+#[cfg(feature = "iron")]
+pub use mounted::{MountedConfig, MountPrefix};
+#[cfg(feature = "persistent")]
+pub use shared::{Secrets, Schemas, shared_config, shared_secret, shared_form};
+pub use pairs::{into_pairs, from_pairs};
+#[cfg(feature = "multimap")]
+pub use pairs::{into_multimap, from_multimap};
+pub use preset::{Preset, PresetOptions};
+pub use snapshot::Snapshot;
+pub use append::{append_query, Duplicates};
+pub use tracking::{TRACKING_PARAMS, strip_params, strip_query};
+pub use cachekey::CacheKey;
+pub use stats::ParamStats;
+pub use analyze::{PayloadAnalyzer, Finding};
+#[cfg(feature = "iron")]
+pub use analyze::Findings;
+pub use fingerprint::{form_fingerprint, SubmissionStore, DuplicateSubmissions};
+#[cfg(feature = "iron")]
+pub use conditional::ConditionalGet;
+pub use idempotency::IdempotencyKey;
+#[cfg(feature = "iron")]
+pub use idempotency::RequestIdempotencyKey;
+#[cfg(feature = "iron")]
+pub use tracking::StripTracking;
+#[cfg(feature = "iron")]
+pub use append::{append_params, append_params_with};
+#[cfg(feature = "iron")]
+pub use snapshot::{capture, apply_to_test_request};
+pub use nested::{NestedValue, NestedUsage, NestedQueryMap, NestedOptions, NestedConflicts,
+                 ReservedKeys, parse_nested};
+pub use de::{NestedDeserializer, DeserializeError, from_nested, from_rows, parse_rows};
+pub use coerce::{coerce, coerce_value};
+pub use dates::{Date, DateRange};
+pub use extract::Extractor;
+pub use param::{Param, Presence, Optional, Required};
+pub use named::{NamedForm, NamedForms};
+pub use versions::{VersionedForm, FormVersions};
+pub use patch::Patch;
+pub use delta::SetDelta;
+pub use hidden::{HiddenState, SignedFields};
+pub use wizard::{Wizard, WizardState};
+#[cfg(feature = "iron")]
+pub use handler::{FromForm, FormHandler, with_form};
+#[cfg(feature = "decimal")]
+pub use decimal::Decimal;
+#[cfg(feature = "locale")]
+pub use locale::{Country, Language};
+#[cfg(feature = "timezone")]
+pub use timezone::TimeZone;
+pub use budget::{ParseBudget, ParseCost, BudgetResource};
+pub use analytics::{AnalyticsExporter, AnalyticsParam};
+
+#[cfg(feature = "iron")]
+mod config;
+mod matrix;
+mod parse;
+#[cfg(feature = "iron")]
+mod referer;
+mod signing;
+mod canonical;
+#[cfg(feature = "iron")]
+mod oauth;
+mod accessors;
+mod oauth2;
+mod values;
+mod network;
+mod repopulate;
+mod encode;
+mod flash;
+mod transform;
+mod schema;
+mod messages;
+#[cfg(feature = "iron")]
+mod problem;
+#[cfg(feature = "iron")]
+mod eager;
+#[cfg(feature = "iron")]
+mod rewrite;
+#[cfg(feature = "iron")]
+mod audit;
+#[cfg(feature = "iron")]
+mod route;
+mod flags;
+mod ratelimit;
+mod webhook;
+#[cfg(feature = "iron")]
+mod reader;
+#[cfg(feature = "futures")]
+mod future;
+#[cfg(feature = "iron")]
+mod mounted;
+#[cfg(feature = "iron")]
+mod old_input;
+#[cfg(feature = "iron")]
+mod multipart;
+#[cfg(feature = "iron")]
+mod timeout;
+#[cfg(feature = "iron")]
+mod spool;
+#[cfg(feature = "persistent")]
+mod shared;
+mod pairs;
+mod preset;
+mod snapshot;
+mod append;
+mod tracking;
+mod cachekey;
+mod fingerprint;
+mod stats;
+mod analyze;
+#[cfg(feature = "iron")]
+mod conditional;
+mod idempotency;
+mod nested;
+mod de;
+mod coerce;
+mod dates;
+#[macro_use]
+mod extract;
+#[cfg(feature = "iron")]
+mod handler;
+mod param;
+mod named;
+mod versions;
+mod patch;
+mod delta;
+mod hidden;
+mod wizard;
+mod budget;
+mod analytics;
+#[cfg(feature = "decimal")]
+mod decimal;
+#[cfg(feature = "phone")]
+mod phone;
+#[cfg(feature = "locale")]
+mod locale;
+#[cfg(feature = "timezone")]
+mod timezone;
+
 /// Plugin for `Request` that extracts URL encoded data from the URL query string.
 ///
 /// Use it like this: `req.get_ref::<UrlEncodedQuery>()`
+#[cfg(feature = "iron")]
 pub struct UrlEncodedQuery;
 
 /// Plugin for `Request` that extracts URL encoded data from the request body.
 ///
 /// Use it like this: `req.get_ref::<UrlEncodedBody>()`
+///
+/// For `multipart/form-data` requests, it yields the fields parsed by
+/// `MultipartBody`.
+#[cfg(feature = "iron")]
 pub struct UrlEncodedBody;
 
-/// An error representing the two possible errors that can occur during URL decoding.
+/// The query string exactly as the client sent it, before any decoding.
+///
+/// Stored in `req.extensions` by `UrlEncodedQuery` once it has parsed the query:
+/// `req.extensions.get::<RawQuery>()`
+#[cfg(feature = "iron")]
+pub struct RawQuery;
+
+/// The request body exactly as the client sent it, before any decoding.
+///
+/// Stored in `req.extensions` by `UrlEncodedBody` once it has read the body, so
+/// signature checks and logging don't have to read the body a second time:
+/// `req.extensions.get::<RawFormBody>()`
+#[cfg(feature = "iron")]
+pub struct RawFormBody;
+
+/// An error representing the possible errors that can occur during URL decoding.
 ///
 /// The first and probably most common one is for the query to be empty,
 /// and that goes for both body and url queries.
 ///
 /// The second type of error that can occur is that something goes wrong
 /// when parsing the request body.
+///
+/// A `UrlEncodedConfig` can refuse to parse bodies sent with certain request
+/// methods, and its `ParseOptions` can put limits on the data and reject
+/// repeated single-only parameters.
+///
+/// Finally, the typed accessors report missing and invalid parameters, and
+/// `SchemaForm` reports every field that failed validation at once.
 #[derive(Debug)]
 pub enum UrlDecodingError{
     /// An error parsing the request body
+    #[cfg(feature = "iron")]
     BodyError(bodyparser::BodyError),
     /// An empty query string, either in body or url query
-    EmptyQuery
+    EmptyQuery,
+    /// The request body took longer to arrive than the configured timeout
+    BodyReadTimeout,
+    /// A body was sent with a method the configuration doesn't parse bodies for
+    #[cfg(feature = "iron")]
+    MethodNotAllowedForBody(Method),
+    /// The data holds more pairs than the configured maximum
+    TooManyPairs(usize),
+    /// A key is longer than the configured maximum
+    KeyTooLong(usize),
+    /// The value of `key` is longer than the configured maximum
+    ValueTooLong {
+        /// The key the value belongs to
+        key: String,
+        /// The configured maximum length
+        limit: usize
+    },
+    /// A nested key has more levels than the configured maximum
+    NestingTooDeep(usize),
+    /// The nested tree holds more nodes than the configured maximum
+    TooManyNodes(usize),
+    /// A list index of nested `key` is greater than the configured maximum
+    IndexTooLarge {
+        /// The base name of the key
+        key: String,
+        /// The configured maximum index
+        limit: usize
+    },
+    /// Nested `key` is used in two incompatible ways, such as a list and a map
+    NestedConflict {
+        /// The key, with the brackets leading to it
+        key: String,
+        /// How it was used first
+        first: NestedUsage,
+        /// How it was used next
+        second: NestedUsage
+    },
+    /// A nested key uses a reserved name such as `__proto__`
+    ReservedKey(String),
+    /// A parameter marked single-only appears more than once
+    ParameterPollution(String),
+    /// A required parameter is absent
+    MissingParameter(String),
+    /// A parameter is present but its value isn't acceptable
+    InvalidParameter {
+        /// The offending parameter
+        key: String,
+        /// What is wrong with it, phrased to follow the parameter name
+        message: String
+    },
+    /// The form failed validation against a `FormSchema`
+    ValidationFailed(Vec<schema::FieldError>),
+    /// A `multipart/form-data` body doesn't follow the format, for the reason
+    /// given
+    MalformedMultipart(String),
+    /// The file uploaded as `key` is bigger than the configured maximum
+    UploadTooLarge {
+        /// The field the file was sent as
+        key: String,
+        /// The configured maximum size, in bytes
+        limit: u64
+    },
+    /// The uploaded files are bigger, together, than the configured maximum
+    UploadsTooLarge(u64),
+    /// More files were uploaded than the configured maximum
+    TooManyFiles(usize),
+    /// The file uploaded as `key` isn't of an accepted type
+    UnsupportedFileType {
+        /// The field the file was sent as
+        key: String,
+        /// The media type the client sent for it
+        content_type: String
+    },
+    /// Parsing took more of a resource than the configured `ParseBudget`
+    /// allows
+    ParseBudgetExceeded(BudgetResource)
 }
 
 pub use UrlDecodingError::*;
 
+impl UrlDecodingError {
+    /// A code identifying the kind of error, such as `EMPTY_QUERY`.
+    ///
+    /// Codes never change between versions, unlike messages, so clients and
+    /// tests can rely on them.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            #[cfg(feature = "iron")]
+            BodyError(_) => "BODY_ERROR",
+            EmptyQuery => "EMPTY_QUERY",
+            BodyReadTimeout => "BODY_READ_TIMEOUT",
+            #[cfg(feature = "iron")]
+            MethodNotAllowedForBody(_) => "METHOD_NOT_ALLOWED_FOR_BODY",
+            TooManyPairs(_) => "TOO_MANY_PAIRS",
+            KeyTooLong(_) => "KEY_TOO_LONG",
+            ValueTooLong { .. } => "VALUE_TOO_LONG",
+            NestingTooDeep(_) => "NESTING_TOO_DEEP",
+            TooManyNodes(_) => "TOO_MANY_NODES",
+            IndexTooLarge { .. } => "INDEX_TOO_LARGE",
+            NestedConflict { .. } => "NESTED_CONFLICT",
+            ReservedKey(_) => "RESERVED_KEY",
+            ParameterPollution(_) => "PARAMETER_POLLUTION",
+            MissingParameter(_) => "MISSING_PARAMETER",
+            InvalidParameter { .. } => "INVALID_PARAMETER",
+            ValidationFailed(_) => "VALIDATION_FAILED",
+            MalformedMultipart(_) => "MALFORMED_MULTIPART",
+            UploadTooLarge { .. } => "UPLOAD_TOO_LARGE",
+            UploadsTooLarge(_) => "UPLOADS_TOO_LARGE",
+            TooManyFiles(_) => "TOO_MANY_FILES",
+            UnsupportedFileType { .. } => "UNSUPPORTED_FILE_TYPE",
+            ParseBudgetExceeded(_) => "PARSE_BUDGET_EXCEEDED"
+        }
+    }
+
+    /// Turn the error into an `IronError`, answered with the response of the
+    /// `UrlEncodedConfig` of `req`:
+    /// `req.get::<UrlEncodedBody>().map_err(|err| err.into_iron_error(req))?`
+    #[cfg(feature = "iron")]
+    pub fn into_iron_error(self, req: &Request) -> IronError {
+        request_config(req).iron_error(self)
+    }
+}
+
+/// Answers with the default response, an English `Problem`. Use
+/// `into_iron_error` to go through the configured responder.
+#[cfg(feature = "iron")]
+impl From<UrlDecodingError> for IronError {
+    fn from(error: UrlDecodingError) -> IronError {
+        UrlEncodedConfig::default().iron_error(error)
+    }
+}
+
 impl fmt::Display for UrlDecodingError {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        self.description().fmt(f)
+        f.write_str(&English.error(self))
     }
 }
 
 impl StdError for UrlDecodingError {
+    #[allow(deprecated)]
     fn description(&self) -> &str {
         match *self {
+            #[cfg(feature = "iron")]
             BodyError(ref err) => err.description(),
-            EmptyQuery => "Expected query, found empty string."
+            EmptyQuery => "Expected query, found empty string.",
+            BodyReadTimeout => "Request body read timed out.",
+            #[cfg(feature = "iron")]
+            MethodNotAllowedForBody(_) => "Request bodies are not accepted for this method.",
+            TooManyPairs(_) => "Too many parameters.",
+            KeyTooLong(_) => "Parameter name too long.",
+            ValueTooLong { .. } => "Parameter value too long.",
+            NestingTooDeep(_) => "Parameter nested too deeply.",
+            TooManyNodes(_) => "Too many nested parameters.",
+            IndexTooLarge { .. } => "List index too large.",
+            NestedConflict { .. } => "Conflicting nested parameters.",
+            ReservedKey(_) => "Reserved parameter name.",
+            ParameterPollution(_) => "Parameter sent more than once.",
+            MissingParameter(_) => "Missing parameter.",
+            InvalidParameter { .. } => "Invalid parameter.",
+            ValidationFailed(_) => "Form validation failed.",
+            MalformedMultipart(_) => "Malformed multipart body.",
+            UploadTooLarge { .. } => "Uploaded file too large.",
+            UploadsTooLarge(_) => "Uploaded files too large.",
+            TooManyFiles(_) => "Too many uploaded files.",
+            UnsupportedFileType { .. } => "Unsupported file type.",
+            ParseBudgetExceeded(_) => "Parse budget exceeded."
         }
     }
 
-    fn cause(&self) -> Option<&StdError> {
+    fn cause(&self) -> Option<&dyn StdError> {
         match *self {
+            #[cfg(feature = "iron")]
             BodyError(ref err) => Some(err),
             _ => None
         }
@@ -73,45 +470,106 @@ pub type QueryMap = HashMap<String, Vec<String>>;
 /// Result type for decoding query parameters.
 pub type QueryResult = Result<QueryMap, UrlDecodingError>;
 
+#[cfg(feature = "iron")]
 impl Key for UrlEncodedBody {
     type Value = QueryMap;
 }
+#[cfg(feature = "iron")]
 impl Key for UrlEncodedQuery {
     type Value = QueryMap;
 }
+#[cfg(feature = "iron")]
+impl Key for RawQuery {
+    type Value = String;
+}
+#[cfg(feature = "iron")]
+impl Key for RawFormBody {
+    type Value = String;
+}
 
+#[cfg(feature = "iron")]
 impl<'a, 'b> plugin::Plugin<Request<'a, 'b>> for UrlEncodedQuery {
     type Error = UrlDecodingError;
 
     fn eval(req: &mut Request) -> QueryResult {
-        match req.url.query {
-            Some(ref query) => create_param_hashmap(&query),
+        match req.url.query.clone() {
+            Some(query) => {
+                let result = request_config(req).parse(&query);
+                req.extensions.insert::<RawQuery>(query);
+                result
+            },
             None => Err(UrlDecodingError::EmptyQuery)
         }
     }
 }
 
+#[cfg(feature = "iron")]
 impl<'a, 'b> plugin::Plugin<Request<'a, 'b>> for UrlEncodedBody {
     type Error = UrlDecodingError;
 
     fn eval(req: &mut Request) -> QueryResult {
-        req.get::<bodyparser::Raw>()
-            .map(|x| x.unwrap_or("".to_string()))
-            .map_err(|e| UrlDecodingError::BodyError(e))
-            .and_then(|x| create_param_hashmap(&x))
+        if multipart::boundary(req).is_some() {
+            return req.get_ref::<MultipartBody>().map(|form| form.fields.clone());
+        }
+
+        let config = request_config(req);
+        if !config.allowed_body_methods().allows(&req.method) {
+            return Err(UrlDecodingError::MethodNotAllowedForBody(req.method.clone()));
+        }
+        if let Some(threshold) = config.body_spool_threshold() {
+            return spool::parse_spooled(req, threshold, &config);
+        }
+
+        let body = match config.body_read_timeout() {
+            Some(timeout) => timeout::read_body_within(req, timeout)?,
+            None => req.get::<bodyparser::Raw>()
+                .map(|x| x.unwrap_or("".to_string()))
+                .map_err(UrlDecodingError::BodyError)?
+        };
+
+        let result = config.parse(&body);
+        req.extensions.insert::<RawFormBody>(body);
+        result
     }
 }
 
-/// Parse a urlencoded string into an optional HashMap.
-fn create_param_hashmap(data: &str) -> QueryResult {
-    match data {
-        "" => Err(UrlDecodingError::EmptyQuery),
-        _ => Ok(combine_duplicates(form_urlencoded::parse(data.as_bytes())))
+/// The configuration linked into the chain, or the default one.
+#[cfg(all(feature = "iron", not(feature = "persistent")))]
+fn request_config(req: &Request) -> UrlEncodedConfig {
+    req.extensions.get::<UrlEncodedConfig>().cloned().unwrap_or_default()
+}
+
+/// The configuration linked into the chain, else the one shared with
+/// `persistent`, or the default one.
+#[cfg(feature = "persistent")]
+fn request_config(req: &Request) -> UrlEncodedConfig {
+    req.extensions.get::<UrlEncodedConfig>().cloned()
+        .or_else(|| shared::shared_config(req))
+        .unwrap_or_default()
+}
+
+/// The submitted form: the body of `application/x-www-form-urlencoded` and
+/// `multipart/form-data` requests, the query string otherwise.
+#[cfg(feature = "iron")]
+fn form_params(req: &mut Request) -> QueryResult {
+    if is_form(req) || multipart::boundary(req).is_some() {
+        req.get::<UrlEncodedBody>()
+    } else {
+        req.get::<UrlEncodedQuery>()
     }
 }
 
-/// Convert a list of (key, value) pairs into a hashmap with vector values.
-fn combine_duplicates(q: Vec<(String, String)>) -> QueryMap {
+/// Whether the request body is `application/x-www-form-urlencoded`.
+#[cfg(feature = "iron")]
+fn is_form(req: &Request) -> bool {
+    matches!(req.headers.get::<ContentType>(),
+        Some(&ContentType(Mime(TopLevel::Application, SubLevel::WwwFormUrlEncoded, _))))
+}
+
+/// Convert a list of (key, value) pairs into a hashmap with vector values,
+/// keeping every value of repeated keys in order. Same as `group_pairs` with
+/// `Grouping::All`.
+pub fn combine_duplicates(q: Vec<(String, String)>) -> QueryMap {
     let mut deduplicated: QueryMap = HashMap::new();
 
     for (k, v) in q.into_iter() {
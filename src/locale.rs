@@ -0,0 +1,120 @@
+//! Country and language codes, checked against the ISO 3166-1 and ISO 639-1
+//! code sets.
+
+use std::fmt;
+
+use super::UrlDecodingError;
+
+/// The officially assigned ISO 3166-1 alpha-2 country codes.
+const COUNTRIES: &[&str] = &[
+    "AD", "AE", "AF", "AG", "AI", "AL", "AM", "AO", "AQ", "AR", "AS", "AT", "AU", "AW", "AX", "AZ",
+    "BA", "BB", "BD", "BE", "BF", "BG", "BH", "BI", "BJ", "BL", "BM", "BN", "BO", "BQ", "BR", "BS",
+    "BT", "BV", "BW", "BY", "BZ", "CA", "CC", "CD", "CF", "CG", "CH", "CI", "CK", "CL", "CM", "CN",
+    "CO", "CR", "CU", "CV", "CW", "CX", "CY", "CZ", "DE", "DJ", "DK", "DM", "DO", "DZ", "EC", "EE",
+    "EG", "EH", "ER", "ES", "ET", "FI", "FJ", "FK", "FM", "FO", "FR", "GA", "GB", "GD", "GE", "GF",
+    "GG", "GH", "GI", "GL", "GM", "GN", "GP", "GQ", "GR", "GS", "GT", "GU", "GW", "GY", "HK", "HM",
+    "HN", "HR", "HT", "HU", "ID", "IE", "IL", "IM", "IN", "IO", "IQ", "IR", "IS", "IT", "JE", "JM",
+    "JO", "JP", "KE", "KG", "KH", "KI", "KM", "KN", "KP", "KR", "KW", "KY", "KZ", "LA", "LB", "LC",
+    "LI", "LK", "LR", "LS", "LT", "LU", "LV", "LY", "MA", "MC", "MD", "ME", "MF", "MG", "MH", "MK",
+    "ML", "MM", "MN", "MO", "MP", "MQ", "MR", "MS", "MT", "MU", "MV", "MW", "MX", "MY", "MZ", "NA",
+    "NC", "NE", "NF", "NG", "NI", "NL", "NO", "NP", "NR", "NU", "NZ", "OM", "PA", "PE", "PF", "PG",
+    "PH", "PK", "PL", "PM", "PN", "PR", "PS", "PT", "PW", "PY", "QA", "RE", "RO", "RS", "RU", "RW",
+    "SA", "SB", "SC", "SD", "SE", "SG", "SH", "SI", "SJ", "SK", "SL", "SM", "SN", "SO", "SR", "SS",
+    "ST", "SV", "SX", "SY", "SZ", "TC", "TD", "TF", "TG", "TH", "TJ", "TK", "TL", "TM", "TN", "TO",
+    "TR", "TT", "TV", "TW", "TZ", "UA", "UG", "UM", "US", "UY", "UZ", "VA", "VC", "VE", "VG", "VI",
+    "VN", "VU", "WF", "WS", "YE", "YT", "ZA", "ZM", "ZW"
+];
+
+/// The ISO 639-1 language codes.
+const LANGUAGES: &[&str] = &[
+    "aa", "ab", "ae", "af", "ak", "am", "an", "ar", "as", "av", "ay", "az", "ba", "be", "bg", "bi",
+    "bm", "bn", "bo", "br", "bs", "ca", "ce", "ch", "co", "cr", "cs", "cu", "cv", "cy", "da", "de",
+    "dv", "dz", "ee", "el", "en", "eo", "es", "et", "eu", "fa", "ff", "fi", "fj", "fo", "fr", "fy",
+    "ga", "gd", "gl", "gn", "gu", "gv", "ha", "he", "hi", "ho", "hr", "ht", "hu", "hy", "hz", "ia",
+    "id", "ie", "ig", "ii", "ik", "io", "is", "it", "iu", "ja", "jv", "ka", "kg", "ki", "kj", "kk",
+    "kl", "km", "kn", "ko", "kr", "ks", "ku", "kv", "kw", "ky", "la", "lb", "lg", "li", "ln", "lo",
+    "lt", "lu", "lv", "mg", "mh", "mi", "mk", "ml", "mn", "mr", "ms", "mt", "my", "na", "nb", "nd",
+    "ne", "ng", "nl", "nn", "no", "nr", "nv", "ny", "oc", "oj", "om", "or", "os", "pa", "pi", "pl",
+    "ps", "pt", "qu", "rm", "rn", "ro", "ru", "rw", "sa", "sc", "sd", "se", "sg", "si", "sk", "sl",
+    "sm", "sn", "so", "sq", "sr", "ss", "st", "su", "sv", "sw", "ta", "te", "tg", "th", "ti", "tk",
+    "tl", "tn", "to", "tr", "ts", "tt", "tw", "ty", "ug", "uk", "ur", "uz", "ve", "vi", "vo", "wa",
+    "wo", "xh", "yi", "yo", "za", "zh", "zu"
+];
+
+/// An ISO 3166-1 alpha-2 country code, such as `FR`, always upper case.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Country([u8; 2]);
+
+impl Country {
+    /// The code, such as `FR`.
+    pub fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.0).unwrap_or("")
+    }
+}
+
+impl fmt::Display for Country {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An ISO 639-1 language code, such as `fr`, always lower case.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Language([u8; 2]);
+
+impl Language {
+    /// The code, such as `fr`.
+    pub fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.0).unwrap_or("")
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The two letters of `value` if it is one of the sorted `codes`, compared
+/// after converting it with `case`.
+fn lookup(value: &str, codes: &[&str], case: fn(&u8) -> u8) -> Option<[u8; 2]> {
+    match value.trim().as_bytes() {
+        [a, b] => {
+            let code = [case(a), case(b)];
+            codes.binary_search_by(|known| known.as_bytes().cmp(&code[..])).ok().map(|_| code)
+        }
+        _ => None
+    }
+}
+
+/// Parse a country code such as `fr` or `FR`, in any case.
+pub fn parse_country(key: &str, value: &str) -> Result<Country, UrlDecodingError> {
+    lookup(value, COUNTRIES, u8::to_ascii_uppercase).map(Country).ok_or_else(|| UrlDecodingError::InvalidParameter {
+        key: key.to_owned(),
+        message: "is not an ISO 3166 country code".to_owned()
+    })
+}
+
+/// Parse a language code such as `fr` or `FR`, in any case. Longer tags such
+/// as `fr-CA` are refused: split them first when the region matters.
+pub fn parse_language(key: &str, value: &str) -> Result<Language, UrlDecodingError> {
+    lookup(value, LANGUAGES, u8::to_ascii_lowercase).map(Language).ok_or_else(|| UrlDecodingError::InvalidParameter {
+        key: key.to_owned(),
+        message: "is not an ISO 639-1 language code".to_owned()
+    })
+}
+
+#[test]
+fn test_parse_locale() {
+    assert_eq!(parse_country("country", "fr").unwrap().as_str(), "FR");
+    assert_eq!(parse_country("country", " US ").unwrap().to_string(), "US");
+    assert!(parse_country("country", "XX").is_err());
+    assert!(parse_country("country", "FRA").is_err());
+    assert!(parse_country("country", "É").is_err());
+
+    assert_eq!(parse_language("lang", "PT").unwrap().as_str(), "pt");
+    assert!(parse_language("lang", "fr-CA").is_err());
+    assert!(parse_language("lang", "xx").is_err());
+    assert!(COUNTRIES.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(LANGUAGES.windows(2).all(|pair| pair[0] < pair[1]));
+}
@@ -0,0 +1,78 @@
+//! Matrix parameters: `key=value` pairs attached to individual path segments,
+//! as in `/items;color=red;size=2/detail`.
+
+#[cfg(feature = "iron")]
+use iron::prelude::*;
+#[cfg(feature = "iron")]
+use iron::typemap::Key;
+
+#[cfg(feature = "iron")]
+use plugin;
+
+use url::percent_encoding::lossy_utf8_percent_decode;
+
+use super::{QueryMap, combine_duplicates};
+#[cfg(feature = "iron")]
+use super::UrlDecodingError;
+
+/// Plugin for `Request` that extracts matrix parameters from every segment of
+/// the URL path.
+///
+/// Use it like this: `req.get_ref::<MatrixParams>()`
+#[cfg(feature = "iron")]
+pub struct MatrixParams;
+
+/// A single path segment with its matrix parameters split off.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatrixSegment {
+    /// The decoded segment, without any parameters.
+    pub segment: String,
+    /// The decoded parameters of this segment. Empty if it had none.
+    pub params: QueryMap
+}
+
+#[cfg(feature = "iron")]
+impl Key for MatrixParams {
+    type Value = Vec<MatrixSegment>;
+}
+
+#[cfg(feature = "iron")]
+impl<'a, 'b> plugin::Plugin<Request<'a, 'b>> for MatrixParams {
+    type Error = UrlDecodingError;
+
+    fn eval(req: &mut Request) -> Result<Vec<MatrixSegment>, UrlDecodingError> {
+        Ok(req.url.path.iter().map(|segment| parse_matrix_segment(segment)).collect())
+    }
+}
+
+/// Split a raw path segment such as `items;color=red;size=2` into the segment
+/// itself and its parameters.
+///
+/// Unlike form data, `+` is left alone in path segments; only percent escapes
+/// are decoded.
+pub fn parse_matrix_segment(raw: &str) -> MatrixSegment {
+    let mut pieces = raw.split(';');
+    let segment = lossy_utf8_percent_decode(pieces.next().unwrap_or("").as_bytes());
+
+    let pairs = pieces.filter(|piece| !piece.is_empty()).map(|piece| {
+        let (key, value) = match piece.find('=') {
+            Some(position) => (&piece[..position], &piece[position + 1..]),
+            None => (piece, "")
+        };
+        (lossy_utf8_percent_decode(key.as_bytes()), lossy_utf8_percent_decode(value.as_bytes()))
+    }).collect();
+
+    MatrixSegment { segment, params: combine_duplicates(pairs) }
+}
+
+#[test]
+fn test_parse_matrix_segment() {
+    let parsed = parse_matrix_segment("my%20items;color=red;size=2;color=dark+blue");
+    assert_eq!(parsed.segment, "my items");
+    assert_eq!(parsed.params["color"], vec!["red".to_string(), "dark+blue".to_string()]);
+    assert_eq!(parsed.params["size"], vec!["2".to_string()]);
+
+    let plain = parse_matrix_segment("detail");
+    assert_eq!(plain.segment, "detail");
+    assert!(plain.params.is_empty());
+}
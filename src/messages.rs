@@ -0,0 +1,136 @@
+//! The text of every user-facing error message, behind a catalog that
+//! applications can replace to answer in the request's locale.
+
+use super::UrlDecodingError;
+use super::UrlDecodingError::*;
+use super::schema::{FieldError, FieldErrorKind, FieldType};
+
+/// A source of error messages.
+///
+/// Every method defaults to the English text, so a translation only needs to
+/// override what it covers. `Display` on the crate's errors uses `English`.
+///
+/// ```ignore
+/// struct French;
+///
+/// impl MessageCatalog for French {
+///     fn field_error(&self, error: &FieldError) -> String {
+///         match error.kind {
+///             FieldErrorKind::Required => format!("Le champ `{}` est obligatoire", error.field),
+///             _ => English.field_error(error)
+///         }
+///     }
+/// }
+/// ```
+pub trait MessageCatalog {
+    /// The message for a parsing or validation error.
+    ///
+    /// `InvalidParameter` errors of the typed accessors carry their own
+    /// English explanation, which is passed along as is. The errors of a
+    /// `ValidationFailed` report go through `field_error`.
+    fn error(&self, error: &UrlDecodingError) -> String {
+        match *error {
+            #[cfg(feature = "iron")]
+            BodyError(ref err) => err.to_string(),
+            EmptyQuery => "Expected query, found empty string.".to_owned(),
+            BodyReadTimeout => "The request body took too long to arrive.".to_owned(),
+            #[cfg(feature = "iron")]
+            MethodNotAllowedForBody(ref method) =>
+                format!("Request bodies are not accepted for {} requests.", method),
+            TooManyPairs(limit) => format!("Expected at most {} parameters.", limit),
+            KeyTooLong(limit) => format!("Parameter names may be at most {} bytes long.", limit),
+            ValueTooLong { ref key, limit } =>
+                format!("The value of `{}` may be at most {} bytes long.", key, limit),
+            NestingTooDeep(limit) =>
+                format!("Parameters may be nested at most {} levels deep.", limit),
+            TooManyNodes(limit) => format!("Expected at most {} nested values.", limit),
+            IndexTooLarge { ref key, limit } =>
+                format!("List indices of `{}` may be at most {}.", key, limit),
+            NestedConflict { ref key, first, second } =>
+                format!("`{}` is used both as {} and as {}.", key, first, second),
+            ReservedKey(ref key) => format!("`{}` uses a reserved name.", key),
+            ParameterPollution(ref key) => format!("Expected a single value for `{}`.", key),
+            MissingParameter(ref key) => format!("Expected a value for `{}`.", key),
+            InvalidParameter { ref key, ref message } => format!("`{}` {}.", key, message),
+            ValidationFailed(ref errors) => errors.iter()
+                .map(|error| format!("{}.", self.field_error(error)))
+                .collect::<Vec<_>>()
+                .join(" "),
+            MalformedMultipart(ref detail) => format!("The multipart body is malformed: {}.", detail),
+            UploadTooLarge { ref key, limit } =>
+                format!("The file sent as `{}` may be at most {} bytes long.", key, limit),
+            UploadsTooLarge(limit) => format!("The uploaded files may be at most {} bytes long in total.", limit),
+            TooManyFiles(limit) => format!("Expected at most {} files.", limit),
+            UnsupportedFileType { ref key, ref content_type } =>
+                format!("The file sent as `{}` is {}, which isn't accepted.", key, content_type),
+            ParseBudgetExceeded(resource) =>
+                format!("Parsing the parameters took more {} than allowed.", resource)
+        }
+    }
+
+    /// A short summary of the kind of error, such as the title of an
+    /// RFC 7807 problem.
+    fn problem_title(&self, error: &UrlDecodingError) -> String {
+        match *error {
+            ValidationFailed(_) => "The submitted form is invalid.".to_owned(),
+            _ => "The request parameters are invalid.".to_owned()
+        }
+    }
+
+    /// The message for one field of a form that failed validation, without a
+    /// final full stop.
+    fn field_error(&self, error: &FieldError) -> String {
+        let quoted = |fields: &[String]| -> Vec<String> {
+            fields.iter().map(|field| format!("`{}`", field)).collect()
+        };
+        let problem = match error.kind {
+            FieldErrorKind::Required => "is required".to_owned(),
+            FieldErrorKind::Repeated => "must be sent only once".to_owned(),
+            FieldErrorKind::Invalid(kind) => format!("must be {}", match kind {
+                FieldType::Text => "text",
+                FieldType::Integer => "an integer",
+                FieldType::Float => "a number",
+                FieldType::Boolean => "true or false",
+                FieldType::Email => "a valid email address",
+                FieldType::Url => "a valid absolute URL"
+            }),
+            FieldErrorKind::TooShort(min) => format!("must be at least {} characters long", min),
+            FieldErrorKind::TooLong(max) => format!("must be at most {} characters long", max),
+            FieldErrorKind::OutOfRange { min, max } => format!("must be between {} and {}", min, max),
+            FieldErrorKind::NotAllowed(ref allowed) => format!("must be one of: {}", allowed.join(", ")),
+            FieldErrorKind::Mismatch(ref other) => format!("must match `{}`", other),
+            FieldErrorKind::Before(ref other) => format!("must not be before `{}`", other),
+            FieldErrorKind::NoneGiven(ref others) =>
+                format!("or {} must be given", quoted(others).join(" or ")),
+            FieldErrorKind::Conflict(ref others) =>
+                format!("and {} cannot be given together", quoted(others).join(", ")),
+            FieldErrorKind::Custom(ref message) => message.clone()
+        };
+        format!("`{}` {}", error.field, problem)
+    }
+}
+
+/// The built-in English messages.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct English;
+
+impl MessageCatalog for English {}
+
+#[test]
+fn test_message_catalog() {
+    struct Shouting;
+
+    impl MessageCatalog for Shouting {
+        fn field_error(&self, error: &FieldError) -> String {
+            English.field_error(error).to_uppercase()
+        }
+    }
+
+    let error = ValidationFailed(vec![
+        FieldError::new("name", FieldErrorKind::Required),
+        FieldError::new("age", FieldErrorKind::OutOfRange { min: 0.0, max: 150.0 })
+    ]);
+    assert_eq!(error.to_string(), "`name` is required. `age` must be between 0 and 150.");
+    assert_eq!(Shouting.error(&error), "`NAME` IS REQUIRED. `AGE` MUST BE BETWEEN 0 AND 150.");
+    assert_eq!(Shouting.error(&TooManyPairs(3)), "Expected at most 3 parameters.");
+}
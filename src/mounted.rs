@@ -0,0 +1,92 @@
+//! Configurations chosen by path prefix, for apps mounting handlers under
+//! `/api`, `/admin` and so on.
+
+use iron::prelude::*;
+use iron::typemap::Key;
+use iron::BeforeMiddleware;
+
+use super::UrlEncodedConfig;
+
+/// The prefix a `MountedConfig` chose the configuration of a request for:
+/// `req.extensions.get::<MountPrefix>()`
+pub struct MountPrefix;
+
+impl Key for MountPrefix {
+    type Value = String;
+}
+
+/// `BeforeMiddleware` choosing the `UrlEncodedConfig` of each request by the
+/// longest prefix of its path, so handlers mounted under `/api` and `/admin`
+/// can parse with different limits.
+///
+/// Link it on the outer chain, before the `mount` crate's `Mount` strips the
+/// prefix from the URL. The chosen configuration is used by the plugins of
+/// the mounted handlers, unless they link one of their own; the prefix is
+/// recorded as `MountPrefix`. Requests under no prefix keep the configuration
+/// they would have had without it.
+///
+/// ```ignore
+/// let mut chain = Chain::new(mount);
+/// chain.link_before(MountedConfig::new()
+///     .mount("/api", UrlEncodedConfig::new().parse_options(ParseOptions::new().max_pairs(1000)))
+///     .mount("/admin", UrlEncodedConfig::new().parse_options(ParseOptions::new().max_pairs(50))));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MountedConfig {
+    mounts: Vec<(Vec<String>, UrlEncodedConfig)>
+}
+
+impl MountedConfig {
+    /// A middleware without prefixes.
+    pub fn new() -> MountedConfig {
+        MountedConfig::default()
+    }
+
+    /// Parse the requests under `prefix`, such as `/api/v1`, with `config`.
+    pub fn mount(mut self, prefix: &str, config: UrlEncodedConfig) -> MountedConfig {
+        self.mounts.push((segments(prefix), config));
+        self
+    }
+
+    /// The longest prefix of `path` and its configuration.
+    fn select(&self, path: &[String]) -> Option<(String, &UrlEncodedConfig)> {
+        self.mounts.iter()
+            .filter(|&(prefix, _)| path.starts_with(prefix))
+            .max_by_key(|&(prefix, _)| prefix.len())
+            .map(|(prefix, config)| (format!("/{}", prefix.join("/")), config))
+    }
+}
+
+/// The non-empty segments of `path`, so `/api/`, `api` and `/api` are the same
+/// prefix.
+fn segments(path: &str) -> Vec<String> {
+    path.split('/').filter(|segment| !segment.is_empty()).map(|segment| segment.to_owned()).collect()
+}
+
+impl BeforeMiddleware for MountedConfig {
+    fn before(&self, req: &mut Request) -> IronResult<()> {
+        let path: Vec<String> = req.url.path.iter().filter(|segment| !segment.is_empty()).cloned().collect();
+        if let Some((prefix, config)) = self.select(&path) {
+            req.extensions.insert::<UrlEncodedConfig>(config.clone());
+            req.extensions.insert::<MountPrefix>(prefix);
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_mounted_config() {
+    use super::ParseOptions;
+
+    let mounted = MountedConfig::new()
+        .mount("/api", UrlEncodedConfig::new().parse_options(ParseOptions::new().max_pairs(1000)))
+        .mount("/api/admin/", UrlEncodedConfig::new().parse_options(ParseOptions::new().max_pairs(5)));
+    let select = |path: &str| mounted.select(&segments(path)).map(|(prefix, config)| {
+        (prefix, config.parse("a=1&b=2&c=3&d=4&e=5&f=6").is_ok())
+    });
+
+    assert_eq!(select("/api/users"), Some(("/api".to_owned(), true)));
+    assert_eq!(select("/api/admin/users"), Some(("/api/admin".to_owned(), false)));
+    assert_eq!(select("/apis"), None);
+    assert_eq!(select("/"), None);
+}
@@ -0,0 +1,803 @@
+//! `multipart/form-data` bodies: the fields of upload forms along with their
+//! files, which are kept in memory or spooled to disk.
+
+use std::env;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use iron::prelude::*;
+use iron::headers::ContentType;
+use iron::mime::{Mime, TopLevel, SubLevel, Attr, Value};
+use iron::typemap::Key;
+
+use plugin;
+use unicode_normalization::UnicodeNormalization;
+
+use super::{QueryMap, UrlDecodingError, ParseOptions, request_config};
+use super::parse::{check_pair, check_single, meter};
+use super::timeout::TimedReader;
+use super::reader::read_error;
+
+/// Limits and storage of `multipart/form-data` bodies, set with
+/// `UrlEncodedConfig::multipart_options`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MultipartOptions {
+    spool_threshold: usize,
+    spool_dir: Option<PathBuf>,
+    max_parts: usize,
+    max_field_length: usize,
+    max_file_size: Option<u64>,
+    max_total_size: Option<u64>,
+    max_files: Option<usize>,
+    allowed_types: Option<Vec<String>>
+}
+
+impl Default for MultipartOptions {
+    fn default() -> MultipartOptions {
+        MultipartOptions {
+            spool_threshold: 256 * 1024,
+            spool_dir: None,
+            max_parts: 1000,
+            max_field_length: 64 * 1024,
+            max_file_size: None,
+            max_total_size: None,
+            max_files: None,
+            allowed_types: None
+        }
+    }
+}
+
+impl MultipartOptions {
+    /// Files spooled to the temporary directory above 256 KiB, at most 1000
+    /// parts and fields of at most 64 KiB, and files of any number, size and
+    /// type.
+    pub fn new() -> MultipartOptions {
+        MultipartOptions::default()
+    }
+
+    /// Keep files of up to `bytes` bytes in memory and spool bigger ones to
+    /// disk.
+    pub fn spool_threshold(mut self, bytes: usize) -> MultipartOptions {
+        self.spool_threshold = bytes;
+        self
+    }
+
+    /// Spool files into `dir` instead of the temporary directory.
+    pub fn spool_dir<P: Into<PathBuf>>(mut self, dir: P) -> MultipartOptions {
+        self.spool_dir = Some(dir.into());
+        self
+    }
+
+    /// Fail with `TooManyPairs` when the body holds more than `max` fields
+    /// and files.
+    pub fn max_parts(mut self, max: usize) -> MultipartOptions {
+        self.max_parts = max;
+        self
+    }
+
+    /// Fail with `ValueTooLong` when a field, as opposed to a file, is longer
+    /// than `max` bytes.
+    pub fn max_field_length(mut self, max: usize) -> MultipartOptions {
+        self.max_field_length = max;
+        self
+    }
+
+    /// Fail with `UploadTooLarge` as soon as a file is bigger than `bytes`.
+    pub fn max_file_size(mut self, bytes: u64) -> MultipartOptions {
+        self.max_file_size = Some(bytes);
+        self
+    }
+
+    /// Fail with `UploadsTooLarge` as soon as the files are bigger than
+    /// `bytes` together.
+    pub fn max_total_size(mut self, bytes: u64) -> MultipartOptions {
+        self.max_total_size = Some(bytes);
+        self
+    }
+
+    /// Fail with `TooManyFiles` when more than `max` files are uploaded.
+    pub fn max_files(mut self, max: usize) -> MultipartOptions {
+        self.max_files = Some(max);
+        self
+    }
+
+    /// Only accept files of these media types, such as `image/png`, or `image/*`
+    /// for a whole type, and fail with `UnsupportedFileType` before reading
+    /// any other file. Files sent without a type are
+    /// `application/octet-stream`.
+    pub fn allowed_types(mut self, types: &[&str]) -> MultipartOptions {
+        self.allowed_types = Some(types.iter().map(|media_type| media_type.to_lowercase()).collect());
+        self
+    }
+
+    /// Where files are spooled.
+    pub fn spool_directory(&self) -> PathBuf {
+        self.spool_dir.clone().unwrap_or_else(env::temp_dir)
+    }
+
+    /// Whether files of `content_type` are accepted.
+    fn allows_type(&self, content_type: &str) -> bool {
+        let media_type = content_type.split(';').next().unwrap_or("").trim().to_lowercase();
+        match self.allowed_types {
+            None => true,
+            Some(ref allowed) => allowed.iter().any(|pattern| match pattern.strip_suffix("/*") {
+                Some(top_level) => media_type.split('/').next() == Some(top_level),
+                None => *pattern == media_type
+            })
+        }
+    }
+}
+
+/// A file spooled to disk, removed when dropped unless it is persisted.
+pub struct SpooledFile {
+    path: Option<PathBuf>
+}
+
+impl SpooledFile {
+    /// Where the file is.
+    pub fn path(&self) -> &Path {
+        self.path.as_ref().expect("the file is only moved out by persist")
+    }
+
+    /// Move the file to `destination` and keep it there.
+    pub fn persist(mut self, destination: &Path) -> io::Result<()> {
+        let path = self.path.take().expect("the file is only moved out by persist");
+        match fs::rename(&path, destination) {
+            Ok(()) => Ok(()),
+            Err(err) => {
+                self.path = Some(path);
+                Err(err)
+            }
+        }
+    }
+}
+
+impl fmt::Debug for SpooledFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SpooledFile({:?})", self.path)
+    }
+}
+
+impl Drop for SpooledFile {
+    fn drop(&mut self) {
+        if let Some(ref path) = self.path {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// The contents of an uploaded file.
+#[derive(Debug)]
+pub enum FileData {
+    /// A small file, kept in memory.
+    Memory(Vec<u8>),
+    /// A file bigger than the spool threshold.
+    Spooled(SpooledFile)
+}
+
+/// A file uploaded with a form.
+#[derive(Debug)]
+pub struct FilePart {
+    /// The name of the form field.
+    pub field: String,
+    filename: String,
+    /// The media type the client sent, if any.
+    pub content_type: Option<String>,
+    /// The size of the file, in bytes.
+    pub size: u64,
+    /// The contents of the file.
+    pub data: FileData
+}
+
+impl FilePart {
+    /// The file name the client sent, made safe to use in a path with
+    /// `sanitize_filename`. `None` when nothing usable is left of it.
+    pub fn filename(&self) -> Option<String> {
+        sanitize_filename(&self.filename)
+    }
+
+    /// The file name exactly as the client sent it, which may hold paths,
+    /// control characters or anything else: never use it as a path.
+    pub fn raw_filename(&self) -> &str {
+        &self.filename
+    }
+
+    /// Read the whole file.
+    pub fn bytes(&self) -> io::Result<Vec<u8>> {
+        match self.data {
+            FileData::Memory(ref bytes) => Ok(bytes.clone()),
+            FileData::Spooled(ref file) => {
+                let mut bytes = Vec::new();
+                File::open(file.path())?.read_to_end(&mut bytes)?;
+                Ok(bytes)
+            }
+        }
+    }
+}
+
+/// The fields and files of a `multipart/form-data` body.
+#[derive(Debug, Default)]
+pub struct MultipartForm {
+    /// The fields, as `UrlEncodedBody` would have parsed them from a
+    /// urlencoded body.
+    pub fields: QueryMap,
+    /// The files, in the order they were sent.
+    pub files: Vec<FilePart>
+}
+
+impl MultipartForm {
+    /// The first file sent for `field`.
+    pub fn file(&self, field: &str) -> Option<&FilePart> {
+        self.files.iter().find(|file| file.field == field)
+    }
+
+    /// Every file sent for `field`, for inputs with `multiple`.
+    pub fn files(&self, field: &str) -> Vec<&FilePart> {
+        self.files.iter().filter(|file| file.field == field).collect()
+    }
+}
+
+/// Plugin for `Request` that parses `multipart/form-data` bodies:
+/// `req.get_ref::<MultipartBody>()`
+///
+/// `UrlEncodedBody` yields the fields of these requests, so handlers and
+/// `SchemaForm` read them as they would read a urlencoded form. Requests of
+/// another type fail with `EmptyQuery`.
+pub struct MultipartBody;
+
+impl Key for MultipartBody {
+    type Value = MultipartForm;
+}
+
+impl<'a, 'b> plugin::Plugin<Request<'a, 'b>> for MultipartBody {
+    type Error = UrlDecodingError;
+
+    fn eval(req: &mut Request) -> Result<MultipartForm, UrlDecodingError> {
+        let config = request_config(req);
+        if !config.allowed_body_methods().allows(&req.method) {
+            return Err(UrlDecodingError::MethodNotAllowedForBody(req.method.clone()));
+        }
+        let boundary = boundary(req).ok_or(UrlDecodingError::EmptyQuery)?;
+
+        match config.body_read_timeout() {
+            Some(timeout) => config.parse_multipart(TimedReader::new(&mut req.body, timeout), &boundary),
+            None => config.parse_multipart(&mut req.body, &boundary)
+        }
+    }
+}
+
+/// The boundary of a `multipart/form-data` request.
+pub fn boundary(req: &Request) -> Option<String> {
+    match req.headers.get::<ContentType>() {
+        Some(&ContentType(Mime(TopLevel::Multipart, SubLevel::FormData, ref params))) =>
+            params.iter().find(|param| param.0 == Attr::Boundary).and_then(|param| match param.1 {
+                Value::Ext(ref boundary) => Some(boundary.clone()),
+                _ => None
+            }),
+        _ => None
+    }
+}
+
+/// Parse a `multipart/form-data` body delimited by `boundary`, reading it as
+/// it goes so that files never need to fit in memory.
+///
+/// File inputs left empty, sent as a part with an empty file name and no
+/// content, are skipped.
+pub fn parse_multipart<R: Read>(body: R, boundary: &str, options: &MultipartOptions)
+                                -> Result<MultipartForm, UrlDecodingError> {
+    parse_multipart_with(body, boundary, options, &ParseOptions::new())
+}
+
+/// Like `parse_multipart`, also holding the fields, as opposed to the files,
+/// to the limits of `fields`, as `parse_with` holds urlencoded pairs: their
+/// number, the lengths of their names and values, single-only keys and the
+/// `ParseBudget`. As the body is parsed while it is read, the time of the
+/// budget includes reading it.
+pub fn parse_multipart_with<R: Read>(body: R, boundary: &str, options: &MultipartOptions, fields: &ParseOptions)
+                                     -> Result<MultipartForm, UrlDecodingError> {
+    let delimiter = format!("\r\n--{}", boundary).into_bytes();
+    // The first boundary isn't preceded by a line break: pretend it is.
+    let mut reader = PartReader { inner: body, buf: b"\r\n".to_vec(), eof: false };
+    let mut form = MultipartForm::default();
+    let mut parts = 0;
+    let mut pairs = 0;
+    let mut meter = meter(fields);
+    let mut total_size = 0;
+
+    reader.read_until(&delimiter, |_| Ok(()))?;
+    loop {
+        match &reader.peek(2)?[..] {
+            b"--" => {
+                meter.finish();
+                check_single(&form.fields, fields)?;
+                return Ok(form);
+            },
+            b"\r\n" => reader.consume(2),
+            _ => return Err(malformed("a boundary is followed by garbage"))
+        }
+
+        parts += 1;
+        if parts > options.max_parts {
+            return Err(UrlDecodingError::TooManyPairs(options.max_parts));
+        }
+
+        let headers = reader.read_headers()?;
+        let disposition = headers.get("content-disposition")
+            .ok_or_else(|| malformed("a part has no Content-Disposition"))?;
+        let params = disposition_params(disposition);
+        let field = params.name.ok_or_else(|| malformed("a part has no name"))?;
+
+        match params.filename {
+            None => {
+                let mut value = Vec::new();
+                reader.read_until(&delimiter, |chunk| {
+                    if value.len() + chunk.len() > options.max_field_length {
+                        return Err(UrlDecodingError::ValueTooLong {
+                            key: field.clone(),
+                            limit: options.max_field_length
+                        });
+                    }
+                    value.extend_from_slice(chunk);
+                    Ok(())
+                })?;
+                let value = String::from_utf8_lossy(&value).into_owned();
+                check_pair(&field, &value, pairs, fields)?;
+                meter.charge(&field, &value)?;
+                pairs += 1;
+                form.fields.entry(field).or_default().push(value);
+            },
+            Some(filename) => {
+                let content_type = headers.get("content-type").cloned();
+                // Empty file inputs are skipped, so files without a name are
+                // only checked once they turn out to have content.
+                let mut checked = !filename.is_empty();
+                if checked {
+                    check_file(&form, &field, content_type.as_deref(), options)?;
+                }
+                let mut sink = FileSink { memory: Vec::new(), file: None, size: 0 };
+                reader.read_until(&delimiter, |chunk| {
+                    if !checked && !chunk.is_empty() {
+                        check_file(&form, &field, content_type.as_deref(), options)?;
+                        checked = true;
+                    }
+                    total_size += chunk.len() as u64;
+                    match (options.max_file_size, options.max_total_size) {
+                        (Some(limit), _) if sink.size + chunk.len() as u64 > limit =>
+                            Err(UrlDecodingError::UploadTooLarge { key: field.clone(), limit }),
+                        (_, Some(limit)) if total_size > limit => Err(UrlDecodingError::UploadsTooLarge(limit)),
+                        _ => sink.write(chunk, options)
+                    }
+                })?;
+                if filename.is_empty() && sink.size == 0 {
+                    continue;
+                }
+                form.files.push(FilePart {
+                    field,
+                    filename,
+                    content_type,
+                    size: sink.size,
+                    data: sink.finish()?
+                });
+            }
+        }
+    }
+}
+
+/// The longest file name most file systems accept, in bytes.
+const MAX_FILENAME_LENGTH: usize = 255;
+
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9"
+];
+
+/// Make a file name sent by a client safe to store a file under:
+///
+/// - only the last component of Unix and Windows paths is kept;
+/// - control characters and the invisible characters used to disguise
+///   extensions, such as right-to-left overrides and zero-width spaces, are
+///   removed, and the characters Windows forbids become `_`;
+/// - names are normalized to Unicode NFC, so an accented letter sent
+///   decomposed is stored like the same letter sent composed;
+/// - Unicode spaces become plain spaces, and leading and trailing dots and
+///   spaces are trimmed, so the name can't be hidden, `.` or `..`;
+/// - Windows device names such as `CON` or `nul.txt` get a `_` prefix;
+/// - names are cut to 255 bytes, keeping the extension.
+///
+/// `None` when nothing is left.
+pub fn sanitize_filename(raw: &str) -> Option<String> {
+    let base = raw.rsplit(['/', '\\']).next().unwrap_or(raw);
+    let cleaned: String = base.nfc()
+        .filter(|&c| !c.is_control() && !is_invisible(c))
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '|' | '?' | '*' => '_',
+            c if c.is_whitespace() => ' ',
+            c => c
+        })
+        .collect();
+    let trimmed = cleaned.trim_matches(['.', ' ']);
+    if trimmed.is_empty() {
+        return None;
+    }
+
+    let stem = trimmed.split('.').next().unwrap_or(trimmed).trim_end();
+    let mut name = if RESERVED_NAMES.iter().any(|reserved| reserved.eq_ignore_ascii_case(stem)) {
+        format!("_{}", trimmed)
+    } else {
+        trimmed.to_owned()
+    };
+
+    if name.len() > MAX_FILENAME_LENGTH {
+        let extension = match name.rfind('.') {
+            Some(dot) if name.len() - dot <= 16 => name[dot..].to_owned(),
+            _ => String::new()
+        };
+        let mut end = MAX_FILENAME_LENGTH - extension.len();
+        while !name.is_char_boundary(end) {
+            end -= 1;
+        }
+        name = format!("{}{}", name[..end].trim_end_matches(['.', ' ']), extension);
+    }
+    Some(name)
+}
+
+/// Format characters that change how a name displays without showing up.
+fn is_invisible(c: char) -> bool {
+    matches!(c, '\u{200b}'..='\u{200f}' | '\u{202a}'..='\u{202e}' | '\u{2060}'..='\u{2069}' | '\u{feff}')
+}
+
+/// Check the number and type of files before any of another one is stored.
+fn check_file(form: &MultipartForm, field: &str, content_type: Option<&str>, options: &MultipartOptions)
+              -> Result<(), UrlDecodingError> {
+    if let Some(max) = options.max_files {
+        if form.files.len() >= max {
+            return Err(UrlDecodingError::TooManyFiles(max));
+        }
+    }
+    let content_type = content_type.unwrap_or("application/octet-stream");
+    if !options.allows_type(content_type) {
+        return Err(UrlDecodingError::UnsupportedFileType {
+            key: field.to_owned(),
+            content_type: content_type.to_owned()
+        });
+    }
+    Ok(())
+}
+
+/// Reads a body up to successive delimiters.
+struct PartReader<R> {
+    inner: R,
+    buf: Vec<u8>,
+    eof: bool
+}
+
+impl<R: Read> PartReader<R> {
+    /// Read some more of the body. `false` at the end of it.
+    fn fill(&mut self) -> Result<bool, UrlDecodingError> {
+        if self.eof {
+            return Ok(false);
+        }
+        let mut chunk = [0; 8192];
+        let read = self.inner.read(&mut chunk).map_err(|err| read_error("Can't read request body", err))?;
+        self.eof = read == 0;
+        self.buf.extend_from_slice(&chunk[..read]);
+        Ok(!self.eof)
+    }
+
+    /// The next `len` bytes, without consuming them.
+    fn peek(&mut self, len: usize) -> Result<Vec<u8>, UrlDecodingError> {
+        while self.buf.len() < len {
+            if !self.fill()? {
+                return Err(malformed("it ends before its closing boundary"));
+            }
+        }
+        Ok(self.buf[..len].to_vec())
+    }
+
+    fn consume(&mut self, len: usize) {
+        self.buf.drain(..len);
+    }
+
+    /// Pass the bytes up to `delimiter` to `sink`, as they are read, and
+    /// consume the delimiter.
+    fn read_until<F>(&mut self, delimiter: &[u8], mut sink: F) -> Result<(), UrlDecodingError>
+    where F: FnMut(&[u8]) -> Result<(), UrlDecodingError> {
+        loop {
+            if let Some(position) = self.buf.windows(delimiter.len()).position(|window| window == delimiter) {
+                sink(&self.buf[..position])?;
+                self.consume(position + delimiter.len());
+                return Ok(());
+            }
+
+            // Keep what could be the start of the delimiter.
+            let complete = self.buf.len().saturating_sub(delimiter.len() - 1);
+            if complete > 0 {
+                sink(&self.buf[..complete])?;
+                self.consume(complete);
+            }
+            if !self.fill()? {
+                return Err(malformed("it ends before its closing boundary"));
+            }
+        }
+    }
+
+    /// The headers of a part, by lowercase name.
+    fn read_headers(&mut self) -> Result<QueryHeaders, UrlDecodingError> {
+        if self.peek(2)? == b"\r\n" {
+            return Err(malformed("a part has no Content-Disposition"));
+        }
+
+        let mut raw = Vec::new();
+        self.read_until(b"\r\n\r\n", |chunk| {
+            if raw.len() + chunk.len() > MAX_HEADERS_LENGTH {
+                return Err(malformed("the headers of a part are too long"));
+            }
+            raw.extend_from_slice(chunk);
+            Ok(())
+        })?;
+
+        let mut headers = QueryHeaders::new();
+        for line in String::from_utf8_lossy(&raw).split("\r\n") {
+            if let Some(colon) = line.find(':') {
+                headers.insert(line[..colon].trim().to_lowercase(), line[colon + 1..].trim().to_owned());
+            }
+        }
+        Ok(headers)
+    }
+}
+
+type QueryHeaders = ::std::collections::HashMap<String, String>;
+
+const MAX_HEADERS_LENGTH: usize = 8 * 1024;
+
+/// The `name` and `filename` of a `Content-Disposition: form-data` header.
+struct Disposition {
+    name: Option<String>,
+    filename: Option<String>
+}
+
+/// Read the parameters of a `Content-Disposition` header. Quoted values are
+/// taken literally, apart from the `%22`, `%0D` and `%0A` escapes browsers
+/// use for quotes and line breaks, and `filename*` wins over `filename`.
+fn disposition_params(header: &str) -> Disposition {
+    let mut disposition = Disposition { name: None, filename: None };
+    let mut extended_filename = None;
+    let mut rest = header;
+
+    while let Some(semicolon) = rest.find(';') {
+        rest = rest[semicolon + 1..].trim_start();
+        let equals = match rest.find('=') {
+            Some(equals) => equals,
+            None => break
+        };
+        let name = rest[..equals].trim().to_lowercase();
+        let value_start = rest[equals + 1..].trim_start();
+        let (value, next) = if let Some(quoted) = value_start.strip_prefix('"') {
+            match quoted.find('"') {
+                Some(end) => (unescape(&quoted[..end]), &quoted[end + 1..]),
+                None => (unescape(quoted), "")
+            }
+        } else {
+            let end = value_start.find(';').unwrap_or(value_start.len());
+            (value_start[..end].trim().to_owned(), &value_start[end..])
+        };
+        rest = next;
+
+        match &*name {
+            "name" => disposition.name = Some(value),
+            "filename" => disposition.filename = Some(value),
+            "filename*" => extended_filename = decode_extended(&value),
+            _ => ()
+        }
+    }
+
+    if extended_filename.is_some() {
+        disposition.filename = extended_filename;
+    }
+    disposition
+}
+
+fn unescape(value: &str) -> String {
+    value.replace("%22", "\"").replace("%0D", "\r").replace("%0A", "\n")
+}
+
+/// Decode an RFC 5987 value such as `UTF-8''%E2%82%AC%20rates.pdf`.
+fn decode_extended(value: &str) -> Option<String> {
+    let mut pieces = value.splitn(3, '\'');
+    let charset = pieces.next()?;
+    let encoded = pieces.nth(1)?;
+    if !charset.eq_ignore_ascii_case("utf-8") {
+        return None;
+    }
+    Some(url::percent_encoding::lossy_utf8_percent_decode(encoded.as_bytes()))
+}
+
+/// Collects a file, spooling it to disk once it outgrows the threshold.
+struct FileSink {
+    memory: Vec<u8>,
+    file: Option<(File, SpooledFile)>,
+    size: u64
+}
+
+impl FileSink {
+    fn write(&mut self, chunk: &[u8], options: &MultipartOptions) -> Result<(), UrlDecodingError> {
+        self.size += chunk.len() as u64;
+
+        if self.file.is_none() && self.memory.len() + chunk.len() > options.spool_threshold {
+            let (mut file, spooled) = create_spool_file(&options.spool_directory())
+                .map_err(|err| read_error("Can't spool upload to disk", err))?;
+            file.write_all(&self.memory).map_err(|err| read_error("Can't spool upload to disk", err))?;
+            self.memory = Vec::new();
+            self.file = Some((file, spooled));
+        }
+
+        match self.file {
+            Some((ref mut file, _)) =>
+                file.write_all(chunk).map_err(|err| read_error("Can't spool upload to disk", err)),
+            None => {
+                self.memory.extend_from_slice(chunk);
+                Ok(())
+            }
+        }
+    }
+
+    fn finish(self) -> Result<FileData, UrlDecodingError> {
+        match self.file {
+            Some((mut file, spooled)) => {
+                file.flush().map_err(|err| read_error("Can't spool upload to disk", err))?;
+                Ok(FileData::Spooled(spooled))
+            },
+            None => Ok(FileData::Memory(self.memory))
+        }
+    }
+}
+
+static SPOOL_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A new, empty file in `dir` with a name no other upload uses.
+pub fn create_spool_file(dir: &Path) -> io::Result<(File, SpooledFile)> {
+    loop {
+        let name = format!("urlencoded-upload-{}-{}", process::id(), SPOOL_COUNTER.fetch_add(1, Ordering::SeqCst));
+        let path = dir.join(name);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((file, SpooledFile { path: Some(path) })),
+            Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err)
+        }
+    }
+}
+
+fn malformed(detail: &str) -> UrlDecodingError {
+    UrlDecodingError::MalformedMultipart(detail.to_owned())
+}
+
+#[cfg(test)]
+fn upload_body() -> &'static str {
+    "preamble\r\n--XyZ\r\n\
+     Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+     Holiday \"photos\"\r\n--XyZ\r\n\
+     Content-Disposition: form-data; name=\"photo\"; filename=\"beach.jpg\"\r\n\
+     Content-Type: image/jpeg\r\n\r\n\
+     \u{ff}\u{d8}JPEG--XyZ-ish\r\n--XyZ\r\n\
+     Content-Disposition: form-data; name=\"empty\"; filename=\"\"\r\n\
+     Content-Type: application/octet-stream\r\n\r\n\
+     \r\n--XyZ--\r\nepilogue"
+}
+
+#[test]
+fn test_parse_multipart() {
+    use super::{ParseBudget, BudgetResource};
+
+    let body = upload_body();
+    let form = parse_multipart(body.as_bytes(), "XyZ", &MultipartOptions::new()).unwrap();
+    assert_eq!(form.fields["title"], vec!["Holiday \"photos\"".to_owned()]);
+    assert_eq!(form.files.len(), 1);
+
+    let photo = form.file("photo").unwrap();
+    assert_eq!(photo.raw_filename(), "beach.jpg");
+    assert_eq!(photo.filename(), Some("beach.jpg".to_owned()));
+    assert_eq!(photo.content_type, Some("image/jpeg".to_owned()));
+    assert_eq!(photo.bytes().unwrap(), "\u{ff}\u{d8}JPEG--XyZ-ish".as_bytes());
+    assert_eq!(photo.size, 17);
+
+    // Reading one byte at a time must give the same result.
+    struct Trickle<'a>(&'a [u8]);
+    impl<'a> Read for Trickle<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() || buf.is_empty() {
+                return Ok(0);
+            }
+            buf[0] = self.0[0];
+            self.0 = &self.0[1..];
+            Ok(1)
+        }
+    }
+    let spooled = parse_multipart(Trickle(body.as_bytes()), "XyZ", &MultipartOptions::new().spool_threshold(4))
+        .unwrap();
+    let photo = spooled.file("photo").unwrap();
+    let path = match photo.data {
+        FileData::Spooled(ref file) => file.path().to_owned(),
+        FileData::Memory(_) => panic!("the photo should be spooled")
+    };
+    assert_eq!(photo.bytes().unwrap(), "\u{ff}\u{d8}JPEG--XyZ-ish".as_bytes());
+    drop(spooled);
+    assert!(!path.exists());
+
+    assert!(matches!(parse_multipart(&body.as_bytes()[..80], "XyZ", &MultipartOptions::new()),
+                     Err(UrlDecodingError::MalformedMultipart(_))));
+    assert!(matches!(parse_multipart(body.as_bytes(), "XyZ", &MultipartOptions::new().max_parts(2)),
+                     Err(UrlDecodingError::TooManyPairs(2))));
+    assert!(matches!(parse_multipart(body.as_bytes(), "XyZ", &MultipartOptions::new().max_field_length(5)),
+                     Err(UrlDecodingError::ValueTooLong { .. })));
+
+    let rejects = |options: MultipartOptions| parse_multipart(body.as_bytes(), "XyZ", &options).unwrap_err().code();
+    assert_eq!(rejects(MultipartOptions::new().max_file_size(10)), "UPLOAD_TOO_LARGE");
+    assert_eq!(rejects(MultipartOptions::new().max_total_size(16)), "UPLOADS_TOO_LARGE");
+    assert_eq!(rejects(MultipartOptions::new().max_files(0)), "TOO_MANY_FILES");
+    assert_eq!(rejects(MultipartOptions::new().allowed_types(&["image/png", "text/*"])), "UNSUPPORTED_FILE_TYPE");
+    let options = MultipartOptions::new().max_files(1).max_file_size(17).allowed_types(&["IMAGE/*"]);
+    assert_eq!(parse_multipart(body.as_bytes(), "XyZ", &options).unwrap().files.len(), 1);
+
+    let fields = |data: &str, limits: ParseOptions| {
+        parse_multipart_with(data.as_bytes(), "XyZ", &MultipartOptions::new(), &limits).map(|form| form.fields)
+    };
+    let repeated = "--XyZ\r\nContent-Disposition: form-data; name=\"id\"\r\n\r\n1\r\n\
+                    --XyZ\r\nContent-Disposition: form-data; name=\"id\"\r\n\r\n2\r\n--XyZ--\r\n";
+    assert_eq!(fields(repeated, ParseOptions::new()).unwrap()["id"].len(), 2);
+    assert!(matches!(fields(repeated, ParseOptions::new().single_only(&["id"])),
+                     Err(UrlDecodingError::ParameterPollution(_))));
+    assert!(matches!(fields(repeated, ParseOptions::new().max_pairs(1)), Err(UrlDecodingError::TooManyPairs(1))));
+    assert!(matches!(fields(repeated, ParseOptions::new().max_key_length(1)), Err(UrlDecodingError::KeyTooLong(1))));
+    assert!(fields(body, ParseOptions::new().max_pairs(1)).is_ok());
+    assert!(matches!(fields(repeated, ParseOptions::new().budget(ParseBudget::new().max_allocations(4))),
+                     Err(UrlDecodingError::ParseBudgetExceeded(BudgetResource::Allocations))));
+
+    // A file without a name but with content is still a file.
+    let unnamed = "--XyZ\r\n\
+                   Content-Disposition: form-data; name=\"doc\"; filename=\"\"\r\n\
+                   Content-Type: text/html\r\n\r\n\
+                   <script>\r\n--XyZ--\r\n";
+    let rejects = |options: MultipartOptions| parse_multipart(unnamed.as_bytes(), "XyZ", &options).unwrap_err().code();
+    assert_eq!(rejects(MultipartOptions::new().max_files(0)), "TOO_MANY_FILES");
+    assert_eq!(rejects(MultipartOptions::new().allowed_types(&["image/*"])), "UNSUPPORTED_FILE_TYPE");
+    assert_eq!(parse_multipart(unnamed.as_bytes(), "XyZ", &MultipartOptions::new()).unwrap().files.len(), 1);
+
+    // Rejected files are neither read further nor spooled.
+    struct Unread;
+    impl Read for Unread {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("the rest of the file was read"))
+        }
+    }
+    let dir = env::temp_dir().join(format!("urlencoded-rejected-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let stops = |start: &str, options: MultipartOptions| {
+        let options = options.spool_threshold(0).spool_dir(&dir);
+        parse_multipart(start.as_bytes().chain(Unread), "XyZ", &options).unwrap_err().code()
+    };
+    let named = "--XyZ\r\nContent-Disposition: form-data; name=\"doc\"; filename=\"a.html\"\r\n\r\n";
+    assert_eq!(stops(named, MultipartOptions::new().max_files(0)), "TOO_MANY_FILES");
+    assert_eq!(stops(&unnamed[..unnamed.len() - 10], MultipartOptions::new().allowed_types(&["image/*"])),
+               "UNSUPPORTED_FILE_TYPE");
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    fs::remove_dir(&dir).unwrap();
+
+    let params = disposition_params("form-data; name=\"a%22b\"; filename=\"old.txt\"; filename*=UTF-8''%E2%82%AC.txt");
+    assert_eq!(params.name, Some("a\"b".to_owned()));
+    assert_eq!(params.filename, Some("€.txt".to_owned()));
+
+    assert_eq!(sanitize_filename("../../etc/passwd"), Some("passwd".to_owned()));
+    assert_eq!(sanitize_filename("C:\\Users\\ann\\report.pdf"), Some("report.pdf".to_owned()));
+    assert_eq!(sanitize_filename("invoice\u{202e}fdp.exe"), Some("invoicefdp.exe".to_owned()));
+    assert_eq!(sanitize_filename(" .hidden\r\n. "), Some("hidden".to_owned()));
+    assert_eq!(sanitize_filename("what?.txt"), Some("what_.txt".to_owned()));
+    assert_eq!(sanitize_filename("nul.tar.gz"), Some("_nul.tar.gz".to_owned()));
+    assert_eq!(sanitize_filename(".."), None);
+    assert_eq!(sanitize_filename("cafe\u{301}.txt"), Some("caf\u{e9}.txt".to_owned()));
+    let long = sanitize_filename(&format!("{}.jpeg", "é".repeat(200))).unwrap();
+    assert!(long.len() <= 255 && long.ends_with("é.jpeg"));
+}
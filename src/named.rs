@@ -0,0 +1,116 @@
+//! Pages hosting several forms posting to one endpoint, told apart by a
+//! hidden parameter naming the form.
+
+use std::collections::BTreeMap;
+
+#[cfg(feature = "iron")]
+use iron::prelude::*;
+
+use super::{QueryMap, UrlDecodingError};
+use super::accessors::QueryMapExt;
+use super::schema::{FormData, FormSchema};
+#[cfg(feature = "iron")]
+use super::form_params;
+
+/// A form that passed the validation of the schema its name selected.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NamedForm {
+    /// The name of the form, as registered.
+    pub name: String,
+    /// The converted values of the form.
+    pub data: FormData
+}
+
+/// The schemas of the forms of a page and the parameter naming which one was
+/// submitted, `_form` by default.
+///
+/// ```ignore
+/// let forms = NamedForms::new()
+///     .form("login", login_schema())
+///     .form("signup", signup_schema());
+/// // <input type="hidden" name="_form" value="login">
+/// let form = forms.from_request(req)?;
+/// match &*form.name { "login" => ..., _ => ... }
+/// ```
+#[derive(Clone, Debug)]
+pub struct NamedForms {
+    param: String,
+    forms: BTreeMap<String, FormSchema>
+}
+
+impl Default for NamedForms {
+    fn default() -> NamedForms {
+        NamedForms { param: "_form".to_owned(), forms: BTreeMap::new() }
+    }
+}
+
+/// The schema `params` names in `param` among `schemas`, and its name.
+/// A missing, repeated or unknown name is an error.
+pub fn select_schema<'a>(params: &QueryMap, param: &str, schemas: &'a BTreeMap<String, FormSchema>)
+                         -> Result<(&'a str, &'a FormSchema), UrlDecodingError> {
+    let name = params.single(param)?.ok_or_else(|| UrlDecodingError::MissingParameter(param.to_owned()))?;
+    schemas.get_key_value(name).map(|(name, schema)| (&**name, schema)).ok_or_else(|| {
+        UrlDecodingError::InvalidParameter {
+            key: param.to_owned(),
+            message: format!("must be one of: {}", schemas.keys().cloned().collect::<Vec<_>>().join(", "))
+        }
+    })
+}
+
+impl NamedForms {
+    /// No forms yet, named in `_form`.
+    pub fn new() -> NamedForms {
+        NamedForms::default()
+    }
+
+    /// Read the name of the form from `param` instead of `_form`.
+    pub fn param(mut self, param: &str) -> NamedForms {
+        self.param = param.to_owned();
+        self
+    }
+
+    /// Validate the form called `name` against `schema`.
+    pub fn form(mut self, name: &str, schema: FormSchema) -> NamedForms {
+        self.forms.insert(name.to_owned(), schema);
+        self
+    }
+
+    /// Validate `params` against the schema of the form they name.
+    pub fn validate(&self, params: &QueryMap) -> Result<NamedForm, UrlDecodingError> {
+        let (name, schema) = select_schema(params, &self.param, &self.forms)?;
+        let data = schema.validate(params).map_err(UrlDecodingError::ValidationFailed)?;
+        Ok(NamedForm { name: name.to_owned(), data })
+    }
+
+    /// Like `validate`, for the submitted form of `req`: the body of
+    /// form-encoded requests, the query string otherwise.
+    #[cfg(feature = "iron")]
+    pub fn from_request(&self, req: &mut Request) -> Result<NamedForm, UrlDecodingError> {
+        match form_params(req) {
+            Ok(params) => self.validate(&params),
+            Err(UrlDecodingError::EmptyQuery) => self.validate(&QueryMap::new()),
+            Err(err) => Err(err)
+        }
+    }
+}
+
+#[test]
+fn test_named_forms() {
+    use super::{parse_with, ParseOptions};
+    use super::schema::{Field, FieldType};
+
+    let forms = NamedForms::new()
+        .form("login", FormSchema::new().field(Field::new("email", FieldType::Email).required()))
+        .form("newsletter", FormSchema::new().field(Field::new("topic", FieldType::Text).required()));
+    let validate = |data: &str| forms.validate(&parse_with(data, &ParseOptions::new()).unwrap());
+
+    let form = validate("_form=newsletter&topic=rust").unwrap();
+    assert_eq!(form.name, "newsletter");
+    assert_eq!(form.data.str("topic"), Some("rust"));
+    assert!(matches!(validate("_form=login&topic=rust"), Err(UrlDecodingError::ValidationFailed(_))));
+    assert!(matches!(validate("topic=rust"), Err(UrlDecodingError::MissingParameter(_))));
+    assert_eq!(validate("_form=admin").unwrap_err().to_string(), "`_form` must be one of: login, newsletter.");
+
+    let renamed = forms.clone().param("form_id");
+    assert!(renamed.validate(&parse_with("form_id=login&email=a@b.com", &ParseOptions::new()).unwrap()).is_ok());
+}
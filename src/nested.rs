@@ -0,0 +1,656 @@
+//! Nested parameters written with brackets or dots, as in
+//! `user[address][city]=Paris&tags[]=a&tags[]=b`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::{UrlDecodingError, ParseOptions};
+use super::parse::{pairs_of, split_pieces};
+
+/// A value of the nested tree.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NestedValue {
+    /// A plain value, as in `a=1`.
+    Value(String),
+    /// A list built with `a[]=1` or `a[0]=1`, or by repeating `a=1&a=2`.
+    List(Vec<NestedValue>),
+    /// A map built with `a[b]=1` or, with dots enabled, `a.b=1`.
+    Map(NestedQueryMap)
+}
+
+/// How a name is used in nested keys, as reported by `NestedConflict`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NestedUsage {
+    /// As a plain value: `a=1`.
+    Value,
+    /// As a list: `a[]=1` or `a[0]=1`.
+    List,
+    /// As a map: `a[b]=1`.
+    Map
+}
+
+impl fmt::Display for NestedUsage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            NestedUsage::Value => "a value",
+            NestedUsage::List => "a list",
+            NestedUsage::Map => "a map"
+        })
+    }
+}
+
+/// What the nested parser does with structurally dangerous names: `__proto__`,
+/// `constructor`, `prototype`, and names with stray brackets, such as
+/// `a[b[c]]`, that weren't parsed as nesting.
+///
+/// Trees are often forwarded to JavaScript consumers or template engines,
+/// where such names can reach object prototypes.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum ReservedKeys {
+    /// Keep them like any other name. This is the default.
+    #[default]
+    Allow,
+    /// Fail with `ReservedKey`.
+    Reject,
+    /// Leave out the pairs using them.
+    Ignore
+}
+
+/// What the nested parser does with a name used in incompatible ways, such as
+/// a list and a map in `a[]=1&a[b]=2`.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum NestedConflicts {
+    /// Fail with `NestedConflict`. This is the default.
+    #[default]
+    Fail,
+    /// Merge them, as `qs` does: a list used as a map becomes a map keyed by
+    /// the indices, a value used as a map becomes a list of both, and a map
+    /// used as a value gets the value as a key set to `true`.
+    Merge,
+    /// Let the last use win, as PHP does: a value used as a list or a map is
+    /// replaced by it, a list used as a map becomes a map keyed by the
+    /// indices, and `[]` on a map adds the key after its greatest number.
+    Replace
+}
+
+/// Names that are dangerous in JavaScript objects.
+const RESERVED_NAMES: &[&str] = &["__proto__", "constructor", "prototype"];
+
+/// The top level of a nested parse result.
+pub type NestedQueryMap = BTreeMap<String, NestedValue>;
+
+/// Syntax and limits of the nested parser, on top of its `ParseOptions`.
+///
+/// Keys may be nested 32 levels deep by default, since each level is a level
+/// of recursion when building the tree; every other limit is off. Sparse
+/// indices never allocate, since lists are compacted in index order, but
+/// `max_index` still rejects them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NestedOptions {
+    dots: bool,
+    max_depth: Option<usize>,
+    max_nodes: Option<usize>,
+    max_index: Option<usize>,
+    reserved_keys: ReservedKeys,
+    depth_limit: Option<usize>,
+    array_limit: Option<usize>,
+    parameter_limit: Option<usize>,
+    comma_lists: bool,
+    conflicts: NestedConflicts,
+    last_value_wins: bool,
+    numeric_map_keys: bool,
+    append_to_last_map: bool,
+    php_names: bool
+}
+
+impl Default for NestedOptions {
+    fn default() -> NestedOptions {
+        NestedOptions {
+            dots: false,
+            max_depth: Some(32),
+            max_nodes: None,
+            max_index: None,
+            reserved_keys: ReservedKeys::default(),
+            depth_limit: None,
+            array_limit: None,
+            parameter_limit: None,
+            comma_lists: false,
+            conflicts: NestedConflicts::default(),
+            last_value_wins: false,
+            numeric_map_keys: false,
+            append_to_last_map: false,
+            php_names: false
+        }
+    }
+}
+
+impl NestedOptions {
+    /// Bracket syntax only, with keys at most 32 levels deep.
+    pub fn new() -> NestedOptions {
+        NestedOptions::default()
+    }
+
+    /// Also nest on dots: `a.b[c]=1` is `a[b][c]=1`.
+    pub fn dots(mut self, enabled: bool) -> NestedOptions {
+        self.dots = enabled;
+        self
+    }
+
+    /// Fail with `NestingTooDeep` when a key has more than `max` levels below
+    /// its base name, instead of 32.
+    pub fn max_depth(mut self, max: usize) -> NestedOptions {
+        self.max_depth = Some(max);
+        self
+    }
+
+    /// Fail with `TooManyNodes` when the tree would hold more than `max`
+    /// values, lists and maps.
+    pub fn max_nodes(mut self, max: usize) -> NestedOptions {
+        self.max_nodes = Some(max);
+        self
+    }
+
+    /// What to do with reserved names such as `__proto__`.
+    pub fn reserved_keys(mut self, policy: ReservedKeys) -> NestedOptions {
+        self.reserved_keys = policy;
+        self
+    }
+
+    /// Fail with `IndexTooLarge` when a list index is greater than `max`.
+    pub fn max_index(mut self, max: usize) -> NestedOptions {
+        self.max_index = Some(max);
+        self
+    }
+
+    /// Nest keys at most `max` levels deep and keep the levels below as one
+    /// literal name, so `a[b][c]=1` with a limit of 1 is `a` → `b` → `[c]`,
+    /// instead of failing like `max_depth` does.
+    pub fn depth_limit(mut self, max: usize) -> NestedOptions {
+        self.depth_limit = Some(max);
+        self
+    }
+
+    /// Treat list indices greater than `max` as map keys, so `a[100]=1` is a
+    /// map instead of a list, instead of failing like `max_index` does.
+    pub fn array_limit(mut self, max: usize) -> NestedOptions {
+        self.array_limit = Some(max);
+        self
+    }
+
+    /// Only parse the first `max` pairs and ignore the rest, instead of
+    /// failing like `ParseOptions::max_pairs` does.
+    pub fn parameter_limit(mut self, max: usize) -> NestedOptions {
+        self.parameter_limit = Some(max);
+        self
+    }
+
+    /// Split values on commas into lists: `a=1,2` is `a[]=1&a[]=2`. Encoded
+    /// commas, `%2C`, don't split.
+    pub fn comma_lists(mut self, enabled: bool) -> NestedOptions {
+        self.comma_lists = enabled;
+        self
+    }
+
+    /// What to do with names used in incompatible ways, such as `a[]=1&a[b]=2`.
+    pub fn conflicts(mut self, policy: NestedConflicts) -> NestedOptions {
+        self.conflicts = policy;
+        self
+    }
+
+    /// Let a plain value replace whatever its name held, so `a=1&a=2` is `2`,
+    /// and only build lists with brackets: `a=1&a[]=2` is then a conflict.
+    pub fn last_value_wins(mut self, enabled: bool) -> NestedOptions {
+        self.last_value_wins = enabled;
+        self
+    }
+
+    /// Read `a[0]` as the key `0` of a map rather than an index of a list.
+    pub fn numeric_map_keys(mut self, enabled: bool) -> NestedOptions {
+        self.numeric_map_keys = enabled;
+        self
+    }
+
+    /// Let `a[][b]` add `b` to the last map of the list `a` while that map
+    /// has no `b` yet, so `a[][b]=1&a[][c]=2&a[][b]=3` is two maps, the first
+    /// with both `b` and `c`.
+    pub fn append_to_last_map(mut self, enabled: bool) -> NestedOptions {
+        self.append_to_last_map = enabled;
+        self
+    }
+
+    /// Rewrite names the way PHP does before nesting them: leading spaces are
+    /// dropped, spaces and dots in the base name become `_`, a `[` without a
+    /// matching `]` becomes `_`, whatever follows the last bracket is dropped,
+    /// and pairs left without a name are ignored.
+    pub fn php_names(mut self, enabled: bool) -> NestedOptions {
+        self.php_names = enabled;
+        self
+    }
+}
+
+/// Parse urlencoded data into a nested tree.
+///
+/// `a[]` appends to a list, `a[2]` sets an element of a list and `a[b]` an
+/// entry of a map; keys that aren't well-formed, such as `a[b`, are kept as
+/// plain names. Repeated values and lists combine, so `a=1&a[]=2` is a list,
+/// but a name used as a map and as a list or value, as in `a[]=1&a[b]=2`, is a
+/// `NestedConflict` error.
+pub fn parse_nested(data: &str, options: &ParseOptions, nested: &NestedOptions)
+                    -> Result<NestedQueryMap, UrlDecodingError> {
+    if data.is_empty() {
+        return Err(UrlDecodingError::EmptyQuery);
+    }
+
+    let mut builder = Builder { options: nested, nodes: 0 };
+    let mut root = BTreeMap::new();
+
+    let mut pieces = split_pieces(data, options);
+    if let Some(max) = nested.parameter_limit {
+        pieces.truncate(max);
+    }
+    let pieces = if nested.comma_lists { split_commas(pieces) } else { pieces.iter().map(|piece| (*piece).to_owned()).collect() };
+
+    for (key, value) in pairs_of(pieces.iter().map(|piece| &**piece), options)? {
+        let key = match nested.php_names {
+            true => match php_name(&key) {
+                Some(key) => key,
+                None => continue
+            },
+            false => key
+        };
+        let (base, path) = split_key(&key, nested)?;
+        if nested.reserved_keys != ReservedKeys::Allow && is_reserved(&base, &path) {
+            match nested.reserved_keys {
+                ReservedKeys::Ignore => continue,
+                _ => return Err(UrlDecodingError::ReservedKey(key))
+            }
+        }
+        if let Some(max) = nested.max_depth {
+            if path.len() > max {
+                return Err(UrlDecodingError::NestingTooDeep(max));
+            }
+        }
+        let node = builder.insert(root.remove(&base), &base, &path, value)?;
+        root.insert(base, node);
+    }
+
+    Ok(root.into_iter().map(|(key, node)| (key, node.finish())).collect())
+}
+
+/// The name PHP registers a variable under, `None` when it has none.
+fn php_name(key: &str) -> Option<String> {
+    let key = key.trim_start_matches(' ');
+    let (base, rest) = match key.find('[') {
+        Some(position) => (&key[..position], &key[position..]),
+        None => (key, "")
+    };
+    let mut name: String = base.chars().map(|c| if c == ' ' || c == '.' { '_' } else { c }).collect();
+    if name.is_empty() {
+        return None;
+    }
+
+    if !rest.is_empty() {
+        if !rest.contains(']') {
+            name.push('_');
+            name.push_str(&rest[1..]);
+            return Some(name);
+        }
+        let mut rest = rest;
+        while let (true, Some(end)) = (rest.starts_with('['), rest.find(']')) {
+            name.push_str(&rest[..end + 1]);
+            rest = &rest[end + 1..];
+        }
+    }
+    Some(name)
+}
+
+/// Turn `a=1,2` into `a[]=1` and `a[]=2`, before decoding so `%2C` stays.
+fn split_commas(pieces: Vec<&str>) -> Vec<String> {
+    let mut split = Vec::new();
+    for piece in pieces {
+        match piece.find('=') {
+            Some(position) if piece[position + 1..].contains(',') => {
+                let key = &piece[..position];
+                let key = if key.ends_with("[]") || key.ends_with("%5B%5D") { key.to_owned() } else { format!("{}[]", key) };
+                split.extend(piece[position + 1..].split(',').map(|value| format!("{}={}", key, value)));
+            },
+            _ => split.push(piece.to_owned())
+        }
+    }
+    split
+}
+
+/// One level below the base name of a key.
+#[derive(Clone, Debug, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+    Append
+}
+
+/// Split `a[b][0][]` into `a` and its segments. Keys that aren't well-formed
+/// are a base name without segments.
+fn split_key(key: &str, options: &NestedOptions) -> Result<(String, Vec<Segment>), UrlDecodingError> {
+    let (head, mut rest) = match key.find('[') {
+        Some(position) if position > 0 => (&key[..position], &key[position..]),
+        _ => (key, "")
+    };
+
+    let mut names: Vec<&str> = if options.dots { head.split('.').collect() } else { vec![head] };
+    if names.iter().any(|name| name.is_empty()) {
+        names = vec![head];
+    }
+
+    let mut brackets = Vec::new();
+    while !rest.is_empty() {
+        match (rest.starts_with('['), rest.find(']')) {
+            (true, Some(end)) => {
+                brackets.push(&rest[1..end]);
+                rest = &rest[end + 1..];
+            },
+            _ => return Ok((key.to_owned(), Vec::new()))
+        }
+    }
+
+    let base = names[0].to_owned();
+    let mut path: Vec<Segment> = names[1..].iter().map(|name| Segment::Key((*name).to_owned())).collect();
+    for inner in brackets {
+        path.push(match inner {
+            "" => Segment::Append,
+            _ if !options.numeric_map_keys && inner.bytes().all(|b| b.is_ascii_digit()) => match inner.parse() {
+                Ok(index) => Segment::Index(index),
+                Err(_) => match options.max_index {
+                    Some(max) => return Err(index_too_large(&base, max)),
+                    None => Segment::Key(inner.to_owned())
+                }
+            },
+            _ => Segment::Key(inner.to_owned())
+        });
+    }
+
+    if let Some(max) = options.max_index {
+        let too_large = path.iter().any(|segment| matches!(*segment, Segment::Index(index) if index > max));
+        if too_large {
+            return Err(index_too_large(&base, max));
+        }
+    }
+    if let Some(max) = options.array_limit {
+        for segment in &mut path {
+            if let Segment::Index(index) = *segment {
+                if index > max {
+                    *segment = Segment::Key(index.to_string());
+                }
+            }
+        }
+    }
+    if let Some(max) = options.depth_limit {
+        if path.len() > max {
+            let literal: String = path[max..].iter().map(|segment| match *segment {
+                Segment::Key(ref key) => format!("[{}]", key),
+                Segment::Index(index) => format!("[{}]", index),
+                Segment::Append => "[]".to_owned()
+            }).collect();
+            path.truncate(max);
+            path.push(Segment::Key(literal));
+        }
+    }
+
+    Ok((base, path))
+}
+
+/// Whether the base name or a map key of a key is dangerous.
+fn is_reserved(base: &str, path: &[Segment]) -> bool {
+    let dangerous = |name: &str| RESERVED_NAMES.contains(&name) || name.contains('[') || name.contains(']');
+    dangerous(base) || path.iter().any(|segment| match *segment {
+        Segment::Key(ref key) => dangerous(key),
+        _ => false
+    })
+}
+
+fn index_too_large(key: &str, limit: usize) -> UrlDecodingError {
+    UrlDecodingError::IndexTooLarge { key: key.to_owned(), limit }
+}
+
+/// The tree while it is being built. Lists are keyed by index so sparse
+/// indices cost nothing.
+enum Node {
+    Leaf(String),
+    List(BTreeMap<usize, Node>),
+    Map(BTreeMap<String, Node>)
+}
+
+impl Node {
+    fn finish(self) -> NestedValue {
+        match self {
+            Node::Leaf(value) => NestedValue::Value(value),
+            Node::List(items) => NestedValue::List(items.into_values().map(Node::finish).collect()),
+            Node::Map(entries) =>
+                NestedValue::Map(entries.into_iter().map(|(key, node)| (key, node.finish())).collect())
+        }
+    }
+}
+
+struct Builder<'a> {
+    options: &'a NestedOptions,
+    nodes: usize
+}
+
+impl<'a> Builder<'a> {
+    /// Count a new node against the limit.
+    fn count(&mut self) -> Result<(), UrlDecodingError> {
+        self.nodes += 1;
+        match self.options.max_nodes {
+            Some(max) if self.nodes > max => Err(UrlDecodingError::TooManyNodes(max)),
+            _ => Ok(())
+        }
+    }
+
+    /// Store `value` at `path` below `existing`, the node of the key `name`,
+    /// returning the updated node.
+    fn insert(&mut self, existing: Option<Node>, name: &str, path: &[Segment], value: String)
+              -> Result<Node, UrlDecodingError> {
+        let segment = match path.first() {
+            Some(segment) => segment,
+            None => {
+                self.count()?;
+                let leaf = Node::Leaf(value);
+                return match existing {
+                    None => Ok(leaf),
+                    Some(_) if self.options.last_value_wins => Ok(leaf),
+                    Some(Node::Map(_)) if self.options.conflicts == NestedConflicts::Replace => Ok(leaf),
+                    Some(Node::Map(mut entries)) if self.options.conflicts == NestedConflicts::Merge => {
+                        if let Node::Leaf(value) = leaf {
+                            entries.insert(value, Node::Leaf("true".to_owned()));
+                        }
+                        Ok(Node::Map(entries))
+                    },
+                    Some(Node::Map(_)) => Err(conflict(name, NestedUsage::Map, NestedUsage::Value)),
+                    Some(Node::Leaf(first)) => {
+                        self.count()?;
+                        let mut items = BTreeMap::new();
+                        items.insert(0, Node::Leaf(first));
+                        items.insert(1, leaf);
+                        Ok(Node::List(items))
+                    },
+                    Some(Node::List(mut items)) => {
+                        let next = next_index(&items);
+                        items.insert(next, leaf);
+                        Ok(Node::List(items))
+                    }
+                };
+            }
+        };
+
+        match *segment {
+            Segment::Key(ref key) => {
+                let mut entries = match existing {
+                    Some(Node::Map(entries)) => entries,
+                    Some(Node::Leaf(_)) if self.options.conflicts == NestedConflicts::Replace =>
+                        return self.insert(None, name, path, value),
+                    Some(Node::Leaf(first)) if self.options.conflicts == NestedConflicts::Merge => {
+                        let map = self.insert(None, name, path, value)?;
+                        self.count()?;
+                        let mut items = BTreeMap::new();
+                        items.insert(0, Node::Leaf(first));
+                        items.insert(1, map);
+                        return Ok(Node::List(items));
+                    },
+                    Some(Node::List(items)) if self.options.conflicts != NestedConflicts::Fail =>
+                        items.into_iter().map(|(index, item)| (index.to_string(), item)).collect(),
+                    Some(Node::Leaf(_)) => return Err(conflict(name, NestedUsage::Value, NestedUsage::Map)),
+                    Some(Node::List(_)) => return Err(conflict(name, NestedUsage::List, NestedUsage::Map)),
+                    None => {
+                        self.count()?;
+                        BTreeMap::new()
+                    }
+                };
+                let child_name = format!("{}[{}]", name, key);
+                let child = self.insert(entries.remove(key), &child_name, &path[1..], value)?;
+                entries.insert(key.clone(), child);
+                Ok(Node::Map(entries))
+            },
+            Segment::Index(_) | Segment::Append => {
+                let mut items = match existing {
+                    Some(Node::List(items)) => items,
+                    Some(Node::Leaf(_)) if self.options.conflicts == NestedConflicts::Replace =>
+                        return self.insert(None, name, path, value),
+                    Some(Node::Leaf(_)) if self.options.last_value_wins =>
+                        return Err(conflict(name, NestedUsage::Value, NestedUsage::List)),
+                    Some(Node::Leaf(first)) => {
+                        self.count()?;
+                        let mut items = BTreeMap::new();
+                        items.insert(0, Node::Leaf(first));
+                        items
+                    },
+                    Some(Node::Map(entries)) if self.options.conflicts != NestedConflicts::Fail => {
+                        let key = match *segment {
+                            Segment::Index(index) => index.to_string(),
+                            _ if self.options.conflicts == NestedConflicts::Replace => entries.keys()
+                                .filter_map(|key| key.parse::<usize>().ok())
+                                .max()
+                                .map_or(0, |greatest| greatest + 1)
+                                .to_string(),
+                            _ => "0".to_owned()
+                        };
+                        let mut path = path.to_vec();
+                        path[0] = Segment::Key(key);
+                        return self.insert(Some(Node::Map(entries)), name, &path, value);
+                    },
+                    Some(Node::Map(_)) => return Err(conflict(name, NestedUsage::Map, NestedUsage::List)),
+                    None => {
+                        self.count()?;
+                        BTreeMap::new()
+                    }
+                };
+                let index = match *segment {
+                    Segment::Index(index) => index,
+                    _ if self.options.append_to_last_map && last_map_lacks(&items, &path[1..]) =>
+                        next_index(&items) - 1,
+                    _ => next_index(&items)
+                };
+                let child_name = format!("{}[{}]", name, index);
+                let child = self.insert(items.remove(&index), &child_name, &path[1..], value)?;
+                items.insert(index, child);
+                Ok(Node::List(items))
+            }
+        }
+    }
+}
+
+fn conflict(key: &str, first: NestedUsage, second: NestedUsage) -> UrlDecodingError {
+    UrlDecodingError::NestedConflict { key: key.to_owned(), first, second }
+}
+
+/// Whether the last element of `items` is a map that `path` leads nowhere in
+/// yet, so `a[]` followed by `path` can go on filling it.
+fn last_map_lacks(items: &BTreeMap<usize, Node>, path: &[Segment]) -> bool {
+    fn has_path(node: &Node, path: &[Segment]) -> bool {
+        match (node, path.first()) {
+            (_, None) => true,
+            (Node::Map(entries), Some(Segment::Key(key))) =>
+                entries.get(key).is_some_and(|child| has_path(child, &path[1..])),
+            (_, Some(Segment::Key(_))) => false,
+            (_, Some(_)) => true
+        }
+    }
+
+    matches!(path.first(), Some(&Segment::Key(_))) &&
+        matches!(items.values().next_back(), Some(last @ &Node::Map(_)) if !has_path(last, path))
+}
+
+/// The index `a[]` appends at.
+fn next_index(items: &BTreeMap<usize, Node>) -> usize {
+    items.keys().next_back().map_or(0, |last| last + 1)
+}
+
+#[test]
+fn test_parse_nested() {
+    use self::NestedValue::*;
+
+    let value = |v: &str| Value(v.to_owned());
+    let map = |entries: Vec<(&str, NestedValue)>| {
+        Map(entries.into_iter().map(|(k, v)| (k.to_owned(), v)).collect())
+    };
+    let parse = |data, nested| parse_nested(data, &ParseOptions::new(), &nested);
+
+    let parsed = parse("user[name]=Ann&user[address][city]=Paris&tags[]=a&tags[]=b&\
+                        ids[5]=x&ids[1]=y&plain=1&plain=2&odd[=1", NestedOptions::new()).unwrap();
+    assert_eq!(parsed["user"], map(vec![("name", value("Ann")),
+                                        ("address", map(vec![("city", value("Paris"))]))]));
+    assert_eq!(parsed["tags"], List(vec![value("a"), value("b")]));
+    assert_eq!(parsed["ids"], List(vec![value("y"), value("x")]));
+    assert_eq!(parsed["plain"], List(vec![value("1"), value("2")]));
+    assert_eq!(parsed["odd["], value("1"));
+
+    let dotted = parse("a.b[c]=1", NestedOptions::new().dots(true)).unwrap();
+    assert_eq!(dotted["a"], map(vec![("b", map(vec![("c", value("1"))]))]));
+
+    let deep = format!("a{}=1", "[]".repeat(5000));
+    assert!(matches!(parse(&*deep, NestedOptions::new()), Err(UrlDecodingError::NestingTooDeep(32))));
+    match parse("a[b][c][d]=1", NestedOptions::new().max_depth(2)) {
+        Err(UrlDecodingError::NestingTooDeep(2)) => (),
+        other => panic!("unexpected result: {:?}", other)
+    }
+    match parse("a[]=1&a[]=2&a[]=3", NestedOptions::new().max_nodes(3)) {
+        Err(UrlDecodingError::TooManyNodes(3)) => (),
+        other => panic!("unexpected result: {:?}", other)
+    }
+    match parse("a[999999999]=1", NestedOptions::new().max_index(20)) {
+        Err(UrlDecodingError::IndexTooLarge { ref key, limit: 20 }) if key == "a" => (),
+        other => panic!("unexpected result: {:?}", other)
+    }
+}
+
+#[test]
+fn test_nested_conflicts() {
+    let parse = |data| parse_nested(data, &ParseOptions::new(), &NestedOptions::new());
+
+    match parse("a[]=1&a[b]=2") {
+        Err(UrlDecodingError::NestedConflict { ref key, first: NestedUsage::List, second: NestedUsage::Map })
+            if key == "a" => (),
+        other => panic!("unexpected result: {:?}", other)
+    }
+    match parse("user[tags][x]=1&user[tags][]=2") {
+        Err(ref err @ UrlDecodingError::NestedConflict { .. }) =>
+            assert_eq!(err.to_string(), "`user[tags]` is used both as a map and as a list."),
+        other => panic!("unexpected result: {:?}", other)
+    }
+    match parse("a[b]=1&a=2") {
+        Err(UrlDecodingError::NestedConflict { first: NestedUsage::Map, second: NestedUsage::Value, .. }) => (),
+        other => panic!("unexpected result: {:?}", other)
+    }
+    assert!(parse("a=1&a[]=2&a[5]=3").is_ok());
+}
+
+#[test]
+fn test_reserved_keys() {
+    let data = "a[__proto__][admin]=1&b[c[d]]=2&user[name]=Ann";
+    let parse = |policy| parse_nested(data, &ParseOptions::new(), &NestedOptions::new().reserved_keys(policy));
+
+    assert_eq!(parse(ReservedKeys::Allow).unwrap().len(), 3);
+    assert_eq!(parse(ReservedKeys::Ignore).unwrap().keys().collect::<Vec<_>>(), vec!["user"]);
+    match parse(ReservedKeys::Reject) {
+        Err(UrlDecodingError::ReservedKey(ref key)) if key == "a[__proto__][admin]" => (),
+        other => panic!("unexpected result: {:?}", other)
+    }
+}
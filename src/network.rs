@@ -0,0 +1,110 @@
+//! IP address and CIDR range parameters.
+
+use std::fmt;
+use std::net::IpAddr;
+
+use super::UrlDecodingError;
+
+/// A range of IP addresses in CIDR notation, such as `10.0.0.0/8` or `2001:db8::/32`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct IpCidr {
+    address: IpAddr,
+    prefix: u8
+}
+
+impl IpCidr {
+    /// The address the range was written with, host bits included.
+    pub fn address(&self) -> IpAddr {
+        self.address
+    }
+
+    /// The number of leading bits shared by every address of the range.
+    pub fn prefix(&self) -> u8 {
+        self.prefix
+    }
+
+    /// Whether `address` is part of the range. Addresses of the other IP
+    /// version never are.
+    pub fn contains(&self, address: &IpAddr) -> bool {
+        match (self.address, *address) {
+            (IpAddr::V4(network), IpAddr::V4(address)) =>
+                masked(u128::from(u32::from(network)), self.prefix, 32) ==
+                    masked(u128::from(u32::from(address)), self.prefix, 32),
+            (IpAddr::V6(network), IpAddr::V6(address)) =>
+                masked(u128::from(network), self.prefix, 128) ==
+                    masked(u128::from(address), self.prefix, 128),
+            _ => false
+        }
+    }
+}
+
+impl fmt::Display for IpCidr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix)
+    }
+}
+
+/// Keep the `prefix` leading bits of a `bits`-wide address.
+fn masked(address: u128, prefix: u8, bits: u8) -> u128 {
+    if prefix == 0 {
+        0
+    } else {
+        address >> (bits - prefix)
+    }
+}
+
+/// Parse an IPv4 or IPv6 address.
+pub fn parse_ip(key: &str, value: &str) -> Result<IpAddr, UrlDecodingError> {
+    value.trim().parse().map_err(|_| UrlDecodingError::InvalidParameter {
+        key: key.to_owned(),
+        message: "is not a valid IP address".to_owned()
+    })
+}
+
+/// Parse a range in CIDR notation. The prefix length is required.
+pub fn parse_cidr(key: &str, value: &str) -> Result<IpCidr, UrlDecodingError> {
+    let invalid = |message: &str| UrlDecodingError::InvalidParameter {
+        key: key.to_owned(),
+        message: message.to_owned()
+    };
+
+    let value = value.trim();
+    let (address, prefix) = match value.find('/') {
+        Some(position) => (&value[..position], &value[position + 1..]),
+        None => return Err(invalid("is not a CIDR range: expected `address/prefix`"))
+    };
+
+    let address: IpAddr = address.parse()
+        .map_err(|_| invalid("is not a CIDR range: invalid IP address"))?;
+    let max_prefix = if address.is_ipv4() { 32 } else { 128 };
+    let digits = !prefix.is_empty() && prefix.bytes().all(|b| b.is_ascii_digit());
+    let prefix = match prefix.parse::<u8>() {
+        Ok(prefix) if digits && prefix <= max_prefix => prefix,
+        _ => return Err(invalid(&format!("is not a CIDR range: the prefix must be between 0 and {}",
+                                         max_prefix)))
+    };
+
+    Ok(IpCidr { address, prefix })
+}
+
+#[test]
+fn test_parse_ip_and_cidr() {
+    assert_eq!(parse_ip("addr", "192.168.0.1").unwrap().to_string(), "192.168.0.1");
+    assert!(parse_ip("addr", "::1").unwrap().is_ipv6());
+    assert!(parse_ip("addr", "300.1.1.1").is_err());
+
+    let range = parse_cidr("range", "10.1.0.0/16").unwrap();
+    assert_eq!(range.to_string(), "10.1.0.0/16");
+    assert!(range.contains(&"10.1.255.3".parse().unwrap()));
+    assert!(!range.contains(&"10.2.0.1".parse().unwrap()));
+    assert!(!range.contains(&"::1".parse().unwrap()));
+
+    let v6 = parse_cidr("range", "2001:db8::/32").unwrap();
+    assert!(v6.contains(&"2001:db8:1::1".parse().unwrap()));
+    assert!(parse_cidr("range", "0.0.0.0/0").unwrap().contains(&"8.8.8.8".parse().unwrap()));
+
+    assert!(parse_cidr("range", "10.0.0.0").is_err());
+    assert!(parse_cidr("range", "10.0.0.0/33").is_err());
+    assert!(parse_cidr("range", "10.0.0.0/+8").is_err());
+    assert!(parse_cidr("range", "::/129").is_err());
+}
@@ -0,0 +1,236 @@
+//! OAuth 1.0a (RFC 5849) request signing helpers.
+//!
+//! OAuth signs the union of the query parameters, the form body and the
+//! `Authorization: OAuth` header parameters, so collecting and normalizing
+//! them is squarely a parameter-parsing job.
+
+use iron::prelude::*;
+use iron::method::Method;
+
+use rustc_serialize::base64::FromBase64;
+
+use super::{QueryMap, UrlDecodingError, UrlEncodedBody, RawFormBody, ParseOptions,
+            parse_with, is_form, request_config};
+use super::canonical::{rfc3986_encode, canonical_query};
+use super::signing::{Signer, constant_time_eq};
+
+/// The parameters of an OAuth 1.0a signed request, along with what else goes
+/// into its signature base string.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OAuthRequest {
+    /// The request method.
+    pub method: Method,
+    /// The base string URI: scheme, host, non-default port and path, without
+    /// the query.
+    pub base_uri: String,
+    /// Every parameter of the request, from the query string, the form body
+    /// and the `Authorization` header, in no particular order.
+    pub params: Vec<(String, String)>
+}
+
+impl OAuthRequest {
+    /// Build an `OAuthRequest` from its parts.
+    pub fn new(method: Method, base_uri: String, params: Vec<(String, String)>) -> OAuthRequest {
+        OAuthRequest { method, base_uri, params }
+    }
+
+    /// Collect the parameters of an Iron request.
+    ///
+    /// The body is only included for `application/x-www-form-urlencoded`
+    /// requests, and the `realm` parameter of the header is left out, as the
+    /// specification requires.
+    ///
+    /// The parameters are decoded from the raw query string and `RawFormBody`,
+    /// as the client signed them: the transforms of the `UrlEncodedConfig`
+    /// and the rules of `RewriteQuery` don't apply. Spooled bodies aren't kept
+    /// as `RawFormBody`, so their requests fail with `InvalidParameter`.
+    pub fn from_request(req: &mut Request) -> Result<OAuthRequest, UrlDecodingError> {
+        let body = if is_form(req) {
+            // Reading the body keeps it as `RawFormBody`.
+            match req.get_ref::<UrlEncodedBody>() {
+                Ok(_) | Err(UrlDecodingError::EmptyQuery) => (),
+                Err(err) => return Err(err)
+            }
+            Some(req.extensions.get::<RawFormBody>().cloned().ok_or_else(|| UrlDecodingError::InvalidParameter {
+                key: "oauth_signature".to_owned(),
+                message: "can't be checked without the raw body, which spooled bodies don't keep".to_owned()
+            })?)
+        } else {
+            None
+        };
+
+        let header = req.headers.get_raw("Authorization")
+            .and_then(|lines| lines.first())
+            .and_then(|line| ::std::str::from_utf8(line).ok())
+            .map(|line| line.to_owned());
+        let params = collect_params(req.url.query.as_deref(), body.as_deref(), header.as_deref(),
+                                    request_config(req).options())?;
+
+        let url = &req.url;
+        let default_port = match &*url.scheme {
+            "http" => 80,
+            "https" => 443,
+            _ => 0
+        };
+        let port = if url.port == default_port { String::new() } else { format!(":{}", url.port) };
+        let base_uri = format!("{}://{}{}/{}", url.scheme.to_lowercase(),
+                               url.host.to_string().to_lowercase(), port, url.path.join("/"));
+
+        Ok(OAuthRequest::new(req.method.clone(), base_uri, params))
+    }
+
+    /// The first value of the parameter `key`.
+    pub fn param(&self, key: &str) -> Option<&str> {
+        self.params.iter().find(|pair| pair.0 == key).map(|pair| &*pair.1)
+    }
+
+    /// The signature base string of RFC 5849, section 3.4.1.
+    pub fn base_string(&self) -> String {
+        let normalized = canonical_query(self.params.iter()
+            .filter(|pair| pair.0 != "oauth_signature")
+            .map(|pair| (&*pair.0, &*pair.1)));
+
+        format!("{}&{}&{}", self.method.as_ref().to_uppercase(),
+                rfc3986_encode(&self.base_uri), rfc3986_encode(&normalized))
+    }
+
+    /// Check the `oauth_signature` parameter.
+    ///
+    /// `PLAINTEXT` signatures are checked directly. Every other method is
+    /// treated as an HMAC: `signer` signs the base string with the key made of
+    /// both secrets, and the result must match the base64 signature.
+    pub fn verify<S: Signer>(&self, signer: &S, consumer_secret: &str, token_secret: &str) -> bool {
+        let signature = match self.param("oauth_signature") {
+            Some(signature) => signature,
+            None => return false
+        };
+        let key = format!("{}&{}", rfc3986_encode(consumer_secret), rfc3986_encode(token_secret));
+
+        match self.param("oauth_signature_method") {
+            Some("PLAINTEXT") => constant_time_eq(signature.as_bytes(), key.as_bytes()),
+            _ => match signature.from_base64() {
+                Ok(expected) => {
+                    let actual = signer.sign(key.as_bytes(), self.base_string().as_bytes());
+                    constant_time_eq(&expected, &actual)
+                },
+                Err(_) => false
+            }
+        }
+    }
+}
+
+/// The parameters of the raw query string, form body and `Authorization`
+/// header of a request.
+fn collect_params(query: Option<&str>, body: Option<&str>, header: Option<&str>, options: &ParseOptions)
+                  -> Result<Vec<(String, String)>, UrlDecodingError> {
+    let mut params = Vec::new();
+    for data in query.iter().chain(body.iter()) {
+        flatten_into(parse_with(data, options), &mut params)?;
+    }
+    if let Some(header) = header {
+        params.extend(authorization_params(header)?);
+    }
+    Ok(params)
+}
+
+/// Add the pairs of a parse result to `params`, treating no data as no pairs.
+fn flatten_into(result: Result<QueryMap, UrlDecodingError>, params: &mut Vec<(String, String)>)
+                -> Result<(), UrlDecodingError> {
+    match result {
+        Ok(map) => {
+            params.extend(pairs_of(&map));
+            Ok(())
+        },
+        Err(UrlDecodingError::EmptyQuery) => Ok(()),
+        Err(err) => Err(err)
+    }
+}
+
+/// Every (key, value) pair of a `QueryMap`.
+fn pairs_of(map: &QueryMap) -> Vec<(String, String)> {
+    map.iter()
+        .flat_map(|(key, values)| values.iter().map(move |value| (key.clone(), value.clone())))
+        .collect()
+}
+
+/// The parameters of an `Authorization: OAuth ...` header, without `realm`.
+/// Other schemes have no OAuth parameters.
+fn authorization_params(header: &str) -> Result<Vec<(String, String)>, UrlDecodingError> {
+    let header = header.trim();
+    if header.len() < 6 || !header[..6].eq_ignore_ascii_case("oauth ") {
+        return Ok(Vec::new());
+    }
+
+    // OAuth header values are percent-encoded inside their quotes, so they can
+    // never contain a comma or a quote: decode them like unquoted values.
+    let options = ParseOptions::header().quoted_values(false);
+    let map = match parse_with(&header[6..], &options) {
+        Ok(map) => map,
+        Err(UrlDecodingError::EmptyQuery) => return Ok(Vec::new()),
+        Err(err) => return Err(err)
+    };
+
+    let mut params = Vec::new();
+    for (key, values) in map {
+        if key == "realm" {
+            continue;
+        }
+        for value in values {
+            let value = if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+                value[1..value.len() - 1].to_owned()
+            } else {
+                value
+            };
+            params.push((key.clone(), value));
+        }
+    }
+
+    Ok(params)
+}
+
+#[test]
+fn test_base_string() {
+    // The example of RFC 5849, section 3.4.1.1.
+    let mut params = authorization_params(
+        "OAuth realm=\"Example\", oauth_consumer_key=\"9djdj82h48djs9d2\", \
+         oauth_token=\"kkk9d7dh3k39sjv7\", oauth_signature_method=\"HMAC-SHA1\", \
+         oauth_timestamp=\"137131201\", oauth_nonce=\"7d8f3e4a\", \
+         oauth_signature=\"bYT5CMsGcbgUdFHObYMEfcx6bsw%3D\"").unwrap();
+    params.extend(pairs_of(&parse_with("b5=%3D%253D&a3=a&c%40=&a2=r%20b", &ParseOptions::new()).unwrap()));
+    params.extend(pairs_of(&parse_with("c2&a3=2+q", &ParseOptions::new()).unwrap()));
+
+    let request = OAuthRequest::new(Method::Post, "http://example.com/request".to_owned(), params);
+    assert_eq!(request.param("oauth_signature"), Some("bYT5CMsGcbgUdFHObYMEfcx6bsw="));
+    assert_eq!(request.base_string(),
+               "POST&http%3A%2F%2Fexample.com%2Frequest&a2%3Dr%2520b%26a3%3D2%2520q\
+                %26a3%3Da%26b5%3D%253D%25253D%26c%2540%3D%26c2%3D%26oauth_consumer_\
+                key%3D9djdj82h48djs9d2%26oauth_nonce%3D7d8f3e4a%26oauth_signature_m\
+                ethod%3DHMAC-SHA1%26oauth_timestamp%3D137131201%26oauth_token%3Dkkk\
+                9d7dh3k39sjv7");
+
+    let plaintext = OAuthRequest::new(Method::Get, "http://example.com/".to_owned(), vec![
+        ("oauth_signature_method".to_owned(), "PLAINTEXT".to_owned()),
+        ("oauth_signature".to_owned(), "consumer&token".to_owned())]);
+    assert!(plaintext.verify(&|_: &[u8], _: &[u8]| Vec::new(), "consumer", "token"));
+    assert!(!plaintext.verify(&|_: &[u8], _: &[u8]| Vec::new(), "consumer", "other"));
+
+    // Configured transforms change what handlers see, not what was signed.
+    use rustc_serialize::base64::{self, ToBase64};
+    use super::{Transform, UrlEncodedConfig};
+
+    let signer = super::signing::test_signer;
+    let uri = "https://example.com/search";
+    let query = "q=Hello%20World";
+    let header = "OAuth oauth_consumer_key=\"key\", oauth_signature_method=\"HMAC-SHA1\"";
+    let unsigned = collect_params(Some(query), None, Some(header), &ParseOptions::new()).unwrap();
+    let base_string = OAuthRequest::new(Method::Get, uri.to_owned(), unsigned).base_string();
+    let signature = signer(b"consumer&", base_string.as_bytes()).to_base64(base64::STANDARD);
+    let header = format!("{}, oauth_signature=\"{}\"", header, rfc3986_encode(&signature));
+
+    let config = UrlEncodedConfig::new().transform_all(Transform::Lowercase);
+    let params = collect_params(Some(query), None, Some(&header), config.options()).unwrap();
+    assert!(OAuthRequest::new(Method::Get, uri.to_owned(), params).verify(&signer, "consumer", ""));
+    let mut transformed = pairs_of(&config.parse(query).unwrap());
+    transformed.extend(authorization_params(&header).unwrap());
+    assert!(!OAuthRequest::new(Method::Get, uri.to_owned(), transformed).verify(&signer, "consumer", ""));
+}
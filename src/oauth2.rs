@@ -0,0 +1,118 @@
+//! The redirect an OAuth 2.0 or OpenID Connect provider sends back to the client.
+
+#[cfg(feature = "iron")]
+use iron::prelude::*;
+#[cfg(feature = "iron")]
+use iron::typemap::Key;
+
+#[cfg(feature = "iron")]
+use plugin;
+
+use super::{QueryMap, UrlDecodingError};
+#[cfg(feature = "iron")]
+use super::UrlEncodedQuery;
+use super::accessors::QueryMapExt;
+
+/// The parameters of an authorization callback (RFC 6749, section 4.1.2).
+///
+/// It is also a plugin for `Request` that extracts them from the query string:
+/// `req.get::<OAuthCallback>()`
+#[derive(Clone, Debug, PartialEq)]
+pub enum OAuthCallback {
+    /// The user granted access.
+    Authorized {
+        /// The authorization code to exchange for a token
+        code: String,
+        /// The `state` sent with the authorization request, if any
+        state: Option<String>
+    },
+    /// The provider refused or failed the authorization.
+    Denied {
+        /// The error code, such as `access_denied`
+        error: String,
+        /// A human-readable explanation, if any
+        error_description: Option<String>,
+        /// A page documenting the error, if any
+        error_uri: Option<String>,
+        /// The `state` sent with the authorization request, if any
+        state: Option<String>
+    }
+}
+
+impl OAuthCallback {
+    /// Read a callback from parsed query parameters.
+    ///
+    /// Exactly one of `code` and `error` must be present, and none of the
+    /// parameters may be repeated.
+    pub fn from_params(params: &QueryMap) -> Result<OAuthCallback, UrlDecodingError> {
+        let state = params.single("state")?.map(|state| state.to_owned());
+
+        match (params.single("code")?, params.single("error")?) {
+            (Some(code), None) => Ok(OAuthCallback::Authorized {
+                code: code.to_owned(),
+                state
+            }),
+            (None, Some(error)) => Ok(OAuthCallback::Denied {
+                error: error.to_owned(),
+                error_description: params.single("error_description")?.map(|d| d.to_owned()),
+                error_uri: params.single("error_uri")?.map(|uri| uri.to_owned()),
+                state
+            }),
+            (Some(_), Some(_)) => Err(UrlDecodingError::InvalidParameter {
+                key: "code".to_owned(),
+                message: "must not be sent along with `error`".to_owned()
+            }),
+            (None, None) => Err(UrlDecodingError::MissingParameter("code".to_owned()))
+        }
+    }
+
+    /// The `state` parameter, whatever the outcome.
+    pub fn state(&self) -> Option<&str> {
+        match *self {
+            OAuthCallback::Authorized { ref state, .. } |
+            OAuthCallback::Denied { ref state, .. } => state.as_ref().map(|state| &**state)
+        }
+    }
+}
+
+#[cfg(feature = "iron")]
+impl Key for OAuthCallback {
+    type Value = OAuthCallback;
+}
+
+#[cfg(feature = "iron")]
+impl<'a, 'b> plugin::Plugin<Request<'a, 'b>> for OAuthCallback {
+    type Error = UrlDecodingError;
+
+    fn eval(req: &mut Request) -> Result<OAuthCallback, UrlDecodingError> {
+        match req.get_ref::<UrlEncodedQuery>() {
+            Ok(params) => OAuthCallback::from_params(params),
+            Err(UrlDecodingError::EmptyQuery) =>
+                Err(UrlDecodingError::MissingParameter("code".to_owned())),
+            Err(err) => Err(err)
+        }
+    }
+}
+
+#[test]
+fn test_oauth_callback() {
+    use super::{parse_with, ParseOptions};
+    let parse = |query| OAuthCallback::from_params(&parse_with(query, &ParseOptions::new()).unwrap());
+
+    assert_eq!(parse("code=abc&state=xyz").unwrap(),
+               OAuthCallback::Authorized { code: "abc".to_owned(), state: Some("xyz".to_owned()) });
+
+    let denied = parse("error=access_denied&error_description=User+said+no").unwrap();
+    assert_eq!(denied.state(), None);
+    match denied {
+        OAuthCallback::Denied { ref error, ref error_description, .. } => {
+            assert_eq!(error, "access_denied");
+            assert_eq!(error_description.as_ref().unwrap(), "User said no");
+        },
+        _ => panic!("expected a denied callback")
+    }
+
+    assert!(parse("code=abc&error=access_denied").is_err());
+    assert!(parse("code=abc&code=def").is_err());
+    assert!(parse("state=xyz").is_err());
+}
@@ -0,0 +1,97 @@
+//! Keeping the input of a rejected form in the session, so the page the user
+//! is redirected to can fill the form in again.
+
+use iron::prelude::*;
+
+use super::{QueryMap, ParseOptions, parse_with};
+use super::encode::encode;
+use super::repopulate::FormValues;
+
+/// A session backend able to keep one string until the next request of the
+/// same session, implemented by the application for its session crate.
+pub trait OldInputStore {
+    /// Keep `data` in the session of `req`, replacing what was kept before.
+    fn stash(&self, req: &mut Request, data: String);
+
+    /// Remove and return what was kept in the session of `req`, if anything.
+    fn take(&self, req: &mut Request) -> Option<String>;
+}
+
+/// Saves the parameters of a rejected form into an `OldInputStore` and reads
+/// them back as `FormValues` on the next request (Post/Redirect/Get).
+///
+/// Sensitive fields are never stored: `password` and
+/// `password_confirmation` by default.
+///
+/// ```ignore
+/// let old_input = OldInput::new(MySessionStore).except(&["card_number"]);
+/// // In the POST handler, when validation fails:
+/// old_input.save(req, &params);
+/// // In the GET handler:
+/// let form = old_input.load(req);
+/// format!(r#"<input name="email" value="{}">"#, form.value("email"));
+/// ```
+pub struct OldInput<S: OldInputStore> {
+    store: S,
+    sensitive: Vec<String>
+}
+
+impl<S: OldInputStore> OldInput<S> {
+    /// Keep old input in `store`.
+    pub fn new(store: S) -> OldInput<S> {
+        OldInput { store, sensitive: vec!["password".to_owned(), "password_confirmation".to_owned()] }
+    }
+
+    /// Never store `fields` either.
+    pub fn except(mut self, fields: &[&str]) -> OldInput<S> {
+        self.sensitive.extend(fields.iter().map(|field| (*field).to_owned()));
+        self
+    }
+
+    /// The stored form of `params`, without the sensitive fields.
+    fn serialize(&self, params: &QueryMap) -> String {
+        let kept: QueryMap = params.iter()
+            .filter(|&(field, _)| !self.sensitive.contains(field))
+            .map(|(field, values)| (field.clone(), values.clone()))
+            .collect();
+        encode(&kept)
+    }
+
+    /// Keep `params` for the next request of the session.
+    pub fn save(&self, req: &mut Request, params: &QueryMap) {
+        let data = self.serialize(params);
+        self.store.stash(req, data);
+    }
+
+    /// The input kept by the previous request, forgetting it. Empty when
+    /// nothing was kept or it can't be read back.
+    pub fn load(&self, req: &mut Request) -> FormValues {
+        FormValues::new(&self.store.take(req).map(|data| restore(&data)).unwrap_or_default())
+    }
+}
+
+/// The parameters of stored input, none if it doesn't parse.
+fn restore(data: &str) -> QueryMap {
+    parse_with(data, &ParseOptions::new()).unwrap_or_default()
+}
+
+#[test]
+fn test_old_input() {
+    struct Nowhere;
+    impl OldInputStore for Nowhere {
+        fn stash(&self, _: &mut Request, _: String) {}
+        fn take(&self, _: &mut Request) -> Option<String> { None }
+    }
+
+    let old_input = OldInput::new(Nowhere).except(&["card_number"]);
+    let params = parse_with("email=ann%40example.com&password=hunter2&card_number=4111&tags=a&tags=b",
+                            &ParseOptions::new()).unwrap();
+    let data = old_input.serialize(&params);
+    assert_eq!(data, "email=ann%40example.com&tags=a&tags=b");
+
+    let form = FormValues::new(&restore(&data));
+    assert_eq!(form.value("email"), "ann@example.com");
+    assert_eq!(form.values("tags"), vec!["a".to_owned(), "b".to_owned()]);
+    assert_eq!(form.value("password"), "");
+    assert_eq!(restore(""), QueryMap::new());
+}
@@ -0,0 +1,60 @@
+//! Converting between a `QueryMap` and flat `(key, value)` pairs, and with
+//! the `multimap` feature, `multimap::MultiMap`.
+//!
+//! `QueryMap` and `MultiMap` both belong to other crates, so the conversions
+//! are functions rather than `From` implementations:
+//!
+//! ```ignore
+//! let multimap: MultiMap<String, String> = into_multimap(req.get::<UrlEncodedBody>()?);
+//! let params: QueryMap = from_multimap(multimap);
+//! ```
+
+#[cfg(feature = "multimap")]
+use multimap::MultiMap;
+
+use super::{QueryMap, combine_duplicates};
+
+/// Every value of `params` as a `(key, value)` pair, keys in order and the
+/// values of each key in the order they were sent.
+pub fn into_pairs(params: QueryMap) -> Vec<(String, String)> {
+    let mut keys: Vec<(String, Vec<String>)> = params.into_iter().collect();
+    keys.sort_by(|a, b| a.0.cmp(&b.0));
+    keys.into_iter()
+        .flat_map(|(key, values)| values.into_iter().map(move |value| (key.clone(), value)))
+        .collect()
+}
+
+/// Group `(key, value)` pairs into a `QueryMap`, keeping repeated keys.
+pub fn from_pairs<I: IntoIterator<Item = (String, String)>>(pairs: I) -> QueryMap {
+    combine_duplicates(pairs.into_iter().collect())
+}
+
+/// `params` as a `MultiMap`, every value of repeated keys kept in order.
+#[cfg(feature = "multimap")]
+pub fn into_multimap(params: QueryMap) -> MultiMap<String, String> {
+    params.into_iter().collect()
+}
+
+/// `multimap` as a `QueryMap`, every value of repeated keys kept in order.
+#[cfg(feature = "multimap")]
+pub fn from_multimap(multimap: MultiMap<String, String>) -> QueryMap {
+    multimap.into_iter().collect()
+}
+
+#[test]
+fn test_pairs() {
+    use super::{ParseOptions, parse_with};
+
+    let params = parse_with("b=2&a=1&b=3", &ParseOptions::new()).unwrap();
+    let pairs = into_pairs(params.clone());
+    assert_eq!(pairs, vec![("a".to_owned(), "1".to_owned()), ("b".to_owned(), "2".to_owned()),
+                           ("b".to_owned(), "3".to_owned())]);
+    assert_eq!(from_pairs(pairs), params);
+
+    #[cfg(feature = "multimap")]
+    {
+        let multimap = into_multimap(params.clone());
+        assert_eq!(multimap.get_vec("b"), Some(&vec!["2".to_owned(), "3".to_owned()]));
+        assert_eq!(from_multimap(multimap), params);
+    }
+}
@@ -0,0 +1,170 @@
+//! Chained extraction and validation of a single parameter.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use super::UrlDecodingError;
+
+/// Whether a `Param` may be absent, and so what `Param::finish` returns.
+pub trait Presence {
+    /// The result of a parameter holding a `T` when given.
+    type Output<T>;
+
+    /// The result for `value`, `None` when the parameter was absent.
+    fn output<T>(key: &str, value: Option<T>) -> Result<Self::Output<T>, UrlDecodingError>;
+}
+
+/// A parameter that may be absent: `finish` gives an `Option`.
+#[derive(Clone, Copy, Debug)]
+pub struct Optional;
+
+/// A parameter that must be given: `finish` fails with `MissingParameter`
+/// without one.
+#[derive(Clone, Copy, Debug)]
+pub struct Required;
+
+impl Presence for Optional {
+    type Output<T> = Option<T>;
+
+    fn output<T>(_: &str, value: Option<T>) -> Result<Option<T>, UrlDecodingError> {
+        Ok(value)
+    }
+}
+
+impl Presence for Required {
+    type Output<T> = T;
+
+    fn output<T>(key: &str, value: Option<T>) -> Result<T, UrlDecodingError> {
+        value.ok_or_else(|| UrlDecodingError::MissingParameter(key.to_owned()))
+    }
+}
+
+/// A parameter being converted and checked, built by `QueryMapExt::param`.
+///
+/// Each step applies to the value when there is one. The first step that
+/// fails gives its error to `finish`, naming the parameter, and the later
+/// steps are skipped.
+///
+/// ```ignore
+/// let age: u32 = params.param("age").required().as_u32().between(0, 150).finish()?;
+/// let sort: Option<&str> = params.param("sort").one_of(&["name", "date"]).finish()?;
+/// ```
+#[derive(Debug)]
+pub struct Param<'a, T, P: Presence = Optional> {
+    key: &'a str,
+    value: Result<Option<T>, UrlDecodingError>,
+    presence: PhantomData<P>
+}
+
+impl<'a> Param<'a, &'a str> {
+    /// The parameter `key` with this single value, an empty value counting
+    /// as absent.
+    pub fn new(key: &'a str, value: Result<Option<&'a str>, UrlDecodingError>) -> Param<'a, &'a str> {
+        Param { key, value: value.map(|value| value.filter(|value| !value.is_empty())), presence: PhantomData }
+    }
+}
+
+impl<'a, T, P: Presence> Param<'a, T, P> {
+    fn invalid(&self, message: String) -> UrlDecodingError {
+        UrlDecodingError::InvalidParameter { key: self.key.to_owned(), message }
+    }
+
+    /// Replace the value with what `convert` makes of it.
+    pub fn and_then<U, F>(self, convert: F) -> Param<'a, U, P>
+    where F: FnOnce(&str, T) -> Result<U, UrlDecodingError> {
+        let key = self.key;
+        let value = self.value.and_then(|value| value.map(|value| convert(key, value)).transpose());
+        Param { key, value, presence: PhantomData }
+    }
+
+    /// Require the parameter to be given.
+    pub fn required(self) -> Param<'a, T, Required> {
+        Param { key: self.key, value: self.value, presence: PhantomData }
+    }
+
+    /// Require `predicate` to hold, failing with `message`, phrased to follow
+    /// the key, otherwise.
+    pub fn check<F: FnOnce(&T) -> bool>(self, predicate: F, message: &str) -> Param<'a, T, P> {
+        let value = match self.value {
+            Ok(Some(ref value)) if !predicate(value) => Err(self.invalid(message.to_owned())),
+            value => value
+        };
+        Param { key: self.key, value, presence: PhantomData }
+    }
+
+    /// The value, or the first error of the chain.
+    pub fn finish(self) -> Result<P::Output<T>, UrlDecodingError> {
+        let key = self.key;
+        self.value.and_then(|value| P::output(key, value))
+    }
+}
+
+impl<'a, P: Presence> Param<'a, &'a str, P> {
+    /// Parse the value, described as `expected` in the error, such as
+    /// `a whole number`.
+    pub fn parse<U: FromStr>(self, expected: &str) -> Param<'a, U, P> {
+        self.and_then(|key, value| value.parse().map_err(|_| UrlDecodingError::InvalidParameter {
+            key: key.to_owned(),
+            message: format!("must be {}", expected)
+        }))
+    }
+
+    /// Parse the value as a non-negative whole number.
+    pub fn as_u32(self) -> Param<'a, u32, P> {
+        self.parse("a non-negative whole number")
+    }
+
+    /// Parse the value as a whole number.
+    pub fn as_i64(self) -> Param<'a, i64, P> {
+        self.parse("a whole number")
+    }
+
+    /// Parse the value as a number.
+    pub fn as_f64(self) -> Param<'a, f64, P> {
+        self.parse("a number")
+    }
+
+    /// Require between `min` and `max` characters.
+    pub fn length(self, min: usize, max: usize) -> Param<'a, &'a str, P> {
+        let message = format!("must be {} to {} characters long", min, max);
+        self.check(|value| (min..=max).contains(&value.chars().count()), &message)
+    }
+
+    /// Require one of `allowed`.
+    pub fn one_of(self, allowed: &[&str]) -> Param<'a, &'a str, P> {
+        let message = format!("must be one of: {}", allowed.join(", "));
+        self.check(|value| allowed.contains(value), &message)
+    }
+}
+
+impl<'a, T: PartialOrd + fmt::Display, P: Presence> Param<'a, T, P> {
+    /// Require a value from `min` to `max`, both included.
+    pub fn between(self, min: T, max: T) -> Param<'a, T, P> {
+        let message = format!("must be between {} and {}", min, max);
+        self.check(|value| min <= *value && *value <= max, &message)
+    }
+}
+
+#[test]
+fn test_param() {
+    use super::{parse_with, ParseOptions, QueryMapExt};
+
+    let params = parse_with("age=36&old=200&name=Ada&page=&sort=size&id=1&id=2", &ParseOptions::new()).unwrap();
+    let message = |error: UrlDecodingError| error.to_string();
+
+    assert_eq!(params.param("age").required().as_u32().between(0, 150).finish().unwrap(), 36);
+    assert_eq!(params.param("page").as_u32().finish().unwrap(), None);
+    assert_eq!(params.param("name").length(2, 20).finish().unwrap(), Some("Ada"));
+    assert_eq!(message(params.param("old").as_u32().between(0, 150).finish().unwrap_err()),
+               "`old` must be between 0 and 150.");
+    assert_eq!(message(params.param("name").as_u32().between(0, 150).finish().unwrap_err()),
+               "`name` must be a non-negative whole number.");
+    assert_eq!(message(params.param("sort").one_of(&["name", "date"]).finish().unwrap_err()),
+               "`sort` must be one of: name, date.");
+    assert!(matches!(params.param("page").required().finish(), Err(UrlDecodingError::MissingParameter(_))));
+    assert!(params.param("id").finish().is_err());
+
+    let doubled = params.param("age").as_i64().and_then(|_, age| Ok(age * 2)).check(|age| *age > 0, "must be positive");
+    assert_eq!(doubled.finish().unwrap(), Some(72));
+}
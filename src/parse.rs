@@ -0,0 +1,655 @@
+//! The core urlencoded parser and the limits it enforces.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use url::percent_encoding::lossy_utf8_percent_decode;
+
+use super::{QueryMap, QueryResult, UrlDecodingError, combine_duplicates};
+use super::budget::{Meter, ParseBudget};
+
+/// Limits and syntax rules applied while parsing urlencoded data.
+///
+/// The defaults parse standard `application/x-www-form-urlencoded` data and every
+/// limit is off. Lengths are measured in bytes, after decoding.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseOptions {
+    max_pairs: Option<usize>,
+    max_key_length: Option<usize>,
+    max_value_length: Option<usize>,
+    separator: char,
+    plus_as_space: bool,
+    quoted_values: bool,
+    trim_whitespace: bool,
+    single_keys: Vec<String>,
+    budget: ParseBudget
+}
+
+impl Default for ParseOptions {
+    fn default() -> ParseOptions {
+        ParseOptions {
+            max_pairs: None,
+            max_key_length: None,
+            max_value_length: None,
+            separator: '&',
+            plus_as_space: true,
+            quoted_values: false,
+            trim_whitespace: false,
+            single_keys: Vec::new(),
+            budget: ParseBudget::new()
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Options for standard form data, without any limits.
+    pub fn new() -> ParseOptions {
+        ParseOptions::default()
+    }
+
+    /// Options for header values made of `k=v, k2="v 2"` pair lists.
+    ///
+    /// Pairs are separated by commas and surrounding whitespace, values may
+    /// be quoted, and `+` is kept as is since header values are often base64.
+    pub fn header() -> ParseOptions {
+        ParseOptions::new()
+            .separator(',')
+            .plus_as_space(false)
+            .quoted_values(true)
+            .trim_whitespace(true)
+    }
+
+    /// Options for cookie-like strings: `a=1; b=2`.
+    ///
+    /// Pairs are separated by `;` and surrounding whitespace, `+` is kept as
+    /// is and values may be quoted. Malformed percent escapes are kept
+    /// literally, as they are with every other preset.
+    pub fn cookie() -> ParseOptions {
+        ParseOptions::new()
+            .separator(';')
+            .plus_as_space(false)
+            .quoted_values(true)
+            .trim_whitespace(true)
+    }
+
+    /// Split pairs on `separator` instead of `&`.
+    pub fn separator(mut self, separator: char) -> ParseOptions {
+        self.separator = separator;
+        self
+    }
+
+    /// Whether `+` decodes to a space. On by default, as in HTML forms.
+    pub fn plus_as_space(mut self, enabled: bool) -> ParseOptions {
+        self.plus_as_space = enabled;
+        self
+    }
+
+    /// Allow values wrapped in double quotes, which may then contain the
+    /// separator and backslash escapes. Quoted values are not percent-decoded.
+    pub fn quoted_values(mut self, enabled: bool) -> ParseOptions {
+        self.quoted_values = enabled;
+        self
+    }
+
+    /// Ignore whitespace around pairs, keys and values.
+    pub fn trim_whitespace(mut self, enabled: bool) -> ParseOptions {
+        self.trim_whitespace = enabled;
+        self
+    }
+
+    /// Fail with `ParameterPollution` when one of `keys` appears more than
+    /// once, instead of leaving it to handlers to pick one of the values.
+    ///
+    /// Use it for parameters such as `user_id` or `amount`, where a second
+    /// value smuggled past a proxy or a signature check is an attack.
+    pub fn single_only(mut self, keys: &[&str]) -> ParseOptions {
+        self.single_keys.extend(keys.iter().map(|key| (*key).to_owned()));
+        self
+    }
+
+    /// Fail with `TooManyPairs` when the input holds more than `max` pairs.
+    pub fn max_pairs(mut self, max: usize) -> ParseOptions {
+        self.max_pairs = Some(max);
+        self
+    }
+
+    /// Fail with `KeyTooLong` when a key is longer than `max` bytes.
+    pub fn max_key_length(mut self, max: usize) -> ParseOptions {
+        self.max_key_length = Some(max);
+        self
+    }
+
+    /// Fail with `ValueTooLong` when a value is longer than `max` bytes.
+    pub fn max_value_length(mut self, max: usize) -> ParseOptions {
+        self.max_value_length = Some(max);
+        self
+    }
+
+    /// Fail with `ParseBudgetExceeded` when parsing takes more of a resource
+    /// than `budget` allows, and report the forms coming close to it.
+    pub fn budget(mut self, budget: ParseBudget) -> ParseOptions {
+        self.budget = budget;
+        self
+    }
+}
+
+/// Parse a urlencoded string into a `QueryMap`, enforcing the given limits.
+///
+/// An empty string is an `EmptyQuery` error, just like in the plugins.
+pub fn parse_with(data: &str, options: &ParseOptions) -> QueryResult {
+    match data {
+        "" => Err(UrlDecodingError::EmptyQuery),
+        _ => {
+            let params = combine_duplicates(decode_pairs(data, options)?);
+            check_single(&params, options)?;
+            Ok(params)
+        }
+    }
+}
+
+/// Parse data arriving in pieces, such as a body read from a file one pair at
+/// a time, into the `QueryMap` `parse_with` would build from the pieces
+/// joined with the separator.
+pub fn parse_piecewise<I>(pieces: I, options: &ParseOptions) -> QueryResult
+where I: IntoIterator<Item = Result<String, UrlDecodingError>> {
+    let mut params = QueryMap::new();
+    let mut pairs = 0;
+    let mut empty = true;
+    let mut meter = Meter::new(&options.budget);
+
+    for (index, piece) in pieces.into_iter().enumerate() {
+        let piece = piece?;
+        empty = empty && index == 0 && piece.is_empty();
+        if let Some((key, value)) = parse_piece(&piece, options)? {
+            if let Some(max) = options.max_pairs {
+                if pairs == max {
+                    return Err(UrlDecodingError::TooManyPairs(max));
+                }
+            }
+            pairs += 1;
+            meter.charge(&key, &value)?;
+            params.entry(key).or_default().push(value);
+        }
+    }
+    meter.finish();
+
+    if empty {
+        return Err(UrlDecodingError::EmptyQuery);
+    }
+    check_single(&params, options)?;
+    Ok(params)
+}
+
+/// How `group_pairs` deals with a key sent more than once.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Grouping {
+    /// Keep every value, in order, as `parse_with` does.
+    All,
+    /// Keep the first value.
+    First,
+    /// Keep the last value.
+    Last,
+    /// Fail with `ParameterPollution`.
+    Unique
+}
+
+/// Group decoded pairs by key into a `QueryMap`, the second stage of
+/// `parse_with`.
+pub fn group_pairs(pairs: Vec<(String, String)>, grouping: Grouping) -> QueryResult {
+    if grouping == Grouping::All {
+        return Ok(combine_duplicates(pairs));
+    }
+
+    let mut params = QueryMap::new();
+    for (key, value) in pairs {
+        match params.get_mut(&key) {
+            None => { params.insert(key, vec![value]); },
+            Some(values) => match grouping {
+                Grouping::Last => values[0] = value,
+                Grouping::Unique => return Err(UrlDecodingError::ParameterPollution(key)),
+                _ => ()
+            }
+        }
+    }
+    Ok(params)
+}
+
+/// Fail with `ParameterPollution` when a single-only key of `options` was
+/// sent more than once.
+pub fn check_single(params: &QueryMap, options: &ParseOptions) -> Result<(), UrlDecodingError> {
+    for key in &options.single_keys {
+        if params.get(key).is_some_and(|values| values.len() > 1) {
+            return Err(UrlDecodingError::ParameterPollution(key.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// The separator as a byte, when splitting on it byte by byte gives the
+/// pieces `parse_with` would: when it is ASCII and can't be quoted.
+pub fn separator_byte(options: &ParseOptions) -> Option<u8> {
+    if options.separator.is_ascii() && !options.quoted_values {
+        Some(options.separator as u8)
+    } else {
+        None
+    }
+}
+
+/// Parse the parameters of a URL fragment, as relayed by OAuth implicit-flow
+/// callbacks: `#access_token=...&state=...`.
+///
+/// A leading `#` is ignored, so both `location.hash` and the bare fragment work.
+pub fn parse_fragment(fragment: &str, options: &ParseOptions) -> QueryResult {
+    parse_with(fragment.strip_prefix('#').unwrap_or(fragment), options)
+}
+
+/// Parse a header value holding urlencoded data or a `k=v, k2=v2` pair list,
+/// such as a webhook signature header, with `ParseOptions::header()`.
+///
+/// For other separators, such as the `;` between `Link` parameters, use
+/// `parse_with` and `ParseOptions::header().separator(';')`.
+pub fn parse_header_params(value: &str) -> QueryResult {
+    parse_with(value, &ParseOptions::header())
+}
+
+/// Parse a cookie-like string of `;`-separated pairs with `ParseOptions::cookie()`.
+pub fn parse_cookie_pairs(value: &str) -> QueryResult {
+    parse_with(value, &ParseOptions::cookie())
+}
+
+/// Split urlencoded data into decoded (key, value) pairs, in input order, the
+/// first stage of `parse_with`. The length and pair limits of `options`
+/// apply; `single_only` keys are checked when grouping.
+///
+/// ```ignore
+/// let pairs = decode_pairs("b=1&a=2&b=3", &ParseOptions::new())?;
+/// let params = group_pairs(pairs, Grouping::Last)?;
+/// ```
+pub fn decode_pairs(data: &str, options: &ParseOptions) -> Result<Vec<(String, String)>, UrlDecodingError> {
+    pairs_of(split_pieces(data, options), options)
+}
+
+/// A decoded pair along with where it was found, from `decode_spanned_pairs`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpannedPair {
+    /// The decoded key.
+    pub key: String,
+    /// The decoded value.
+    pub value: String,
+    /// The position of the pair among all pairs, from 0.
+    pub index: usize,
+    /// How many pairs with the same key came before it, so the third `id`
+    /// has an occurrence of 2.
+    pub occurrence: usize,
+    /// The byte range of the pair in the data, still encoded.
+    pub span: Range<usize>
+}
+
+impl SpannedPair {
+    /// The pair as it was written in `data`, the data it was decoded from.
+    pub fn raw<'a>(&self, data: &'a str) -> &'a str {
+        &data[self.span.clone()]
+    }
+}
+
+/// Like `decode_pairs`, but also tell where each pair was: its position,
+/// which occurrence of its key it is and its byte range in `data`, for error
+/// messages pointing at a precise value, signatures over raw pairs and
+/// tooling.
+pub fn decode_spanned_pairs(data: &str, options: &ParseOptions) -> Result<Vec<SpannedPair>, UrlDecodingError> {
+    let mut pairs: Vec<SpannedPair> = Vec::new();
+    let mut occurrences: HashMap<String, usize> = HashMap::new();
+    let mut meter = Meter::new(&options.budget);
+
+    for piece in split_pieces(data, options) {
+        let raw = if options.trim_whitespace { piece.trim() } else { piece };
+        if let Some((key, value)) = parse_piece(raw, options)? {
+            if let Some(max) = options.max_pairs {
+                if pairs.len() == max {
+                    return Err(UrlDecodingError::TooManyPairs(max));
+                }
+            }
+            meter.charge(&key, &value)?;
+            // Pieces are slices of `data`, so their offset is where they start.
+            let start = raw.as_ptr() as usize - data.as_ptr() as usize;
+            let occurrence = occurrences.entry(key.clone()).or_insert(0);
+            pairs.push(SpannedPair {
+                key,
+                value,
+                index: pairs.len(),
+                occurrence: *occurrence,
+                span: start..start + raw.len()
+            });
+            *occurrence += 1;
+        }
+    }
+    meter.finish();
+
+    Ok(pairs)
+}
+
+/// Decode pieces of data already split on the separator into pairs.
+pub fn pairs_of<'a, I>(pieces: I, options: &ParseOptions) -> Result<Vec<(String, String)>, UrlDecodingError>
+where I: IntoIterator<Item = &'a str> {
+    let mut pairs = Vec::new();
+    let mut meter = Meter::new(&options.budget);
+
+    for piece in pieces {
+        if let Some(pair) = parse_piece(piece, options)? {
+            if let Some(max) = options.max_pairs {
+                if pairs.len() == max {
+                    return Err(UrlDecodingError::TooManyPairs(max));
+                }
+            }
+            meter.charge(&pair.0, &pair.1)?;
+            pairs.push(pair);
+        }
+    }
+    meter.finish();
+
+    Ok(pairs)
+}
+
+/// Decode one `key=value` piece of the data, checking the length limits.
+/// `None` for empty pieces, which are skipped.
+fn parse_piece(piece: &str, options: &ParseOptions) -> Result<Option<(String, String)>, UrlDecodingError> {
+    let piece = if options.trim_whitespace { piece.trim() } else { piece };
+    if piece.is_empty() {
+        return Ok(None);
+    }
+
+    let (key, value) = match piece.find('=') {
+        Some(position) => (&piece[..position], &piece[position + 1..]),
+        None => (piece, "")
+    };
+    let (key, value) = if options.trim_whitespace {
+        (key.trim(), value.trim())
+    } else {
+        (key, value)
+    };
+    let key = decode(key, options);
+    let value = if options.quoted_values && is_quoted(value) {
+        unquote(value)
+    } else {
+        decode(value, options)
+    };
+
+    check_lengths(&key, &value, options)?;
+    Ok(Some((key, value)))
+}
+
+fn check_lengths(key: &str, value: &str, options: &ParseOptions) -> Result<(), UrlDecodingError> {
+    if let Some(max) = options.max_key_length {
+        if key.len() > max {
+            return Err(UrlDecodingError::KeyTooLong(max));
+        }
+    }
+    if let Some(max) = options.max_value_length {
+        if value.len() > max {
+            return Err(UrlDecodingError::ValueTooLong { key: key.to_owned(), limit: max });
+        }
+    }
+    Ok(())
+}
+
+/// A meter for the budget of `options`, for pairs decoded by another parser.
+#[cfg(feature = "iron")]
+pub fn meter(options: &ParseOptions) -> Meter {
+    Meter::new(&options.budget)
+}
+
+/// Check a pair decoded by another parser, such as a field of a multipart
+/// body, against the pair and length limits of `options`, `pairs` being the
+/// number of pairs before it.
+#[cfg(feature = "iron")]
+pub fn check_pair(key: &str, value: &str, pairs: usize, options: &ParseOptions) -> Result<(), UrlDecodingError> {
+    if let Some(max) = options.max_pairs {
+        if pairs == max {
+            return Err(UrlDecodingError::TooManyPairs(max));
+        }
+    }
+    check_lengths(key, value, options)
+}
+
+/// Split data on the separator, leaving separators inside quoted values alone
+/// when quoting is enabled.
+pub fn split_pieces<'a>(data: &'a str, options: &ParseOptions) -> Vec<&'a str> {
+    if !options.quoted_values {
+        return data.split(options.separator).collect();
+    }
+
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut escaped = false;
+
+    for (index, c) in data.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if in_quotes && c == '\\' {
+            escaped = true;
+        } else if c == '"' {
+            in_quotes = !in_quotes;
+        } else if !in_quotes && c == options.separator {
+            pieces.push(&data[start..index]);
+            start = index + c.len_utf8();
+        }
+    }
+    pieces.push(&data[start..]);
+
+    pieces
+}
+
+fn is_quoted(value: &str) -> bool {
+    value.len() >= 2 && value.starts_with('"') && value.ends_with('"')
+}
+
+/// Strip the quotes off a quoted value and resolve its backslash escapes.
+fn unquote(value: &str) -> String {
+    let mut unquoted = String::with_capacity(value.len());
+    let mut chars = value[1..value.len() - 1].chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            unquoted.extend(chars.next());
+        } else {
+            unquoted.push(c);
+        }
+    }
+
+    unquoted
+}
+
+/// Decode a single component: percent escapes, and `+` as a space if enabled.
+pub fn decode(component: &str, options: &ParseOptions) -> String {
+    if options.plus_as_space {
+        lossy_utf8_percent_decode(component.replace('+', " ").as_bytes())
+    } else {
+        lossy_utf8_percent_decode(component.as_bytes())
+    }
+}
+
+/// A parser fed urlencoded data in chunks, for proxies and transports
+/// receiving a body piece by piece, returning each pair as soon as it is
+/// complete.
+///
+/// Only the pair being received is buffered. With both `max_key_length` and
+/// `max_value_length` set, a pair growing past what they allow fails right
+/// away instead of being buffered to the end.
+///
+/// ```ignore
+/// let mut parser = FormParser::new(ParseOptions::new().max_pairs(100));
+/// for chunk in chunks {
+///     for (key, value) in parser.feed(&chunk)? {
+///         forward(key, value);
+///     }
+/// }
+/// let last = parser.finish()?;
+/// ```
+#[derive(Clone, Debug)]
+pub struct FormParser {
+    options: ParseOptions,
+    pending: Vec<u8>,
+    pairs: usize,
+    seen_single: Vec<String>,
+    meter: Meter
+}
+
+impl FormParser {
+    /// A parser enforcing the limits of `options`.
+    pub fn new(options: ParseOptions) -> FormParser {
+        let mut meter = Meter::new(&options.budget);
+        meter.pause();
+        FormParser { options, pending: Vec::new(), pairs: 0, seen_single: Vec::new(), meter }
+    }
+
+    /// Take in the next chunk of data and return the pairs it completed.
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<Vec<(String, String)>, UrlDecodingError> {
+        self.meter.resume();
+        let pairs = self.feed_chunk(chunk);
+        self.meter.pause();
+        pairs
+    }
+
+    fn feed_chunk(&mut self, chunk: &[u8]) -> Result<Vec<(String, String)>, UrlDecodingError> {
+        self.pending.extend_from_slice(chunk);
+        let separator = match separator_byte(&self.options) {
+            Some(separator) => separator,
+            // Quotes can hide separators: wait for the whole data.
+            None => return Ok(Vec::new())
+        };
+
+        let mut pairs = Vec::new();
+        while let Some(position) = self.pending.iter().position(|&byte| byte == separator) {
+            let piece: Vec<u8> = self.pending.drain(..position + 1).take(position).collect();
+            self.push_piece(&piece, &mut pairs)?;
+        }
+
+        if let (Some(key), Some(value)) = (self.options.max_key_length, self.options.max_value_length) {
+            // Escapes take at most three bytes per decoded byte.
+            if self.pending.len() > 3 * (key + value) + 1 {
+                parse_piece(&String::from_utf8_lossy(&self.pending), &self.options)?;
+            }
+        }
+        Ok(pairs)
+    }
+
+    /// Parse what is left once the data is complete, returning the last pairs.
+    pub fn finish(mut self) -> Result<Vec<(String, String)>, UrlDecodingError> {
+        self.meter.resume();
+        let pending = ::std::mem::take(&mut self.pending);
+        let mut pairs = Vec::new();
+        match separator_byte(&self.options) {
+            Some(_) => self.push_piece(&pending, &mut pairs)?,
+            None => for piece in split_pieces(&String::from_utf8_lossy(&pending), &self.options) {
+                self.push_piece(piece.as_bytes(), &mut pairs)?;
+            }
+        }
+        self.meter.finish();
+        Ok(pairs)
+    }
+
+    fn push_piece(&mut self, piece: &[u8], pairs: &mut Vec<(String, String)>) -> Result<(), UrlDecodingError> {
+        let (key, value) = match parse_piece(&String::from_utf8_lossy(piece), &self.options)? {
+            Some(pair) => pair,
+            None => return Ok(())
+        };
+
+        if let Some(max) = self.options.max_pairs {
+            if self.pairs == max {
+                return Err(UrlDecodingError::TooManyPairs(max));
+            }
+        }
+        if self.options.single_keys.contains(&key) {
+            if self.seen_single.contains(&key) {
+                return Err(UrlDecodingError::ParameterPollution(key));
+            }
+            self.seen_single.push(key.clone());
+        }
+
+        self.meter.charge(&key, &value)?;
+        self.pairs += 1;
+        pairs.push((key, value));
+        Ok(())
+    }
+}
+
+#[test]
+fn test_parse_with_limits() {
+    let data = "a=1&b=hello+world&a=%C3%A9";
+    let parsed = parse_with(data, &ParseOptions::new()).unwrap();
+    assert_eq!(parsed["a"], vec!["1".to_string(), "é".to_string()]);
+    assert_eq!(parsed["b"], vec!["hello world".to_string()]);
+
+    match parse_with(data, &ParseOptions::new().max_pairs(2)) {
+        Err(UrlDecodingError::TooManyPairs(2)) => (),
+        other => panic!("unexpected result: {:?}", other)
+    }
+    match parse_with(data, &ParseOptions::new().max_value_length(5)) {
+        Err(UrlDecodingError::ValueTooLong { ref key, limit: 5 }) if key == "b" => (),
+        other => panic!("unexpected result: {:?}", other)
+    }
+
+    match parse_with(data, &ParseOptions::new().single_only(&["b", "a"])) {
+        Err(UrlDecodingError::ParameterPollution(ref key)) if key == "a" => (),
+        other => panic!("unexpected result: {:?}", other)
+    }
+    assert!(parse_with(data, &ParseOptions::new().single_only(&["b"])).is_ok());
+
+    let pieces = data.split('&').map(|piece| Ok(piece.to_owned()));
+    assert_eq!(parse_piecewise(pieces, &ParseOptions::new()).unwrap(), parsed);
+    assert!(matches!(parse_piecewise(vec![Ok(String::new())], &ParseOptions::new()),
+                     Err(UrlDecodingError::EmptyQuery)));
+    let pieces = data.split('&').map(|piece| Ok(piece.to_owned()));
+    assert!(matches!(parse_piecewise(pieces, &ParseOptions::new().max_pairs(2)),
+                     Err(UrlDecodingError::TooManyPairs(2))));
+
+    let mut parser = FormParser::new(ParseOptions::new());
+    assert_eq!(parser.feed(b"a=1&b=hel").unwrap(), vec![("a".to_owned(), "1".to_owned())]);
+    assert_eq!(parser.feed(b"lo+world&a=%C3").unwrap(), vec![("b".to_owned(), "hello world".to_owned())]);
+    assert_eq!(parser.feed(b"%A9").unwrap(), vec![]);
+    assert_eq!(parser.finish().unwrap(), vec![("a".to_owned(), "é".to_owned())]);
+
+    let mut parser = FormParser::new(ParseOptions::new().max_key_length(2).max_value_length(2));
+    assert!(matches!(parser.feed(&[b'x'; 64]), Err(UrlDecodingError::KeyTooLong(2))));
+    let mut parser = FormParser::new(ParseOptions::new().single_only(&["a"]));
+    assert_eq!(parser.feed(data.as_bytes()).unwrap().len(), 2);
+    assert!(matches!(parser.finish(), Err(UrlDecodingError::ParameterPollution(_))));
+
+    let pairs = decode_pairs(data, &ParseOptions::new()).unwrap();
+    assert_eq!(pairs[2], ("a".to_owned(), "é".to_owned()));
+    assert_eq!(group_pairs(pairs.clone(), Grouping::All).unwrap(), parsed);
+    assert_eq!(group_pairs(pairs.clone(), Grouping::First).unwrap()["a"], vec!["1".to_owned()]);
+    assert_eq!(group_pairs(pairs.clone(), Grouping::Last).unwrap()["a"], vec!["é".to_owned()]);
+    assert!(matches!(group_pairs(pairs, Grouping::Unique), Err(UrlDecodingError::ParameterPollution(_))));
+
+    let spanned = decode_spanned_pairs(data, &ParseOptions::new()).unwrap();
+    assert_eq!((spanned[2].index, spanned[2].occurrence, spanned[2].raw(data)), (2, 1, "a=%C3%A9"));
+    assert_eq!(spanned[1].span, 4..17);
+    let spanned = decode_spanned_pairs(" a = 1 ;b=2", &ParseOptions::cookie()).unwrap();
+    assert_eq!(spanned[0].span, 1..6);
+
+    let fragment = parse_fragment("#access_token=abc&state=xyz", &ParseOptions::new()).unwrap();
+    assert_eq!(fragment["access_token"], vec!["abc".to_string()]);
+    assert_eq!(fragment["state"], vec!["xyz".to_string()]);
+}
+
+#[test]
+fn test_parse_header_params() {
+    let parsed = parse_header_params(r#"t=1492774577, v1=ab+c/d==, note="a, \"b\"""#).unwrap();
+    assert_eq!(parsed["t"], vec!["1492774577".to_string()]);
+    assert_eq!(parsed["v1"], vec!["ab+c/d==".to_string()]);
+    assert_eq!(parsed["note"], vec![r#"a, "b""#.to_string()]);
+
+    let link = parse_with(r#"rel="next"; title="Page 2""#, &ParseOptions::header().separator(';'));
+    assert_eq!(link.unwrap()["title"], vec!["Page 2".to_string()]);
+}
+
+#[test]
+fn test_parse_cookie_pairs() {
+    let parsed = parse_cookie_pairs("lang=en; theme=dark%20blue; ratio=50%; sum=1+1").unwrap();
+    assert_eq!(parsed["lang"], vec!["en".to_string()]);
+    assert_eq!(parsed["theme"], vec!["dark blue".to_string()]);
+    assert_eq!(parsed["ratio"], vec!["50%".to_string()]);
+    assert_eq!(parsed["sum"], vec!["1+1".to_string()]);
+}
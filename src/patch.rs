@@ -0,0 +1,80 @@
+//! Partial updates from forms, where leaving a field out and clearing it are
+//! two different requests.
+
+use std::str::FromStr;
+
+use super::{QueryMap, UrlDecodingError};
+
+/// A field of a `PATCH` form: left out, sent empty to clear it, or sent with
+/// a new value.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Patch<T> {
+    /// The field was not sent: keep the current value.
+    #[default]
+    Missing,
+    /// The field was sent empty: clear the current value.
+    Null,
+    /// The field was sent with a value: replace the current one.
+    Value(T)
+}
+
+impl<T> Patch<T> {
+    /// Whether the field was sent at all.
+    pub fn is_present(&self) -> bool {
+        !matches!(*self, Patch::Missing)
+    }
+
+    /// The change to make, `None` when there is none: `Some(None)` clears
+    /// the field.
+    pub fn into_change(self) -> Option<Option<T>> {
+        match self {
+            Patch::Missing => None,
+            Patch::Null => Some(None),
+            Patch::Value(value) => Some(Some(value))
+        }
+    }
+
+    /// Apply the change to an optional field of a record.
+    ///
+    /// ```ignore
+    /// params.get_patch::<String>("nickname")?.apply(&mut user.nickname);
+    /// ```
+    pub fn apply(self, field: &mut Option<T>) {
+        if let Some(change) = self.into_change() {
+            *field = change;
+        }
+    }
+}
+
+/// The change to `key` in `params`, which must not be sent more than once. A
+/// value of only whitespace counts as empty.
+pub fn parse_patch<T: FromStr>(params: &QueryMap, key: &str) -> Result<Patch<T>, UrlDecodingError> {
+    match params.get(key).map(|values| &values[..]) {
+        None | Some([]) => Ok(Patch::Missing),
+        Some([value]) if value.trim().is_empty() => Ok(Patch::Null),
+        Some([value]) => value.parse().map(Patch::Value).map_err(|_| UrlDecodingError::InvalidParameter {
+            key: key.to_owned(),
+            message: format!("has an invalid value `{}`", value)
+        }),
+        Some(_) => Err(UrlDecodingError::ParameterPollution(key.to_owned()))
+    }
+}
+
+#[test]
+fn test_parse_patch() {
+    use super::{parse_with, ParseOptions};
+
+    let params = parse_with("nickname=&age=37&bio=+&tag=a&tag=b", &ParseOptions::new()).unwrap();
+    assert_eq!(parse_patch::<String>(&params, "nickname").unwrap(), Patch::Null);
+    assert_eq!(parse_patch::<String>(&params, "bio").unwrap(), Patch::Null);
+    assert_eq!(parse_patch::<u32>(&params, "age").unwrap(), Patch::Value(37));
+    assert_eq!(parse_patch::<u32>(&params, "email").unwrap(), Patch::Missing);
+    assert!(parse_patch::<u32>(&params, "tag").is_err());
+
+    let mut nickname = Some("ada".to_owned());
+    parse_patch::<String>(&params, "email").unwrap().apply(&mut nickname);
+    assert_eq!(nickname, Some("ada".to_owned()));
+    parse_patch::<String>(&params, "nickname").unwrap().apply(&mut nickname);
+    assert_eq!(nickname, None);
+    assert!(!Patch::<u32>::Missing.is_present() && Patch::<u32>::Null.is_present());
+}
@@ -0,0 +1,77 @@
+//! Normalizing phone numbers typed into forms to E.164, such as
+//! `+14155550123`.
+
+use super::UrlDecodingError;
+
+/// The calling code of each region, by ISO 3166 code, and the trunk prefix
+/// dialed before national numbers there, which E.164 leaves out.
+const REGIONS: &[(&str, &str, &str)] = &[
+    ("AE", "971", "0"), ("AR", "54", "0"), ("AT", "43", "0"), ("AU", "61", "0"), ("BE", "32", "0"),
+    ("BR", "55", "0"), ("CA", "1", "1"), ("CH", "41", "0"), ("CL", "56", ""), ("CN", "86", "0"),
+    ("CO", "57", ""), ("CZ", "420", ""), ("DE", "49", "0"), ("DK", "45", ""), ("EG", "20", "0"),
+    ("ES", "34", ""), ("FI", "358", "0"), ("FR", "33", "0"), ("GB", "44", "0"), ("GR", "30", ""),
+    ("HK", "852", ""), ("HU", "36", "06"), ("ID", "62", "0"), ("IE", "353", "0"), ("IL", "972", "0"),
+    ("IN", "91", "0"), ("IT", "39", ""), ("JP", "81", "0"), ("KE", "254", "0"), ("KR", "82", "0"),
+    ("MX", "52", ""), ("MY", "60", "0"), ("NG", "234", "0"), ("NL", "31", "0"), ("NO", "47", ""),
+    ("NZ", "64", "0"), ("PE", "51", "0"), ("PH", "63", "0"), ("PK", "92", "0"), ("PL", "48", ""),
+    ("PT", "351", ""), ("RO", "40", "0"), ("RU", "7", "8"), ("SA", "966", "0"), ("SE", "46", "0"),
+    ("SG", "65", ""), ("TH", "66", "0"), ("TR", "90", "0"), ("TW", "886", "0"), ("UA", "380", "0"),
+    ("US", "1", "1"), ("VN", "84", "0"), ("ZA", "27", "0")
+];
+
+/// Normalize a phone number to E.164: `+`, the calling code and the national
+/// number, 8 to 15 digits in all.
+///
+/// Spaces, dots, dashes, slashes and parentheses are ignored. Numbers
+/// starting with `+` or `00` are international; others are national numbers
+/// of `default_region`, an ISO 3166 code such as `FR`, whose trunk prefix is
+/// dropped. Only the length and structure of the number are checked, not
+/// whether it is assigned.
+pub fn parse_phone(key: &str, value: &str, default_region: &str) -> Result<String, UrlDecodingError> {
+    let invalid = |message: &str| UrlDecodingError::InvalidParameter { key: key.to_owned(), message: message.to_owned() };
+
+    let trimmed = value.trim();
+    let (international, rest) = match trimmed.strip_prefix('+') {
+        Some(rest) => (true, rest),
+        None => (false, trimmed)
+    };
+    let mut digits = String::with_capacity(rest.len());
+    for c in rest.chars() {
+        match c {
+            '0'..='9' => digits.push(c),
+            ' ' | '.' | '-' | '/' | '(' | ')' | '\u{a0}' => (),
+            _ => return Err(invalid("is not a valid phone number"))
+        }
+    }
+
+    let number = if international {
+        digits
+    } else if let Some(number) = digits.strip_prefix("00") {
+        number.to_owned()
+    } else {
+        let region = default_region.to_ascii_uppercase();
+        let (_, code, trunk) = REGIONS.iter().find(|&&(name, _, _)| name == region)
+            .ok_or_else(|| invalid("must start with a country code"))?;
+        let national = if trunk.is_empty() { &digits[..] } else { digits.strip_prefix(trunk).unwrap_or(&digits) };
+        format!("{}{}", code, national)
+    };
+
+    if number.starts_with('0') || !(8..=15).contains(&number.len()) {
+        return Err(invalid("is not a valid phone number"));
+    }
+    Ok(format!("+{}", number))
+}
+
+#[test]
+fn test_parse_phone() {
+    assert_eq!(parse_phone("phone", "+1 (415) 555-0123", "FR").unwrap(), "+14155550123");
+    assert_eq!(parse_phone("phone", "06 12 34 56 78", "fr").unwrap(), "+33612345678");
+    assert_eq!(parse_phone("phone", "0044 20 7946 0958", "US").unwrap(), "+442079460958");
+    assert_eq!(parse_phone("phone", "06 1234 5678", "IT").unwrap(), "+390612345678");
+    assert_eq!(parse_phone("phone", "(415) 555-0123", "US").unwrap(), "+14155550123");
+
+    assert!(parse_phone("phone", "555-0123x4", "US").is_err());
+    assert!(parse_phone("phone", "12", "US").is_err());
+    assert!(parse_phone("phone", "+0 123 456 789", "US").is_err());
+    assert!(parse_phone("phone", "0612345678", "XX").is_err());
+}
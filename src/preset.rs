@@ -0,0 +1,213 @@
+//! Named bundles of options, such as the ones reproducing how other
+//! ecosystems parse query strings for apps migrating to Iron, which
+//! individual options can then override.
+
+use super::{ParseOptions, QueryResult, UrlDecodingError, parse_with};
+use super::nested::{NestedOptions, NestedConflicts, NestedQueryMap, parse_nested};
+
+/// A named set of parser options: `ParseOptions::preset(Preset::Rack)`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Preset {
+    /// Standard form data: `ParseOptions::new()` and `NestedOptions::new()`.
+    Form,
+    /// Header values made of `k=v, k2="v 2"` lists: `ParseOptions::header()`.
+    Header,
+    /// Cookie-like `a=1; b=2` strings: `ParseOptions::cookie()`.
+    Cookie,
+    /// The `qs` library of Node.js, with its default options: brackets nest
+    /// five levels deep, the levels below are kept as a literal name, list
+    /// indices above 20 are map keys, names used in incompatible ways are
+    /// merged, and pairs after the first 1000 are ignored.
+    ///
+    /// `qs`'s `allowDots` and `comma` options are `NestedOptions::dots` and
+    /// `NestedOptions::comma_lists`. Where `qs` sets a map key to `true`, the
+    /// tree holds the string `"true"`.
+    Qs,
+    /// Rack's `parse_nested_query`, as used by Rails: a plain value replaces
+    /// what its name held, lists are only built with `[]`, `a[0]` is a map
+    /// key, `a[][b]` fills the last map of a list until `b` repeats, names
+    /// used in incompatible ways fail with `NestedConflict` and keys nest at
+    /// most 100 levels.
+    ///
+    /// Rack caps the bytes of all keys together at 65536; the preset caps the
+    /// pairs at that number instead. Names without `=`, which Rack sets to
+    /// `nil`, are empty strings.
+    Rack,
+    /// PHP's `parse_str` and `$_GET`: names are rewritten as PHP does, so
+    /// `a.b` and `a b` are `a_b`, the last use of a name wins, `[]` appends
+    /// after the greatest index, pairs after the first 1000 are ignored and
+    /// keys nest at most 64 levels.
+    ///
+    /// PHP arrays are ordered maps: where one only has consecutive indices,
+    /// the tree holds a list, compacted in index order; PHP drops variables
+    /// nested too deeply, the preset fails with `NestingTooDeep`.
+    Php
+}
+
+impl Preset {
+    /// The options of the flat parser.
+    pub fn parse_options(self) -> ParseOptions {
+        match self {
+            Preset::Form | Preset::Qs | Preset::Php => ParseOptions::new(),
+            Preset::Header => ParseOptions::header(),
+            Preset::Cookie => ParseOptions::cookie(),
+            Preset::Rack => ParseOptions::new().max_pairs(65536)
+        }
+    }
+
+    /// The options of the nested parser.
+    pub fn nested_options(self) -> NestedOptions {
+        match self {
+            Preset::Form | Preset::Header | Preset::Cookie => NestedOptions::new(),
+            Preset::Qs => NestedOptions::new()
+                .depth_limit(5)
+                .array_limit(20)
+                .parameter_limit(1000)
+                .conflicts(NestedConflicts::Merge),
+            Preset::Rack => NestedOptions::new()
+                .max_depth(100)
+                .last_value_wins(true)
+                .numeric_map_keys(true)
+                .append_to_last_map(true),
+            Preset::Php => NestedOptions::new()
+                .max_depth(64)
+                .parameter_limit(1000)
+                .last_value_wins(true)
+                .conflicts(NestedConflicts::Replace)
+                .php_names(true)
+        }
+    }
+
+    /// Parse `data` into a nested tree with the options of the preset.
+    pub fn parse(self, data: &str) -> Result<NestedQueryMap, UrlDecodingError> {
+        parse_nested(data, &self.parse_options(), &self.nested_options())
+    }
+}
+
+/// The options of a `Preset`, with some of them overridden:
+///
+/// ```ignore
+/// let options = ParseOptions::preset(Preset::Rack)
+///     .with(|options| options.max_pairs(1000))
+///     .with_nested(|nested| nested.max_depth(8));
+/// let tree = options.parse(query)?;
+/// let config = UrlEncodedConfig::new().parse_options(options.parse_options().clone());
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct PresetOptions {
+    preset: Preset,
+    options: ParseOptions,
+    nested: NestedOptions
+}
+
+impl ParseOptions {
+    /// Start from the options of `preset`.
+    pub fn preset(preset: Preset) -> PresetOptions {
+        PresetOptions { preset, options: preset.parse_options(), nested: preset.nested_options() }
+    }
+}
+
+impl PresetOptions {
+    /// Override options of the flat parser, on top of the preset.
+    pub fn with<F: FnOnce(ParseOptions) -> ParseOptions>(mut self, f: F) -> PresetOptions {
+        self.options = f(self.options);
+        self
+    }
+
+    /// Override options of the nested parser, on top of the preset.
+    pub fn with_nested<F: FnOnce(NestedOptions) -> NestedOptions>(mut self, f: F) -> PresetOptions {
+        self.nested = f(self.nested);
+        self
+    }
+
+    /// The preset the options started from.
+    pub fn base(&self) -> Preset {
+        self.preset
+    }
+
+    /// The options of the flat parser.
+    pub fn parse_options(&self) -> &ParseOptions {
+        &self.options
+    }
+
+    /// The options of the nested parser.
+    pub fn nested_options(&self) -> &NestedOptions {
+        &self.nested
+    }
+
+    /// Parse `data` into a flat `QueryMap`.
+    pub fn parse_flat(&self, data: &str) -> QueryResult {
+        parse_with(data, &self.options)
+    }
+
+    /// Parse `data` into a nested tree.
+    pub fn parse(&self, data: &str) -> Result<NestedQueryMap, UrlDecodingError> {
+        parse_nested(data, &self.options, &self.nested)
+    }
+}
+
+#[test]
+fn test_qs_preset() {
+    use super::{encode_nested, NestedValue};
+
+    let qs = |data: &str| encode_nested(&Preset::Qs.parse(data).unwrap());
+
+    assert_eq!(qs("a[b][c][d][e][f][g][h]=i"), "a[b][c][d][e][f][%5Bg%5D%5Bh%5D]=i");
+    assert_eq!(qs("a[1]=b&a[15]=c"), "a[0]=b&a[1]=c");
+    assert_eq!(qs("a[b]=c&a=d"), "a[b]=c&a[d]=true");
+    assert_eq!(qs("a=b&a[c]=d"), "a[0]=b&a[1][c]=d");
+    assert_eq!(qs(&"x=1&".repeat(1500)).matches("x[").count(), 1000);
+
+    let is_map = |data: &str| matches!(Preset::Qs.parse(data).unwrap()["a"], NestedValue::Map(_));
+    assert!(is_map("a[100]=b"));
+    assert!(is_map("a[]=b&a[t]=f"));
+    assert!(!is_map("a[20]=b"));
+
+    let comma = Preset::Qs.nested_options().comma_lists(true);
+    let parsed = parse_nested("a=b,c&d=e%2Cf", &ParseOptions::new(), &comma).unwrap();
+    assert_eq!(encode_nested(&parsed), "a[0]=b&a[1]=c&d=e%2Cf");
+}
+
+#[test]
+fn test_rack_preset() {
+    use super::{encode_nested, NestedValue};
+
+    let rack = |data: &str| Preset::Rack.parse(data).map(|tree| encode_nested(&tree));
+
+    assert_eq!(rack("a=1&a=2").unwrap(), "a=2");
+    assert_eq!(rack("a[b]=1&a=2").unwrap(), "a=2");
+    assert_eq!(rack("a[]=1&a[]=2").unwrap(), "a[0]=1&a[1]=2");
+    assert_eq!(rack("a[][b]=1&a[][c]=2&a[][b]=3").unwrap(), "a[0][b]=1&a[0][c]=2&a[1][b]=3");
+    assert_eq!(rack("a[][b][c]=1&a[][b][d]=2").unwrap(), "a[0][b][c]=1&a[0][b][d]=2");
+    assert!(matches!(Preset::Rack.parse("a[01]=x").unwrap()["a"], NestedValue::Map(ref map) if map.contains_key("01")));
+    assert!(matches!(rack("a=1&a[b]=2"), Err(UrlDecodingError::NestedConflict { .. })));
+    assert!(matches!(rack("a=1&a[]=2"), Err(UrlDecodingError::NestedConflict { .. })));
+}
+
+#[test]
+fn test_php_preset() {
+    use super::{encode_nested, NestedValue};
+
+    let php = |data: &str| encode_nested(&Preset::Php.parse(data).unwrap());
+
+    assert_eq!(php("a.b=1&a%20c=2&%20d=3&e[f.g]=4"), "a_b=1&a_c=2&d=3&e[f.g]=4");
+    assert_eq!(php("a[b=1&c[d]x=2&=3&[e]=4"), "a_b=1&c[d]=2");
+    assert_eq!(php("a=1&a=2&b[]=1&b=2&c=1&c[]=2"), "a=2&b=2&c[0]=2");
+    assert_eq!(php("a[]=x&a[]=y"), "a[0]=x&a[1]=y");
+    assert_eq!(php("a[x]=1&a[]=2&a[5]=3&a[]=4"), "a[0]=2&a[5]=3&a[6]=4&a[x]=1");
+    assert!(matches!(Preset::Php.parse("a[x]=1&a[]=2").unwrap()["a"], NestedValue::Map(_)));
+}
+
+#[test]
+fn test_preset_overrides() {
+    let options = ParseOptions::preset(Preset::Rack).with(|options| options.max_pairs(2));
+    assert_eq!(options.base(), Preset::Rack);
+    assert!(options.parse("a=1&b=2").is_ok());
+    assert!(matches!(options.parse("a=1&b=2&c=3"), Err(UrlDecodingError::TooManyPairs(2))));
+    assert_eq!(options.nested_options(), &Preset::Rack.nested_options());
+
+    let options = ParseOptions::preset(Preset::Php).with_nested(|nested| nested.php_names(false));
+    assert!(options.parse("a.b=1").unwrap().contains_key("a.b"));
+    assert_eq!(ParseOptions::preset(Preset::Cookie).parse_options(), &ParseOptions::cookie());
+    assert_eq!(ParseOptions::preset(Preset::Header).parse_flat("a=1, b=2").unwrap()["b"], vec!["2".to_owned()]);
+}
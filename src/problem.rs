@@ -0,0 +1,164 @@
+//! RFC 7807 `application/problem+json` bodies for decoding and validation
+//! errors.
+
+use std::collections::BTreeMap;
+
+use iron::prelude::*;
+use iron::status::{self, Status};
+use iron::mime::{Mime, TopLevel, SubLevel};
+
+use rustc_serialize::json::{self, Json};
+
+use super::{UrlDecodingError, BudgetResource};
+use super::UrlDecodingError::*;
+use super::messages::MessageCatalog;
+
+/// The problem details of an error, as described by RFC 7807.
+///
+/// Besides the standard members, `code` holds the stable code of the error
+/// and `errors` lists the fields the problem is about, each with its own code
+/// and message, so clients can show them next to the corresponding inputs.
+///
+/// ```ignore
+/// match req.get::<SchemaForm<SignupForm>>() {
+///     Ok(form) => ...,
+///     Err(err) => Ok(Problem::new(&err, &English).into_response())
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Problem {
+    /// The HTTP status of the response.
+    pub status: Status,
+    /// A short summary of the kind of problem.
+    pub title: String,
+    /// The full message of the error.
+    pub detail: String,
+    /// The stable code of the error.
+    pub code: &'static str,
+    /// Every field involved, in order.
+    pub errors: Vec<ProblemField>
+}
+
+/// A field a `Problem` is about.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProblemField {
+    /// The name of the field.
+    pub field: String,
+    /// The stable code of what is wrong with it.
+    pub code: &'static str,
+    /// The message for it.
+    pub detail: String
+}
+
+impl Problem {
+    /// Describe `error` with the messages of `catalog`.
+    ///
+    /// Failed validations are `422 Unprocessable Entity`, uploads over the
+    /// limits `413 Payload Too Large`, files of a type that isn't accepted
+    /// `415 Unsupported Media Type` and bodies that took too long to arrive
+    /// `408 Request Timeout`; every other error is `400 Bad Request`.
+    pub fn new<C: MessageCatalog + ?Sized>(error: &UrlDecodingError, catalog: &C) -> Problem {
+        let (status, errors) = match *error {
+            ValidationFailed(ref errors) => (status::UnprocessableEntity, errors.iter()
+                .map(|error| ProblemField {
+                    field: error.field.clone(),
+                    code: error.code(),
+                    detail: catalog.field_error(error)
+                })
+                .collect()),
+            ValueTooLong { ref key, .. } | IndexTooLarge { ref key, .. } | NestedConflict { ref key, .. } |
+            ReservedKey(ref key) | ParameterPollution(ref key) | MissingParameter(ref key) |
+            InvalidParameter { ref key, .. } | UploadTooLarge { ref key, .. } |
+            UnsupportedFileType { ref key, .. } =>
+                (status_of(error), vec![ProblemField {
+                    field: key.clone(),
+                    code: error.code(),
+                    detail: catalog.error(error)
+                }]),
+            _ => (status_of(error), Vec::new())
+        };
+
+        Problem {
+            status,
+            title: catalog.problem_title(error),
+            detail: catalog.error(error),
+            code: error.code(),
+            errors
+        }
+    }
+
+    /// The JSON body. The problem `type` is `about:blank`, as the status and
+    /// title say all there is to say about it.
+    pub fn to_json(&self) -> String {
+        let mut object = BTreeMap::new();
+        object.insert("type".to_owned(), Json::String("about:blank".to_owned()));
+        object.insert("title".to_owned(), Json::String(self.title.clone()));
+        object.insert("status".to_owned(), Json::U64(self.status.to_u16() as u64));
+        object.insert("detail".to_owned(), Json::String(self.detail.clone()));
+        object.insert("code".to_owned(), Json::String(self.code.to_owned()));
+        if !self.errors.is_empty() {
+            object.insert("errors".to_owned(), Json::Array(self.errors.iter().map(|error| {
+                let mut entry = BTreeMap::new();
+                entry.insert("field".to_owned(), Json::String(error.field.clone()));
+                entry.insert("code".to_owned(), Json::String(error.code.to_owned()));
+                entry.insert("detail".to_owned(), Json::String(error.detail.clone()));
+                Json::Object(entry)
+            }).collect()));
+        }
+
+        json::encode(&Json::Object(object)).unwrap_or_default()
+    }
+
+    /// A response with the problem as its `application/problem+json` body.
+    pub fn into_response(self) -> Response {
+        let mime = Mime(TopLevel::Application, SubLevel::Ext("problem+json".to_owned()), vec![]);
+        Response::with((self.status, mime, self.to_json()))
+    }
+}
+
+/// The status of the errors that aren't validation failures.
+fn status_of(error: &UrlDecodingError) -> Status {
+    match *error {
+        // A form that is slow to parse isn't necessarily a large one.
+        ParseBudgetExceeded(BudgetResource::Time) => status::BadRequest,
+        UploadTooLarge { .. } | UploadsTooLarge(_) | TooManyFiles(_) | ParseBudgetExceeded(_) =>
+            status::PayloadTooLarge,
+        UnsupportedFileType { .. } => status::UnsupportedMediaType,
+        BodyReadTimeout => status::RequestTimeout,
+        MethodNotAllowedForBody(_) => status::MethodNotAllowed,
+        _ => status::BadRequest
+    }
+}
+
+#[test]
+fn test_problem_json() {
+    use super::English;
+    use super::schema::{FieldError, FieldErrorKind};
+
+    let error = ValidationFailed(vec![FieldError::new("email", FieldErrorKind::Required)]);
+    let problem = Problem::new(&error, &English);
+    assert_eq!(problem.status, status::UnprocessableEntity);
+    assert_eq!(problem.to_json(),
+               "{\"code\":\"VALIDATION_FAILED\",\"detail\":\"`email` is required.\",\
+                \"errors\":[{\"code\":\"FIELD_REQUIRED\",\"detail\":\"`email` is required\",\
+                \"field\":\"email\"}],\
+                \"status\":422,\"title\":\"The submitted form is invalid.\",\"type\":\"about:blank\"}");
+
+    let problem = Problem::new(&EmptyQuery, &English);
+    assert_eq!(problem.status, status::BadRequest);
+    assert!(problem.errors.is_empty());
+    assert_eq!(problem.title, "The request parameters are invalid.");
+    assert_eq!(problem.code, "EMPTY_QUERY");
+
+    let error = UnsupportedFileType { key: "avatar".to_owned(), content_type: "text/html".to_owned() };
+    let problem = Problem::new(&error, &English);
+    assert_eq!(problem.status, status::UnsupportedMediaType);
+    assert_eq!(problem.errors[0].field, "avatar");
+    assert_eq!(Problem::new(&TooManyFiles(3), &English).status, status::PayloadTooLarge);
+    assert_eq!(Problem::new(&BodyReadTimeout, &English).status, status::RequestTimeout);
+    assert_eq!(Problem::new(&MethodNotAllowedForBody(::iron::method::Get), &English).status,
+               status::MethodNotAllowed);
+    assert_eq!(Problem::new(&ParseBudgetExceeded(BudgetResource::Time), &English).status, status::BadRequest);
+    assert_eq!(Problem::new(&ParseBudgetExceeded(BudgetResource::DecodedBytes), &English).status,
+               status::PayloadTooLarge);
+}
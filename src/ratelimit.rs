@@ -0,0 +1,126 @@
+//! Keys for rate limiters, derived from the parameters a request is about.
+
+use std::sync::Arc;
+
+#[cfg(feature = "iron")]
+use iron::prelude::*;
+use rustc_serialize::hex::ToHex;
+
+use super::QueryMap;
+#[cfg(feature = "iron")]
+use super::form_params;
+use super::canonical::canonical_query;
+use super::signing::Signer;
+
+/// Derives a stable key from some parameters of a request, such as the
+/// `email` of a login form, for an external rate limiter to count attempts
+/// against.
+///
+/// The values are canonicalized first, so requests only differing in how the
+/// parameters were written fall in the same bucket: percent-encoding is
+/// decoded by the parser, so `%41` and `A` are equal, and values are trimmed
+/// and, optionally, lowercased.
+///
+/// Keys are FNV-1a hashes by default, which are stable across builds but easy
+/// to collide on purpose. Configure a signer to key them with a secret when
+/// clients could try to share a bucket with someone else.
+///
+/// ```ignore
+/// let login = RateLimitKey::new("login", &["email"]).lowercase(true);
+/// if let Some(key) = login.from_request(req) {
+///     limiter.check(&key)?;
+/// }
+/// ```
+#[derive(Clone)]
+pub struct RateLimitKey {
+    scope: String,
+    params: Vec<String>,
+    lowercase: bool,
+    signing: Option<(Arc<dyn Signer + Send + Sync>, Vec<u8>)>
+}
+
+impl RateLimitKey {
+    /// Keys from the values of `params`, prefixed with `scope` so keys of
+    /// different endpoints don't mix.
+    pub fn new(scope: &str, params: &[&str]) -> RateLimitKey {
+        RateLimitKey {
+            scope: scope.to_owned(),
+            params: params.iter().map(|param| (*param).to_owned()).collect(),
+            lowercase: false,
+            signing: None
+        }
+    }
+
+    /// Whether to lowercase values, for case-insensitive ones such as emails or
+    /// usernames.
+    pub fn lowercase(mut self, enabled: bool) -> RateLimitKey {
+        self.lowercase = enabled;
+        self
+    }
+
+    /// Hash with `signer` and `key` instead of FNV-1a.
+    pub fn signed<S>(mut self, signer: S, key: &[u8]) -> RateLimitKey
+    where S: Signer + Send + Sync + 'static {
+        self.signing = Some((Arc::new(signer), key.to_vec()));
+        self
+    }
+
+    /// The key of `params`, as `<scope>:<hex hash>`, or `None` when none of the
+    /// configured parameters has a value.
+    pub fn derive(&self, params: &QueryMap) -> Option<String> {
+        let canonical: Vec<(&str, String)> = self.params.iter()
+            .flat_map(|param| params.get(param).into_iter().flatten().map(move |value| (&**param, value)))
+            .map(|(param, value)| {
+                let value = value.trim();
+                (param, if self.lowercase { value.to_lowercase() } else { value.to_owned() })
+            })
+            .filter(|(_, value)| !value.is_empty())
+            .collect();
+        if canonical.is_empty() {
+            return None;
+        }
+
+        let message = canonical_query(canonical.iter().map(|(param, value)| (*param, &**value)));
+        let hash = match self.signing {
+            Some((ref signer, ref key)) => signer.sign(key, message.as_bytes()).to_hex(),
+            None => format!("{:016x}", fnv1a(message.as_bytes()))
+        };
+        Some(format!("{}:{}", self.scope, hash))
+    }
+
+    /// The key of the submitted form: the body of
+    /// `application/x-www-form-urlencoded` requests, the query string
+    /// otherwise. Requests whose parameters don't parse have no key.
+    #[cfg(feature = "iron")]
+    pub fn from_request(&self, req: &mut Request) -> Option<String> {
+        form_params(req).ok().and_then(|params| self.derive(&params))
+    }
+}
+
+/// The 64-bit FNV-1a hash of `bytes`.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[test]
+fn test_rate_limit_key() {
+    use super::{parse_with, ParseOptions};
+
+    let parse = |data: &str| parse_with(data, &ParseOptions::new()).unwrap();
+    let login = RateLimitKey::new("login", &["email"]).lowercase(true);
+
+    let key = login.derive(&parse("email=ann%40example.com&password=a")).unwrap();
+    assert!(key.starts_with("login:"));
+    assert_eq!(login.derive(&parse("email=+Ann@Example.com+&password=b")), Some(key.clone()));
+    assert_eq!(login.derive(&parse("email=%41nn%40example.com")), Some(key.clone()));
+    assert!(login.derive(&parse("email=bob@example.com")) != Some(key.clone()));
+    assert_eq!(login.derive(&parse("email=&password=a")), None);
+
+    assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+    assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+
+    let signed = login.signed(|key: &[u8], message: &[u8]| [key, message].concat(), b"k");
+    assert_eq!(signed.derive(&parse("email=A")), Some(format!("login:{}", b"kemail=a".to_hex())));
+}
@@ -0,0 +1,115 @@
+//! Parsing bodies read from any source, for servers and proxies that don't go
+//! through Iron.
+
+use std::io::{self, BufRead, Read};
+
+use bodyparser::{BodyError, BodyErrorCause};
+
+use super::{QueryResult, UrlDecodingError, UrlEncodedConfig};
+use super::timeout::is_timeout;
+use super::parse::separator_byte;
+
+/// Read a body of `content_length` bytes from `body` and parse it with
+/// `config`, as `UrlEncodedBody` does for Iron requests.
+///
+/// Bodies announcing more than `limit` bytes are refused before anything is
+/// read, and bodies ending before `content_length` bytes are read fail.
+/// Bytes after `content_length` are left in `body`, so keep-alive connections
+/// can go on with the next request. Wrap `body` in a `TimedReader` to bound
+/// how long reading may take.
+///
+/// ```ignore
+/// let params = parse_reader(&mut stream, content_length, 64 * 1024, &config)?;
+/// ```
+pub fn parse_reader<R: Read>(body: R, content_length: u64, limit: usize, config: &UrlEncodedConfig)
+                             -> QueryResult {
+    if content_length > limit as u64 {
+        return Err(io_error(io::ErrorKind::InvalidInput, "Body is too big"));
+    }
+
+    let mut bytes = Vec::with_capacity(content_length as usize);
+    body.take(content_length).read_to_end(&mut bytes).map_err(|err| read_error("Can't read request body", err))?;
+    if (bytes.len() as u64) < content_length {
+        return Err(io_error(io::ErrorKind::UnexpectedEof, "Body is shorter than its Content-Length"));
+    }
+
+    let body = String::from_utf8(bytes).map_err(|err| UrlDecodingError::BodyError(BodyError {
+        detail: "Invalid UTF-8 sequence".to_owned(),
+        cause: BodyErrorCause::Utf8Error(err.utf8_error())
+    }))?;
+    config.parse(&body)
+}
+
+/// Parse a body of any length with `config`, reading one pair at a time, so
+/// that only the resulting `QueryMap` and the pair being decoded are held in
+/// memory.
+///
+/// Options with quoted values or a non-ASCII separator can't be split that
+/// way: the whole body is read first.
+pub fn parse_stream<R: BufRead>(mut body: R, config: &UrlEncodedConfig) -> QueryResult {
+    let separator = match separator_byte(config.options()) {
+        Some(separator) => separator,
+        None => {
+            let mut data = String::new();
+            body.read_to_string(&mut data).map_err(|err| read_error("Can't read request body", err))?;
+            return config.parse(&data);
+        }
+    };
+
+    let pieces = body.split(separator).map(|piece| {
+        let piece = piece.map_err(|err| read_error("Can't read request body", err))?;
+        String::from_utf8(piece).map_err(|err| UrlDecodingError::BodyError(BodyError {
+            detail: "Invalid UTF-8 sequence".to_owned(),
+            cause: BodyErrorCause::Utf8Error(err.utf8_error())
+        }))
+    });
+    // `split` yields nothing at all for empty data, where `parse_with` sees one
+    // empty piece.
+    let mut pieces = pieces.peekable();
+    if pieces.peek().is_none() {
+        return config.parse("");
+    }
+    config.parse_pieces(pieces)
+}
+
+/// A body that couldn't be read, or `BodyReadTimeout` if it took too long.
+pub fn read_error(detail: &str, err: io::Error) -> UrlDecodingError {
+    if is_timeout(&err) {
+        return UrlDecodingError::BodyReadTimeout;
+    }
+    UrlDecodingError::BodyError(BodyError { detail: detail.to_owned(), cause: BodyErrorCause::IoError(err) })
+}
+
+fn io_error(kind: io::ErrorKind, detail: &str) -> UrlDecodingError {
+    UrlDecodingError::BodyError(BodyError {
+        detail: detail.to_owned(),
+        cause: BodyErrorCause::IoError(io::Error::new(kind, detail))
+    })
+}
+
+#[test]
+fn test_parse_reader() {
+    use super::ParseOptions;
+
+    let config = UrlEncodedConfig::new();
+    let mut stream = io::Cursor::new("a=1&b=2GET / HTTP/1.1");
+    let params = parse_reader(&mut stream, 7, 1024, &config).unwrap();
+    assert_eq!(params["b"], vec!["2".to_owned()]);
+    assert_eq!(stream.position(), 7);
+
+    assert!(parse_reader(io::Cursor::new("a=1"), 7, 1024, &config).is_err());
+    assert!(parse_reader(io::Cursor::new("a=1&b=2"), 7, 4, &config).is_err());
+    assert!(parse_reader(&b"a=\xff"[..], 3, 1024, &config).is_err());
+    assert!(matches!(parse_reader(io::empty(), 0, 1024, &config), Err(UrlDecodingError::EmptyQuery)));
+
+    let strict = UrlEncodedConfig::new().parse_options(ParseOptions::new().max_pairs(1));
+    assert!(matches!(parse_reader(io::Cursor::new("a=1&b=2"), 7, 1024, &strict),
+                     Err(UrlDecodingError::TooManyPairs(1))));
+
+    let params = parse_stream(io::Cursor::new("a=1&&b=2&a=3"), &config).unwrap();
+    assert_eq!(params["a"], vec!["1".to_owned(), "3".to_owned()]);
+    assert_eq!(params, config.parse("a=1&&b=2&a=3").unwrap());
+    assert!(matches!(parse_stream(io::empty(), &config), Err(UrlDecodingError::EmptyQuery)));
+    assert!(matches!(parse_stream(&b"a=\xff"[..], &config), Err(UrlDecodingError::BodyError(_))));
+    assert!(matches!(parse_stream(io::Cursor::new("a=1&b=2"), &strict), Err(UrlDecodingError::TooManyPairs(1))));
+}
@@ -0,0 +1,51 @@
+//! Query parameters of the page the client came from.
+
+use iron::prelude::*;
+use iron::headers::Referer;
+use iron::typemap::Key;
+
+use plugin;
+
+use super::{QueryMap, QueryResult, UrlDecodingError, request_config};
+
+/// Plugin for `Request` that extracts URL encoded data from the query string of
+/// the URL in the `Referer` header.
+///
+/// It parses with the same options and transforms as `UrlEncodedQuery`, and a
+/// missing header or a referer without a query is an `EmptyQuery` error.
+///
+/// Use it like this: `req.get_ref::<RefererQuery>()`
+pub struct RefererQuery;
+
+impl Key for RefererQuery {
+    type Value = QueryMap;
+}
+
+impl<'a, 'b> plugin::Plugin<Request<'a, 'b>> for RefererQuery {
+    type Error = UrlDecodingError;
+
+    fn eval(req: &mut Request) -> QueryResult {
+        let config = request_config(req);
+        match req.headers.get::<Referer>() {
+            Some(referer) => config.parse(query_of(referer)),
+            None => Err(UrlDecodingError::EmptyQuery)
+        }
+    }
+}
+
+/// The query string of a URL, without the fragment. Empty if there is none.
+fn query_of(url: &str) -> &str {
+    let without_fragment = url.split('#').next().unwrap_or("");
+    match without_fragment.find('?') {
+        Some(position) => &without_fragment[position + 1..],
+        None => ""
+    }
+}
+
+#[test]
+fn test_query_of() {
+    assert_eq!(query_of("http://example.com/search?q=iron&page=2#results"), "q=iron&page=2");
+    assert_eq!(query_of("/search?q=iron"), "q=iron");
+    assert_eq!(query_of("http://example.com/#a?b"), "");
+    assert_eq!(query_of("http://example.com/"), "");
+}
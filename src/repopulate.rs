@@ -0,0 +1,159 @@
+//! Re-rendering a rejected form with what the user typed.
+
+use super::QueryMap;
+use super::accessors::QueryMapExt;
+
+/// Escape text for use inside an HTML attribute value or element content.
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#x27;"),
+            _ => escaped.push(c)
+        }
+    }
+
+    escaped
+}
+
+/// Escape text for use inside HTML element content only, where quotes are
+/// harmless and left as they are.
+pub fn escape_html_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            _ => escaped.push(c)
+        }
+    }
+
+    escaped
+}
+
+/// A view over parameters giving their values escaped for the HTML context
+/// they are written into, from `QueryMapExt::escaped`.
+///
+/// ```ignore
+/// let html = params.escaped();
+/// format!(r#"<input name="q" value="{}"> Results for {}"#, html.attr("q"), html.text("q"));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Escaped<'a> {
+    params: &'a QueryMap
+}
+
+impl<'a> Escaped<'a> {
+    /// A view over `params`.
+    pub fn new(params: &'a QueryMap) -> Escaped<'a> {
+        Escaped { params }
+    }
+
+    /// The first value of `key` for element content, or an empty string.
+    pub fn text(&self, key: &str) -> String {
+        self.params.first(key).map(escape_html_text).unwrap_or_default()
+    }
+
+    /// The first value of `key` for a quoted attribute value, or an empty
+    /// string.
+    pub fn attr(&self, key: &str) -> String {
+        self.params.first(key).map(escape_html).unwrap_or_default()
+    }
+
+    /// Every value of `key` for element content.
+    pub fn texts(&self, key: &str) -> Vec<String> {
+        self.params.get(key).into_iter().flatten().map(|value| escape_html_text(value)).collect()
+    }
+
+    /// Every value of `key` for quoted attribute values.
+    pub fn attrs(&self, key: &str) -> Vec<String> {
+        self.params.get(key).into_iter().flatten().map(|value| escape_html(value)).collect()
+    }
+}
+
+/// The submitted values of a form, ready to be written back into its fields.
+///
+/// Every accessor returns escaped text, so it can go straight into a template:
+///
+/// ```ignore
+/// let form = FormValues::new(params).except(&["password"]);
+/// format!(r#"<input name="email" value="{}">"#, form.value("email"));
+/// format!(r#"<option value="fr"{}>French</option>"#, form.selected("lang", "fr"));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FormValues {
+    params: QueryMap
+}
+
+impl FormValues {
+    /// Wrap the parameters of a submitted form.
+    pub fn new(params: &QueryMap) -> FormValues {
+        FormValues { params: params.clone() }
+    }
+
+    /// Forget the given fields, such as passwords, which should never be sent
+    /// back to the browser.
+    pub fn except(mut self, fields: &[&str]) -> FormValues {
+        for field in fields {
+            self.params.remove(*field);
+        }
+        self
+    }
+
+    /// The escaped first value of `field`, or an empty string.
+    pub fn value(&self, field: &str) -> String {
+        self.params.first(field).map(escape_html).unwrap_or_default()
+    }
+
+    /// Every escaped value of `field`, for repeated fields.
+    pub fn values(&self, field: &str) -> Vec<String> {
+        self.params.get(field)
+            .map(|values| values.iter().map(|value| escape_html(value)).collect())
+            .unwrap_or_default()
+    }
+
+    /// Whether `value` was submitted for `field`.
+    pub fn contains(&self, field: &str, value: &str) -> bool {
+        self.params.get(field).is_some_and(|values| values.iter().any(|v| v == value))
+    }
+
+    /// `" checked"` if the checkbox or radio button `field` with `value` was
+    /// ticked, an empty string otherwise.
+    pub fn checked(&self, field: &str, value: &str) -> &'static str {
+        if self.contains(field, value) { " checked" } else { "" }
+    }
+
+    /// `" selected"` if the option `value` of the select `field` was chosen,
+    /// an empty string otherwise.
+    pub fn selected(&self, field: &str, value: &str) -> &'static str {
+        if self.contains(field, value) { " selected" } else { "" }
+    }
+}
+
+#[test]
+fn test_form_values() {
+    let mut params = QueryMap::new();
+    params.insert("name".to_owned(), vec![r#""><script>alert('x')</script>"#.to_owned()]);
+    params.insert("lang".to_owned(), vec!["fr".to_owned(), "de".to_owned()]);
+    params.insert("password".to_owned(), vec!["hunter2".to_owned()]);
+
+    let form = FormValues::new(&params).except(&["password"]);
+    assert_eq!(form.value("name"), "&quot;&gt;&lt;script&gt;alert(&#x27;x&#x27;)&lt;/script&gt;");
+    assert_eq!(form.value("password"), "");
+    assert_eq!(form.values("lang"), vec!["fr".to_owned(), "de".to_owned()]);
+    assert_eq!(form.selected("lang", "de"), " selected");
+    assert_eq!(form.checked("lang", "en"), "");
+
+    let html = Escaped::new(&params);
+    assert_eq!(html.text("name"), "\"&gt;&lt;script&gt;alert('x')&lt;/script&gt;");
+    assert_eq!(html.attr("name"), form.value("name"));
+    assert_eq!(html.attrs("lang"), vec!["fr".to_owned(), "de".to_owned()]);
+    assert!(html.texts("missing").is_empty() && html.text("missing").is_empty());
+}
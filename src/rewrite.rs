@@ -0,0 +1,158 @@
+//! Middleware rewriting the parsed parameters before handlers see them.
+
+use std::fmt;
+use std::sync::Arc;
+
+use iron::prelude::*;
+use iron::BeforeMiddleware;
+
+use super::{QueryMap, UrlDecodingError, UrlEncodedQuery, UrlEncodedBody, is_form};
+
+type Compute = dyn Fn(&Request) -> Option<String> + Send + Sync;
+
+#[derive(Clone)]
+enum Rule {
+    Rename(String, String),
+    Drop(Vec<String>),
+    Only(Vec<String>),
+    Inject(String, Arc<Compute>)
+}
+
+impl fmt::Debug for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Rule::Rename(ref from, ref to) => write!(f, "Rename({:?}, {:?})", from, to),
+            Rule::Drop(ref keys) => write!(f, "Drop({:?})", keys),
+            Rule::Only(ref keys) => write!(f, "Only({:?})", keys),
+            Rule::Inject(ref key, _) => write!(f, "Inject({:?})", key)
+        }
+    }
+}
+
+/// `BeforeMiddleware` that rewrites the parameters of incoming requests and
+/// stores the result as the value of `UrlEncodedQuery`, so handlers read the
+/// rewritten map with the usual `req.get_ref::<UrlEncodedQuery>()`.
+///
+/// Rules apply in the order they were added. Parse errors are answered with
+/// the response of the `UrlEncodedConfig`, like `ParseEagerly` does.
+///
+/// ```ignore
+/// chain.link_before(RewriteQuery::new()
+///     .rename("q", "query")
+///     .drop(&["debug"])
+///     .inject("tenant", |req| Some(req.url.host.to_string())));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct RewriteQuery {
+    rules: Vec<Rule>,
+    body: bool
+}
+
+impl RewriteQuery {
+    /// A middleware without rules.
+    pub fn new() -> RewriteQuery {
+        RewriteQuery::default()
+    }
+
+    /// Move the values of `from` to `to`, after any values `to` already has.
+    pub fn rename(mut self, from: &str, to: &str) -> RewriteQuery {
+        self.rules.push(Rule::Rename(from.to_owned(), to.to_owned()));
+        self
+    }
+
+    /// Remove `keys`.
+    pub fn drop(mut self, keys: &[&str]) -> RewriteQuery {
+        self.rules.push(Rule::Drop(keys.iter().map(|key| (*key).to_owned()).collect()));
+        self
+    }
+
+    /// Remove every key but `keys`.
+    pub fn only(mut self, keys: &[&str]) -> RewriteQuery {
+        self.rules.push(Rule::Only(keys.iter().map(|key| (*key).to_owned()).collect()));
+        self
+    }
+
+    /// Set `key` to the value computed from the request, replacing whatever
+    /// the client sent for it. Nothing changes when `compute` returns `None`.
+    pub fn inject<F>(mut self, key: &str, compute: F) -> RewriteQuery
+    where F: Fn(&Request) -> Option<String> + Send + Sync + 'static {
+        self.rules.push(Rule::Inject(key.to_owned(), Arc::new(compute)));
+        self
+    }
+
+    /// Also rewrite form bodies, stored as the value of `UrlEncodedBody`.
+    pub fn body(mut self, enabled: bool) -> RewriteQuery {
+        self.body = enabled;
+        self
+    }
+
+    /// Apply the rules to `params`, running the computations of injected
+    /// values with `compute`.
+    fn rewrite<F>(&self, params: &mut QueryMap, compute: F) where F: Fn(&Compute) -> Option<String> {
+        for rule in &self.rules {
+            match *rule {
+                Rule::Rename(ref from, ref to) => if let Some(values) = params.remove(from) {
+                    params.entry(to.clone()).or_default().extend(values);
+                },
+                Rule::Drop(ref keys) => for key in keys {
+                    params.remove(key);
+                },
+                Rule::Only(ref keys) => params.retain(|key, _| keys.contains(key)),
+                Rule::Inject(ref key, ref computation) => if let Some(value) = compute(&**computation) {
+                    params.insert(key.clone(), vec![value]);
+                }
+            }
+        }
+    }
+
+    /// Rewrite a parse result. Empty data only becomes a map when a value is
+    /// injected into it, while data whose every key was dropped becomes an
+    /// empty map, so the original parameters can't come back.
+    fn rewrite_result(&self, req: &Request, result: Result<QueryMap, UrlDecodingError>)
+                      -> IronResult<Option<QueryMap>> {
+        let (mut params, was_empty) = match result {
+            Ok(params) => (params, false),
+            Err(UrlDecodingError::EmptyQuery) => (QueryMap::new(), true),
+            Err(err) => return Err(err.into_iron_error(req))
+        };
+        self.rewrite(&mut params, |compute| compute(req));
+        Ok(if was_empty && params.is_empty() { None } else { Some(params) })
+    }
+}
+
+impl BeforeMiddleware for RewriteQuery {
+    fn before(&self, req: &mut Request) -> IronResult<()> {
+        let query = req.get::<UrlEncodedQuery>();
+        if let Some(params) = self.rewrite_result(req, query)? {
+            req.extensions.insert::<UrlEncodedQuery>(params);
+        }
+
+        if self.body && is_form(req) {
+            let body = req.get::<UrlEncodedBody>();
+            if let Some(params) = self.rewrite_result(req, body)? {
+                req.extensions.insert::<UrlEncodedBody>(params);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn test_rewrite_query() {
+    use super::{parse_with, ParseOptions};
+
+    let rewrite = RewriteQuery::new()
+        .rename("q", "query")
+        .drop(&["debug"])
+        .inject("tenant", |_| None)
+        .only(&["query", "page", "tenant"]);
+    let mut params = parse_with("q=rust&query=iron&debug=1&page=2&tenant=evil&x=y", &ParseOptions::new()).unwrap();
+    rewrite.rewrite(&mut params, |_| Some("acme".to_owned()));
+
+    let mut keys: Vec<&String> = params.keys().collect();
+    keys.sort();
+    assert_eq!(keys, vec!["page", "query", "tenant"]);
+    assert_eq!(params["query"], vec!["iron".to_owned(), "rust".to_owned()]);
+    assert_eq!(params["tenant"], vec!["acme".to_owned()]);
+}
@@ -0,0 +1,101 @@
+//! Dispatching requests on the value of a parameter.
+
+use std::collections::BTreeMap;
+
+use iron::prelude::*;
+use iron::Handler;
+
+use super::{QueryResult, UrlDecodingError, form_params};
+use super::accessors::QueryMapExt;
+
+/// A `Handler` dispatching to the handler registered for the value of a
+/// parameter, for endpoints that multiplex several actions through one URL.
+///
+/// The parameter is read from the form body of
+/// `application/x-www-form-urlencoded` requests and from the query string
+/// otherwise. A missing, repeated or unknown value is answered with the
+/// response of the `UrlEncodedConfig`, unless a fallback handler is set.
+///
+/// ```ignore
+/// let router = route_on_param("action")
+///     .on("create", create_handler)
+///     .on("delete", delete_handler);
+/// Iron::new(router).http("localhost:3000").unwrap();
+/// ```
+pub struct ParamRouter {
+    param: String,
+    routes: BTreeMap<String, Box<dyn Handler>>,
+    fallback: Option<Box<dyn Handler>>
+}
+
+/// A `ParamRouter` dispatching on the value of `param`.
+pub fn route_on_param(param: &str) -> ParamRouter {
+    ParamRouter { param: param.to_owned(), routes: BTreeMap::new(), fallback: None }
+}
+
+impl ParamRouter {
+    /// Handle requests where the parameter is `value` with `handler`.
+    pub fn on<H: Handler>(mut self, value: &str, handler: H) -> ParamRouter {
+        self.routes.insert(value.to_owned(), Box::new(handler));
+        self
+    }
+
+    /// Handle requests matching no route with `handler`.
+    pub fn fallback<H: Handler>(mut self, handler: H) -> ParamRouter {
+        self.fallback = Some(Box::new(handler));
+        self
+    }
+
+    /// The handler for the parameters in `result`.
+    fn select(&self, result: QueryResult) -> Result<&dyn Handler, UrlDecodingError> {
+        let selected = match result {
+            Ok(params) => params.single(&self.param).and_then(|value| {
+                let value = value.ok_or_else(|| UrlDecodingError::MissingParameter(self.param.clone()))?;
+                self.routes.get(value).ok_or_else(|| UrlDecodingError::InvalidParameter {
+                    key: self.param.clone(),
+                    message: format!("must be one of: {}",
+                                     self.routes.keys().cloned().collect::<Vec<_>>().join(", "))
+                })
+            }),
+            Err(UrlDecodingError::EmptyQuery) => Err(UrlDecodingError::MissingParameter(self.param.clone())),
+            Err(err) => return Err(err)
+        };
+
+        match (selected, &self.fallback) {
+            (Ok(handler), _) => Ok(&**handler),
+            (Err(_), Some(fallback)) => Ok(&**fallback),
+            (Err(err), None) => Err(err)
+        }
+    }
+}
+
+impl Handler for ParamRouter {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        let params = form_params(req);
+        match self.select(params) {
+            Ok(handler) => handler.handle(req),
+            Err(err) => Err(err.into_iron_error(req))
+        }
+    }
+}
+
+#[test]
+fn test_route_on_param() {
+    use iron::status;
+    use super::{parse_with, ParseOptions};
+
+    let ok = |_: &mut Request| Ok(Response::with(status::Ok));
+    let select = |router: &ParamRouter, data: &str| {
+        router.select(parse_with(data, &ParseOptions::new())).map(|_| ()).map_err(|err| err.to_string())
+    };
+
+    let router = route_on_param("action").on("create", ok).on("delete", ok);
+    assert_eq!(select(&router, "action=create&id=1"), Ok(()));
+    assert_eq!(select(&router, "id=1"), Err("Expected a value for `action`.".to_owned()));
+    assert_eq!(select(&router, ""), Err("Expected a value for `action`.".to_owned()));
+    assert_eq!(select(&router, "action=archive"), Err("`action` must be one of: create, delete.".to_owned()));
+    assert_eq!(select(&router, "action=create&action=delete"), Err("`action` must be sent only once.".to_owned()));
+
+    let router = router.fallback(ok);
+    assert_eq!(select(&router, "action=archive"), Ok(()));
+}
@@ -0,0 +1,725 @@
+//! Declarative form schemas: field types, transforms, validation rules and
+//! defaults, checked all at once.
+
+use std::any::Any;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+#[cfg(feature = "iron")]
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+#[cfg(feature = "iron")]
+use iron::prelude::*;
+#[cfg(feature = "iron")]
+use iron::typemap::Key;
+
+#[cfg(feature = "iron")]
+use plugin;
+
+use super::QueryMap;
+#[cfg(feature = "iron")]
+use super::{UrlDecodingError, form_params};
+use super::transform::Transform;
+use super::values::{self, EmailOptions, UrlOptions};
+use super::messages::{MessageCatalog, English};
+
+/// A problem with one field of a form.
+///
+/// Its text comes from a `MessageCatalog`; `Display` uses the English one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldError {
+    /// The name of the field.
+    pub field: String,
+    /// What is wrong with it.
+    pub kind: FieldErrorKind
+}
+
+impl FieldError {
+    /// An error for `field`.
+    pub fn new(field: &str, kind: FieldErrorKind) -> FieldError {
+        FieldError { field: field.to_owned(), kind }
+    }
+
+    /// An error for `field` with a fixed message, phrased to follow the field
+    /// name.
+    pub fn custom(field: &str, message: &str) -> FieldError {
+        FieldError::new(field, FieldErrorKind::Custom(message.to_owned()))
+    }
+
+    /// The stable code of the error kind, such as `FIELD_REQUIRED`.
+    pub fn code(&self) -> &'static str {
+        self.kind.code()
+    }
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&English.field_error(self))
+    }
+}
+
+/// The ways a field can fail validation.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FieldErrorKind {
+    /// A required field is absent or empty.
+    Required,
+    /// A single-valued field was sent more than once.
+    Repeated,
+    /// The value can't be converted to the field's type.
+    Invalid(FieldType),
+    /// The text is shorter than the given number of characters.
+    TooShort(usize),
+    /// The text is longer than the given number of characters.
+    TooLong(usize),
+    /// The number is outside of the range.
+    OutOfRange {
+        /// The smallest accepted number
+        min: f64,
+        /// The largest accepted number
+        max: f64
+    },
+    /// The text is not one of the given values.
+    NotAllowed(Vec<String>),
+    /// The value differs from the one of the given field.
+    Mismatch(String),
+    /// The value is before the one of the given field.
+    Before(String),
+    /// Neither this field nor any of the given ones was given.
+    NoneGiven(Vec<String>),
+    /// This field was given along with some of the given ones.
+    Conflict(Vec<String>),
+    /// An application-specific problem, phrased to follow the field name.
+    Custom(String)
+}
+
+impl FieldErrorKind {
+    /// A code identifying the kind of error, such as `FIELD_REQUIRED`.
+    ///
+    /// Codes never change between versions, unlike messages.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            FieldErrorKind::Required => "FIELD_REQUIRED",
+            FieldErrorKind::Repeated => "FIELD_REPEATED",
+            FieldErrorKind::Invalid(_) => "FIELD_INVALID",
+            FieldErrorKind::TooShort(_) => "FIELD_TOO_SHORT",
+            FieldErrorKind::TooLong(_) => "FIELD_TOO_LONG",
+            FieldErrorKind::OutOfRange { .. } => "FIELD_OUT_OF_RANGE",
+            FieldErrorKind::NotAllowed(_) => "FIELD_NOT_ALLOWED",
+            FieldErrorKind::Mismatch(_) => "FIELD_MISMATCH",
+            FieldErrorKind::Before(_) => "FIELD_BEFORE",
+            FieldErrorKind::NoneGiven(_) => "FIELD_NONE_GIVEN",
+            FieldErrorKind::Conflict(_) => "FIELD_CONFLICT",
+            FieldErrorKind::Custom(_) => "FIELD_CUSTOM"
+        }
+    }
+}
+
+/// The type the values of a field are converted to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FieldType {
+    /// Any text.
+    Text,
+    /// A signed integer.
+    Integer,
+    /// A floating point number.
+    Float,
+    /// `true`/`false`, `on`/`off`, `yes`/`no` or `1`/`0`.
+    Boolean,
+    /// An email address, as checked by `QueryMapExt::get_email`.
+    Email,
+    /// An absolute URL.
+    Url
+}
+
+/// A converted field value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    /// A `Text`, `Email` or `Url` field.
+    Text(String),
+    /// An `Integer` field.
+    Integer(i64),
+    /// A `Float` field.
+    Float(f64),
+    /// A `Boolean` field.
+    Boolean(bool),
+    /// A field declared `multiple`.
+    List(Vec<Value>)
+}
+
+impl Value {
+    /// The text of a `Text` value.
+    pub fn as_str(&self) -> Option<&str> {
+        match *self { Value::Text(ref text) => Some(text), _ => None }
+    }
+
+    /// The number of an `Integer` value.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self { Value::Integer(n) => Some(n), _ => None }
+    }
+
+    /// The number of a `Float` or `Integer` value.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Value::Float(n) => Some(n),
+            Value::Integer(n) => Some(n as f64),
+            _ => None
+        }
+    }
+
+    /// The flag of a `Boolean` value.
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self { Value::Boolean(b) => Some(b), _ => None }
+    }
+
+    /// The values of a `List`.
+    pub fn as_list(&self) -> Option<&[Value]> {
+        match *self { Value::List(ref list) => Some(list), _ => None }
+    }
+}
+
+type Check = dyn Fn(&Value) -> Result<(), FieldErrorKind> + Send + Sync;
+
+/// A validation rule for the values of a field.
+///
+/// Rules check each value of a `multiple` field separately.
+#[derive(Clone)]
+pub struct Rule(Arc<Check>);
+
+impl Rule {
+    /// A rule running `check`, which returns an error message on failure.
+    pub fn custom<F>(check: F) -> Rule
+    where F: Fn(&Value) -> Result<(), String> + Send + Sync + 'static {
+        Rule(Arc::new(move |value: &Value| check(value).map_err(FieldErrorKind::Custom)))
+    }
+
+    fn new<F>(check: F) -> Rule
+    where F: Fn(&Value) -> Result<(), FieldErrorKind> + Send + Sync + 'static {
+        Rule(Arc::new(check))
+    }
+
+    /// Text of at least `min` characters.
+    pub fn min_length(min: usize) -> Rule {
+        Rule::new(move |value| match value.as_str() {
+            Some(text) if text.chars().count() < min => Err(FieldErrorKind::TooShort(min)),
+            _ => Ok(())
+        })
+    }
+
+    /// Text of at most `max` characters.
+    pub fn max_length(max: usize) -> Rule {
+        Rule::new(move |value| match value.as_str() {
+            Some(text) if text.chars().count() > max => Err(FieldErrorKind::TooLong(max)),
+            _ => Ok(())
+        })
+    }
+
+    /// A number between `min` and `max`, inclusive.
+    pub fn range(min: f64, max: f64) -> Rule {
+        Rule::new(move |value| match value.as_f64() {
+            Some(n) if n < min || n > max => Err(FieldErrorKind::OutOfRange { min, max }),
+            _ => Ok(())
+        })
+    }
+
+    /// Text equal to one of `allowed`.
+    pub fn one_of(allowed: &[&str]) -> Rule {
+        let allowed: Vec<String> = allowed.iter().map(|s| (*s).to_owned()).collect();
+        Rule::new(move |value| match value.as_str() {
+            Some(text) if !allowed.iter().any(|a| a == text) =>
+                Err(FieldErrorKind::NotAllowed(allowed.clone())),
+            _ => Ok(())
+        })
+    }
+
+    /// Check a value against the rule.
+    pub fn check(&self, value: &Value) -> Result<(), FieldErrorKind> {
+        (self.0)(value)
+    }
+}
+
+impl fmt::Debug for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Rule")
+    }
+}
+
+/// A reusable check of the submitted values of a parameter, such as a VAT
+/// number or username validator, registered on fields with `Field::validator`.
+///
+/// Validators see the values after the field's transforms and before type
+/// conversion, with empty values left out. Closures taking the key and the
+/// values are validators too.
+pub trait ParamValidator {
+    /// Check the `values` submitted for `key`.
+    fn validate(&self, key: &str, values: &[String]) -> Result<(), FieldError>;
+}
+
+impl<F> ParamValidator for F where F: Fn(&str, &[String]) -> Result<(), FieldError> {
+    fn validate(&self, key: &str, values: &[String]) -> Result<(), FieldError> {
+        self(key, values)
+    }
+}
+
+#[derive(Clone)]
+struct SharedValidator(Arc<dyn ParamValidator + Send + Sync>);
+
+impl fmt::Debug for SharedValidator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ParamValidator")
+    }
+}
+
+/// The declaration of one field of a `FormSchema`.
+#[derive(Clone, Debug)]
+pub struct Field {
+    name: String,
+    kind: FieldType,
+    required: bool,
+    multiple: bool,
+    default: Option<String>,
+    transforms: Vec<Transform>,
+    rules: Vec<Rule>,
+    validators: Vec<SharedValidator>
+}
+
+impl Field {
+    /// An optional, single-valued field.
+    pub fn new(name: &str, kind: FieldType) -> Field {
+        Field {
+            name: name.to_owned(),
+            kind,
+            required: false,
+            multiple: false,
+            default: None,
+            transforms: Vec::new(),
+            rules: Vec::new(),
+            validators: Vec::new()
+        }
+    }
+
+    /// Fail validation when the field is absent or empty.
+    pub fn required(mut self) -> Field {
+        self.required = true;
+        self
+    }
+
+    /// Accept any number of values, collected in a `Value::List`.
+    pub fn multiple(mut self) -> Field {
+        self.multiple = true;
+        self
+    }
+
+    /// Use `value` when the field is absent or empty. It is converted and
+    /// validated like a submitted value.
+    pub fn default(mut self, value: &str) -> Field {
+        self.default = Some(value.to_owned());
+        self
+    }
+
+    /// Normalize the submitted values with `transform` before converting them.
+    pub fn transform(mut self, transform: Transform) -> Field {
+        self.transforms.push(transform);
+        self
+    }
+
+    /// Check the converted values against `rule`.
+    pub fn rule(mut self, rule: Rule) -> Field {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Check the submitted values with `validator`.
+    pub fn validator<V>(mut self, validator: V) -> Field
+    where V: ParamValidator + Send + Sync + 'static {
+        self.validators.push(SharedValidator(Arc::new(validator)));
+        self
+    }
+
+    /// The name of the field.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn convert(&self, raw: &str) -> Result<Value, FieldErrorKind> {
+        let converted = match self.kind {
+            FieldType::Text => Some(Value::Text(raw.to_owned())),
+            FieldType::Integer => raw.parse().ok().map(Value::Integer),
+            FieldType::Float => raw.parse().ok().map(Value::Float),
+            FieldType::Boolean => match &*raw.to_lowercase() {
+                "true" | "on" | "yes" | "1" => Some(Value::Boolean(true)),
+                "false" | "off" | "no" | "0" => Some(Value::Boolean(false)),
+                _ => None
+            },
+            FieldType::Email => values::parse_email(&self.name, raw, EmailOptions::new())
+                .ok().map(Value::Text),
+            FieldType::Url => values::parse_url(&self.name, raw, &UrlOptions::new())
+                .ok().map(|url| Value::Text(url.serialize()))
+        };
+        converted.ok_or(FieldErrorKind::Invalid(self.kind))
+    }
+
+    /// Transform, convert and validate the submitted values of this field.
+    fn validate(&self, params: &QueryMap) -> Result<Option<Value>, FieldError> {
+        let error = |kind: FieldErrorKind| FieldError { field: self.name.clone(), kind };
+
+        let mut raw: Vec<String> = params.get(&self.name)
+            .map(|values| values.iter()
+                 .map(|value| self.transforms.iter().fold(value.clone(), |v, t| t.apply(&v)))
+                 .filter(|value| !value.is_empty())
+                 .collect())
+            .unwrap_or_default();
+
+        if raw.is_empty() {
+            match self.default {
+                Some(ref default) => raw.push(default.clone()),
+                None if self.required => return Err(error(FieldErrorKind::Required)),
+                None => return Ok(None)
+            }
+        }
+        if !self.multiple && raw.len() > 1 {
+            return Err(error(FieldErrorKind::Repeated));
+        }
+        for validator in &self.validators {
+            validator.0.validate(&self.name, &raw)?;
+        }
+
+        let mut converted = Vec::with_capacity(raw.len());
+        for value in &raw {
+            let value = self.convert(value).map_err(&error)?;
+            for rule in &self.rules {
+                rule.check(&value).map_err(&error)?;
+            }
+            converted.push(value);
+        }
+
+        Ok(Some(if self.multiple { Value::List(converted) } else { converted.remove(0) }))
+    }
+}
+
+/// The converted values of a form that passed validation.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FormData {
+    values: HashMap<String, Value>
+}
+
+impl FormData {
+    /// The value of `field`. `None` for optional fields that were left empty.
+    pub fn get(&self, field: &str) -> Option<&Value> {
+        self.values.get(field)
+    }
+
+    /// The text of `field`.
+    pub fn str(&self, field: &str) -> Option<&str> {
+        self.get(field).and_then(Value::as_str)
+    }
+
+    /// The integer value of `field`.
+    pub fn int(&self, field: &str) -> Option<i64> {
+        self.get(field).and_then(Value::as_i64)
+    }
+
+    /// The numeric value of `field`.
+    pub fn float(&self, field: &str) -> Option<f64> {
+        self.get(field).and_then(Value::as_f64)
+    }
+
+    /// The boolean value of `field`.
+    pub fn bool(&self, field: &str) -> Option<bool> {
+        self.get(field).and_then(Value::as_bool)
+    }
+
+    /// The values of a `multiple` field, empty if none were sent.
+    pub fn list(&self, field: &str) -> &[Value] {
+        self.get(field).and_then(Value::as_list).unwrap_or(&[])
+    }
+
+    /// Every converted value, by field name.
+    pub fn values(&self) -> &HashMap<String, Value> {
+        &self.values
+    }
+}
+
+/// A set of field declarations that a form is checked against as a whole.
+///
+/// ```ignore
+/// FormSchema::new()
+///     .field(Field::new("email", FieldType::Email).required().transform(Transform::Trim))
+///     .field(Field::new("age", FieldType::Integer).rule(Rule::range(0.0, 150.0)))
+///     .field(Field::new("plan", FieldType::Text).default("free"))
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct FormSchema {
+    fields: Vec<Field>,
+    cross_rules: Vec<CrossRule>
+}
+
+type CrossCheck = dyn Fn(&FormData) -> Result<(), FieldError> + Send + Sync;
+
+/// A rule spanning several fields, only checked once they all passed their
+/// own validation.
+#[derive(Clone)]
+struct CrossRule {
+    fields: Vec<String>,
+    check: Arc<CrossCheck>
+}
+
+impl fmt::Debug for CrossRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CrossRule").field("fields", &self.fields).finish()
+    }
+}
+
+impl FormSchema {
+    /// A schema without fields.
+    pub fn new() -> FormSchema {
+        FormSchema::default()
+    }
+
+    /// Declare a field.
+    pub fn field(mut self, field: Field) -> FormSchema {
+        self.fields.push(field);
+        self
+    }
+
+    /// Check `fields` together with `check`, which returns an error attached
+    /// to whichever field should display it.
+    ///
+    /// The rule is skipped when one of `fields` failed its own validation, so
+    /// it can rely on their values being well-formed; absent optional fields
+    /// are simply missing from the `FormData`.
+    pub fn rule_across<F>(mut self, fields: &[&str], check: F) -> FormSchema
+    where F: Fn(&FormData) -> Result<(), FieldError> + Send + Sync + 'static {
+        self.cross_rules.push(CrossRule {
+            fields: fields.iter().map(|field| (*field).to_owned()).collect(),
+            check: Arc::new(check)
+        });
+        self
+    }
+
+    /// Require `field` to have the same value as `other`, as with password
+    /// confirmations. The error is attached to `field`.
+    pub fn same_as(self, field: &str, other: &str) -> FormSchema {
+        let (name, other_name) = (field.to_owned(), other.to_owned());
+        self.rule_across(&[field, other], move |data| {
+            if data.get(&name) == data.get(&other_name) {
+                Ok(())
+            } else {
+                Err(FieldError::new(&name, FieldErrorKind::Mismatch(other_name.clone())))
+            }
+        })
+    }
+
+    /// Require `field` to be greater than or equal to `other` when both are
+    /// given, as with `end_date` and `start_date`. Numbers compare by value
+    /// and text compares lexicographically, which orders ISO 8601 dates and
+    /// times correctly. The error is attached to `field`.
+    pub fn not_before(self, field: &str, other: &str) -> FormSchema {
+        let (name, other_name) = (field.to_owned(), other.to_owned());
+        self.rule_across(&[field, other], move |data| {
+            match (data.get(&name), data.get(&other_name)) {
+                (Some(value), Some(other)) if compare(value, other) == Some(Ordering::Less) =>
+                    Err(FieldError::new(&name, FieldErrorKind::Before(other_name.clone()))),
+                _ => Ok(())
+            }
+        })
+    }
+
+    /// Require exactly one of `fields` to be given. The error is attached to
+    /// the first of them.
+    ///
+    /// Panics when `fields` is empty, while the schema is built rather than
+    /// when a request is validated.
+    pub fn exactly_one_of(self, fields: &[&str]) -> FormSchema {
+        assert!(!fields.is_empty(), "exactly_one_of needs at least one field");
+        let names: Vec<String> = fields.iter().map(|field| (*field).to_owned()).collect();
+        self.rule_across(fields, move |data| {
+            let given = names.iter().filter(|name| data.get(name).is_some()).count();
+            let others = names[1..].to_vec();
+            match given {
+                1 => Ok(()),
+                0 => Err(FieldError::new(&names[0], FieldErrorKind::NoneGiven(others))),
+                _ => Err(FieldError::new(&names[0], FieldErrorKind::Conflict(others)))
+            }
+        })
+    }
+
+    /// The declared fields, in declaration order.
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    /// Check `params` against every field, reporting every failing field
+    /// rather than just the first one. Undeclared parameters are ignored.
+    pub fn validate(&self, params: &QueryMap) -> Result<FormData, Vec<FieldError>> {
+        let mut data = FormData::default();
+        let mut errors = Vec::new();
+
+        for field in &self.fields {
+            match field.validate(params) {
+                Ok(Some(value)) => { data.values.insert(field.name.clone(), value); },
+                Ok(None) => (),
+                Err(error) => errors.push(error)
+            }
+        }
+
+        for rule in &self.cross_rules {
+            if rule.fields.iter().any(|field| errors.iter().any(|error| error.field == *field)) {
+                continue;
+            }
+            if let Err(error) = (rule.check)(&data) {
+                errors.push(error);
+            }
+        }
+
+        if errors.is_empty() { Ok(data) } else { Err(errors) }
+    }
+}
+
+/// Order two values of the same kind: numbers by value, text lexicographically.
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Text(a), Value::Text(b)) => Some(a.cmp(b)),
+        _ => a.as_f64().and_then(|a| b.as_f64().and_then(|b| a.partial_cmp(&b)))
+    }
+}
+
+/// A type describing a form, for use with the `SchemaForm` plugin.
+pub trait Schema: Any {
+    /// The schema the form is validated against.
+    fn schema() -> FormSchema;
+}
+
+/// Plugin for `Request` that validates the submitted form against the schema
+/// of `S`: the body of form-encoded requests, the query string otherwise.
+///
+/// Failures are reported all at once in a `ValidationFailed` error:
+/// `req.get_ref::<SchemaForm<SignupForm>>()`
+#[cfg(feature = "iron")]
+pub struct SchemaForm<S: Schema> {
+    marker: PhantomData<S>
+}
+
+#[cfg(feature = "iron")]
+impl<S: Schema> Key for SchemaForm<S> {
+    type Value = FormData;
+}
+
+#[cfg(feature = "iron")]
+impl<'a, 'b, S: Schema> plugin::Plugin<Request<'a, 'b>> for SchemaForm<S> {
+    type Error = UrlDecodingError;
+
+    fn eval(req: &mut Request) -> Result<FormData, UrlDecodingError> {
+        let params = match form_params(req) {
+            Ok(params) => params,
+            Err(UrlDecodingError::EmptyQuery) => QueryMap::new(),
+            Err(err) => return Err(err)
+        };
+        S::schema().validate(&params).map_err(UrlDecodingError::ValidationFailed)
+    }
+}
+
+#[cfg(test)]
+fn messages(errors: Vec<FieldError>) -> Vec<String> {
+    errors.iter().map(|error| error.to_string()).collect()
+}
+
+#[test]
+fn test_form_schema() {
+    use super::{parse_with, ParseOptions};
+
+    let schema = FormSchema::new()
+        .field(Field::new("email", FieldType::Email).required().transform(Transform::Trim))
+        .field(Field::new("age", FieldType::Integer).rule(Rule::range(0.0, 150.0)))
+        .field(Field::new("plan", FieldType::Text).default("free").rule(Rule::one_of(&["free", "pro"])))
+        .field(Field::new("tag", FieldType::Text).multiple().rule(Rule::max_length(5)))
+        .field(Field::new("newsletter", FieldType::Boolean));
+    let parse = |data| parse_with(data, &ParseOptions::new()).unwrap();
+
+    let data = schema.validate(&parse("email=+a%40b.com+&age=42&tag=x&tag=y&newsletter=on")).unwrap();
+    assert_eq!(data.str("email"), Some("a@b.com"));
+    assert_eq!(data.int("age"), Some(42));
+    assert_eq!(data.str("plan"), Some("free"));
+    assert_eq!(data.list("tag"), &[Value::Text("x".to_owned()), Value::Text("y".to_owned())][..]);
+    assert_eq!(data.bool("newsletter"), Some(true));
+
+    let errors = schema.validate(&parse("email=&age=200&age=1&plan=gold&tag=toolong")).unwrap_err();
+    assert_eq!(errors.iter().map(FieldError::code).collect::<Vec<_>>(),
+               vec!["FIELD_REQUIRED", "FIELD_REPEATED", "FIELD_NOT_ALLOWED", "FIELD_TOO_LONG"]);
+    assert_eq!(messages(errors), vec![
+        "`email` is required",
+        "`age` must be sent only once",
+        "`plan` must be one of: free, pro",
+        "`tag` must be at most 5 characters long"
+    ]);
+}
+
+#[test]
+fn test_cross_field_rules() {
+    use super::{parse_with, ParseOptions};
+
+    let schema = FormSchema::new()
+        .field(Field::new("password", FieldType::Text).required())
+        .field(Field::new("password_confirmation", FieldType::Text).required())
+        .field(Field::new("start_date", FieldType::Text))
+        .field(Field::new("end_date", FieldType::Text))
+        .field(Field::new("email", FieldType::Email))
+        .field(Field::new("phone", FieldType::Text))
+        .same_as("password_confirmation", "password")
+        .not_before("end_date", "start_date")
+        .exactly_one_of(&["email", "phone"]);
+    let parse = |data| parse_with(data, &ParseOptions::new()).unwrap();
+
+    assert!(schema.validate(&parse("password=x&password_confirmation=x&\
+                                    start_date=2016-01-01&end_date=2016-01-31&phone=555")).is_ok());
+
+    let errors = schema.validate(&parse("password=x&password_confirmation=y&\
+                                         start_date=2016-02-01&end_date=2016-01-31")).unwrap_err();
+    assert_eq!(messages(errors), vec![
+        "`password_confirmation` must match `password`",
+        "`end_date` must not be before `start_date`",
+        "`email` or `phone` must be given"
+    ]);
+
+    // Rules involving a field that failed on its own are not checked.
+    let errors = schema.validate(&parse("password_confirmation=y&email=bad&phone=555")).unwrap_err();
+    assert_eq!(messages(errors), vec![
+        "`password` is required",
+        "`email` must be a valid email address"
+    ]);
+
+    let empty = ::std::panic::catch_unwind(|| FormSchema::new().exactly_one_of(&[]));
+    assert!(empty.is_err());
+}
+
+#[test]
+fn test_param_validator() {
+    use super::{parse_with, ParseOptions};
+
+    struct Username;
+
+    impl ParamValidator for Username {
+        fn validate(&self, key: &str, values: &[String]) -> Result<(), FieldError> {
+            match values.iter().all(|value| value.chars().all(|c| c.is_ascii_alphanumeric())) {
+                true => Ok(()),
+                false => Err(FieldError::custom(key, "may only contain letters and digits"))
+            }
+        }
+    }
+
+    let schema = FormSchema::new()
+        .field(Field::new("user", FieldType::Text).validator(Username))
+        .field(Field::new("code", FieldType::Integer).validator(|key: &str, values: &[String]| {
+            match values[0].len() {
+                4 => Ok(()),
+                _ => Err(FieldError::custom(key, "must have four digits"))
+            }
+        }));
+    let parse = |data| parse_with(data, &ParseOptions::new()).unwrap();
+
+    assert!(schema.validate(&parse("user=jdoe42&code=0042")).is_ok());
+    assert_eq!(messages(schema.validate(&parse("user=j.doe&code=42")).unwrap_err()), vec![
+        "`user` may only contain letters and digits",
+        "`code` must have four digits"
+    ]);
+}
@@ -0,0 +1,91 @@
+//! Sharing configuration, schemas and secrets across a chain with the
+//! `persistent` crate, the way Iron apps already share state.
+
+use std::collections::HashMap;
+
+use iron::prelude::*;
+use iron::typemap::{Key, TypeMap};
+use persistent::{Read, State};
+
+use super::{QueryMap, UrlDecodingError, UrlEncodedConfig, form_params};
+use super::schema::{FormSchema, FormData};
+
+/// Key for named secrets shared with `persistent`, such as the keys of
+/// `Flash`, `FeatureFlags` or `WebhookVerifier`:
+///
+/// ```ignore
+/// let mut secrets = HashMap::new();
+/// secrets.insert("flash".to_owned(), FLASH_KEY.to_vec());
+/// chain.link_before(Read::<Secrets>::one(secrets));
+/// // In a handler:
+/// let key = shared_secret(req, "flash").expect("the flash key is configured");
+/// ```
+pub struct Secrets;
+
+impl Key for Secrets {
+    type Value = HashMap<String, Vec<u8>>;
+}
+
+/// Key for named `FormSchema`s shared with `persistent`, so they are built
+/// once, or replaced at runtime when shared with `State`.
+pub struct Schemas;
+
+impl Key for Schemas {
+    type Value = HashMap<String, FormSchema>;
+}
+
+/// Call `f` with the value of `K` shared with `persistent::Read`, or else
+/// with `persistent::State`.
+fn with_shared<K, T, F>(extensions: &TypeMap, f: F) -> Option<T>
+where K: Key, K::Value: Send + Sync, F: FnOnce(&K::Value) -> T {
+    if let Some(value) = extensions.get::<Read<K>>() {
+        return Some(f(value));
+    }
+    extensions.get::<State<K>>()
+        .and_then(|state| state.read().ok().map(|value| f(&value)))
+}
+
+/// The `UrlEncodedConfig` shared with `persistent`, which the plugins use
+/// when no configuration was linked directly.
+pub fn shared_config(req: &Request) -> Option<UrlEncodedConfig> {
+    with_shared::<UrlEncodedConfig, _, _>(&req.extensions, UrlEncodedConfig::clone)
+}
+
+/// The secret called `name` among the shared `Secrets`.
+pub fn shared_secret(req: &Request, name: &str) -> Option<Vec<u8>> {
+    with_shared::<Secrets, _, _>(&req.extensions, |secrets| secrets.get(name).cloned()).flatten()
+}
+
+/// Validate the submitted form against the schema called `name` among the
+/// shared `Schemas`, like `SchemaForm` does. `None` when there is no such
+/// schema.
+pub fn shared_form(req: &mut Request, name: &str) -> Option<Result<FormData, UrlDecodingError>> {
+    let params = match form_params(req) {
+        Ok(params) => Ok(params),
+        Err(UrlDecodingError::EmptyQuery) => Ok(QueryMap::new()),
+        Err(err) => Err(err)
+    };
+    with_shared::<Schemas, _, _>(&req.extensions, |schemas| schemas.get(name).map(|schema| {
+        params.and_then(|params| schema.validate(&params).map_err(UrlDecodingError::ValidationFailed))
+    })).flatten()
+}
+
+#[test]
+fn test_with_shared() {
+    use std::sync::{Arc, RwLock};
+
+    let mut secrets = HashMap::new();
+    secrets.insert("flash".to_owned(), b"key".to_vec());
+    let secret = |extensions: &TypeMap| {
+        with_shared::<Secrets, _, _>(extensions, |secrets| secrets.get("flash").cloned()).flatten()
+    };
+
+    let mut extensions = TypeMap::new();
+    assert_eq!(secret(&extensions), None);
+    extensions.insert::<State<Secrets>>(Arc::new(RwLock::new(secrets.clone())));
+    assert_eq!(secret(&extensions), Some(b"key".to_vec()));
+
+    secrets.insert("flash".to_owned(), b"read".to_vec());
+    extensions.insert::<Read<Secrets>>(Arc::new(secrets));
+    assert_eq!(secret(&extensions), Some(b"read".to_vec()));
+}
@@ -0,0 +1,42 @@
+//! Message signing, for the helpers that protect or check parameters with a
+//! signature.
+
+/// Computes a message authentication code, for example HMAC-SHA1.
+///
+/// urlencoded doesn't ship any cryptography: implement this with the library
+/// your application already uses. Closures taking the key and the message
+/// and returning the signature bytes implement it as well.
+pub trait Signer {
+    /// Sign `message` with `key`.
+    fn sign(&self, key: &[u8], message: &[u8]) -> Vec<u8>;
+}
+
+impl<F> Signer for F where F: Fn(&[u8], &[u8]) -> Vec<u8> {
+    fn sign(&self, key: &[u8], message: &[u8]) -> Vec<u8> {
+        self(key, message)
+    }
+}
+
+/// Compare two byte strings in a time that doesn't depend on where they differ,
+/// so signatures can't be guessed byte by byte.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b.iter()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// A toy signer for the tests, which only need something key-dependent: the
+/// message XORed with the key.
+#[cfg(test)]
+pub fn test_signer(key: &[u8], message: &[u8]) -> Vec<u8> {
+    message.iter().zip(key.iter().cycle()).map(|(m, k)| m ^ k).collect()
+}
+
+#[test]
+fn test_constant_time_eq() {
+    assert!(constant_time_eq(b"signature", b"signature"));
+    assert!(!constant_time_eq(b"signature", b"signaturE"));
+    assert!(!constant_time_eq(b"signature", b"sig"));
+}
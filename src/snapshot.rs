@@ -0,0 +1,151 @@
+//! Recording the parameters of a request so that it can be replayed in a test,
+//! for reproducing production issues with the same parsing code.
+
+use std::collections::BTreeMap;
+
+use rustc_serialize::json::{self, Json};
+#[cfg(feature = "iron")]
+use bodyparser;
+#[cfg(feature = "iron")]
+use iron::prelude::*;
+#[cfg(feature = "iron")]
+use iron::headers::ContentType;
+#[cfg(feature = "iron")]
+use iron::method::Method;
+#[cfg(feature = "iron")]
+use iron::mime::Mime;
+
+use super::UrlDecodingError;
+#[cfg(feature = "iron")]
+use super::{RawFormBody, RawQuery, UrlEncodedBody, UrlEncodedQuery};
+
+/// What the plugins parse in a request: its method, query string, content
+/// type and body, all still encoded, so replaying it goes through the same
+/// options and transforms.
+///
+/// ```ignore
+/// // In production:
+/// log.write_all(capture(req)?.to_json().as_bytes())?;
+/// // In a test:
+/// apply_to_test_request(&Snapshot::from_json(&logged)?, &mut req)?;
+/// let params = req.get::<UrlEncodedBody>();
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    /// The request method, such as `POST`.
+    pub method: String,
+    /// The query string, without the `?`.
+    pub query: Option<String>,
+    /// The `Content-Type` header.
+    pub content_type: Option<String>,
+    /// The body.
+    pub body: String
+}
+
+impl Snapshot {
+    /// The snapshot as a JSON object.
+    pub fn to_json(&self) -> String {
+        let optional = |value: &Option<String>| value.clone().map(Json::String).unwrap_or(Json::Null);
+        let mut object = BTreeMap::new();
+        object.insert("method".to_owned(), Json::String(self.method.clone()));
+        object.insert("query".to_owned(), optional(&self.query));
+        object.insert("content_type".to_owned(), optional(&self.content_type));
+        object.insert("body".to_owned(), Json::String(self.body.clone()));
+        json::encode(&Json::Object(object)).unwrap_or_default()
+    }
+
+    /// Read a snapshot back from `to_json`.
+    pub fn from_json(data: &str) -> Result<Snapshot, UrlDecodingError> {
+        let invalid = |message: &str| UrlDecodingError::InvalidParameter {
+            key: "snapshot".to_owned(),
+            message: message.to_owned()
+        };
+        let object = match Json::from_str(data) {
+            Ok(Json::Object(object)) => object,
+            _ => return Err(invalid("is not a JSON object"))
+        };
+        let field = |name: &str| match object.get(name) {
+            Some(Json::String(value)) => Ok(Some(value.clone())),
+            Some(Json::Null) | None => Ok(None),
+            Some(_) => Err(invalid(&format!("has a {} that isn't a string", name)))
+        };
+
+        Ok(Snapshot {
+            method: field("method")?.ok_or_else(|| invalid("has no method"))?,
+            query: field("query")?,
+            content_type: field("content_type")?,
+            body: field("body")?.unwrap_or_default()
+        })
+    }
+}
+
+/// Record the parameters of `req`. Capture it after parsing its body, or the
+/// body is read through `bodyparser::Raw`; multipart bodies aren't recorded.
+#[cfg(feature = "iron")]
+pub fn capture(req: &mut Request) -> Result<Snapshot, UrlDecodingError> {
+    let content_type = req.headers.get::<ContentType>().map(|content_type| content_type.0.to_string());
+    let multipart = content_type.as_ref().is_some_and(|mime| mime.starts_with("multipart/"));
+    let body = match req.extensions.get::<RawFormBody>().cloned() {
+        Some(body) => body,
+        None if multipart => String::new(),
+        None => req.get::<bodyparser::Raw>()
+            .map(|body| body.unwrap_or_default())
+            .map_err(UrlDecodingError::BodyError)?
+    };
+
+    Ok(Snapshot {
+        method: req.method.to_string(),
+        query: req.url.query.clone(),
+        content_type,
+        body
+    })
+}
+
+/// Make `req`, typically built for a test, carry the parameters of
+/// `snapshot`, and forget what the plugins parsed from it so far.
+///
+/// The body is given to `bodyparser::Raw`, where `UrlEncodedBody` reads it
+/// from, so configurations spooling bodies or with a read timeout, which read
+/// the body stream themselves, don't see it.
+#[cfg(feature = "iron")]
+pub fn apply_to_test_request(snapshot: &Snapshot, req: &mut Request) -> Result<(), UrlDecodingError> {
+    req.method = snapshot.method.parse::<Method>().map_err(|_| UrlDecodingError::InvalidParameter {
+        key: "method".to_owned(),
+        message: "is not a valid method".to_owned()
+    })?;
+    req.url.query = snapshot.query.clone();
+    match snapshot.content_type {
+        Some(ref content_type) => {
+            let mime = content_type.parse::<Mime>().map_err(|_| UrlDecodingError::InvalidParameter {
+                key: "content_type".to_owned(),
+                message: "is not a valid media type".to_owned()
+            })?;
+            req.headers.set(ContentType(mime));
+        },
+        None => { req.headers.remove::<ContentType>(); }
+    }
+
+    req.extensions.remove::<UrlEncodedQuery>();
+    req.extensions.remove::<UrlEncodedBody>();
+    req.extensions.remove::<RawQuery>();
+    req.extensions.remove::<RawFormBody>();
+    req.extensions.insert::<bodyparser::Raw>(Some(snapshot.body.clone()));
+    Ok(())
+}
+
+#[test]
+fn test_snapshot_json() {
+    let snapshot = Snapshot {
+        method: "POST".to_owned(),
+        query: Some("page=2&q=%22a%22".to_owned()),
+        content_type: None,
+        body: "name=caf%C3%A9&tags=a&tags=b".to_owned()
+    };
+    assert_eq!(Snapshot::from_json(&snapshot.to_json()).unwrap(), snapshot);
+    assert_eq!(Snapshot::from_json(r#"{"method": "GET"}"#).unwrap().body, "");
+
+    let err = Snapshot::from_json(r#"{"method": "GET", "body": 3}"#).unwrap_err();
+    assert!(err.to_string().contains("has a body that isn't a string"));
+    assert!(matches!(Snapshot::from_json("[]"), Err(UrlDecodingError::InvalidParameter { .. })));
+    assert!(Snapshot::from_json("{}").is_err());
+}
@@ -0,0 +1,92 @@
+//! Spooling huge urlencoded bodies to disk, for bulk imports posting forms
+//! that shouldn't be held in memory.
+
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+
+use bodyparser::{BodyError, BodyErrorCause, LimitReader};
+use iron::prelude::*;
+use iron::headers::ContentType;
+
+use super::{QueryResult, RawFormBody, UrlDecodingError, UrlEncodedConfig};
+use super::multipart::{SpooledFile, create_spool_file};
+use super::reader::{parse_stream, read_error};
+use super::timeout::{TimedReader, body_limit};
+
+/// A body small enough for memory, or the file it was spooled to.
+enum Spooled {
+    Memory(Vec<u8>),
+    File(SpooledFile)
+}
+
+/// Read `body` into memory up to `threshold` bytes, into a new file in `dir`
+/// past that.
+fn spool<R: Read>(mut body: R, threshold: usize, dir: &Path) -> io::Result<Spooled> {
+    let mut memory = Vec::new();
+    body.by_ref().take(threshold as u64 + 1).read_to_end(&mut memory)?;
+    if memory.len() <= threshold {
+        return Ok(Spooled::Memory(memory));
+    }
+
+    let (mut file, spooled) = create_spool_file(dir)?;
+    file.write_all(&memory)?;
+    io::copy(&mut body, &mut file)?;
+    file.flush()?;
+    Ok(Spooled::File(spooled))
+}
+
+/// Read the body of `req` like `bodyparser::Raw` does, spooling it to the
+/// multipart spool directory once it is bigger than `threshold`, and parse
+/// it with `config`. Only bodies kept in memory are stored as `RawFormBody`.
+pub fn parse_spooled(req: &mut Request, threshold: usize, config: &UrlEncodedConfig) -> QueryResult {
+    if req.headers.get::<ContentType>().is_none() {
+        return config.parse("");
+    }
+
+    let dir = config.multipart().spool_directory();
+    let limit = body_limit(req);
+    let body = LimitReader::new(req.body.by_ref(), limit);
+    let spooled = match config.body_read_timeout() {
+        Some(timeout) => spool(TimedReader::new(body, timeout), threshold, &dir),
+        None => spool(body, threshold, &dir)
+    }.map_err(|err| read_error("Can't read request body", err))?;
+
+    match spooled {
+        Spooled::Memory(bytes) => {
+            let body = String::from_utf8(bytes).map_err(|err| UrlDecodingError::BodyError(BodyError {
+                detail: "Invalid UTF-8 sequence".to_owned(),
+                cause: BodyErrorCause::Utf8Error(err.utf8_error())
+            }))?;
+            let result = config.parse(&body);
+            req.extensions.insert::<RawFormBody>(body);
+            result
+        },
+        Spooled::File(file) => {
+            let reader = File::open(file.path()).map_err(|err| read_error("Can't read spooled body", err))?;
+            parse_stream(BufReader::new(reader), config)
+        }
+    }
+}
+
+#[test]
+fn test_spool() {
+    use std::env;
+
+    let body = "a=1&b=2&a=3";
+    match spool(body.as_bytes(), 64, &env::temp_dir()).unwrap() {
+        Spooled::Memory(bytes) => assert_eq!(bytes, body.as_bytes()),
+        Spooled::File(_) => panic!("the body fits in memory")
+    }
+
+    let file = match spool(body.as_bytes(), 4, &env::temp_dir()).unwrap() {
+        Spooled::File(file) => file,
+        Spooled::Memory(_) => panic!("the body should be spooled")
+    };
+    let params = parse_stream(BufReader::new(File::open(file.path()).unwrap()), &UrlEncodedConfig::new()).unwrap();
+    assert_eq!(params["a"], vec!["1".to_owned(), "3".to_owned()]);
+
+    let path = file.path().to_owned();
+    drop(file);
+    assert!(!path.exists());
+}
@@ -0,0 +1,68 @@
+//! Figures about parsed parameters, for logging and alerting on anomalous
+//! requests.
+
+use super::QueryMap;
+
+/// The size of a set of parameters, from `QueryMapExt::stats`.
+///
+/// ```ignore
+/// let stats = params.stats().raw_len(req.extensions.get::<RawQuery>().map_or(0, |raw| raw.len()));
+/// if stats.pairs > 1000 || stats.expansion_ratio() > Some(2.5) {
+///     warn!("unusual parameters: {:?}", stats);
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ParamStats {
+    /// The number of `key=value` pairs.
+    pub pairs: usize,
+    /// The number of distinct keys.
+    pub keys: usize,
+    /// The most values any key has.
+    pub max_values: usize,
+    /// The bytes of every key and value once decoded, a key counting once
+    /// per value.
+    pub decoded_bytes: usize,
+    /// The bytes of the data the parameters were parsed from, if known.
+    pub raw_bytes: Option<usize>
+}
+
+impl ParamStats {
+    /// The figures of `params`.
+    pub fn of(params: &QueryMap) -> ParamStats {
+        params.iter().fold(ParamStats::default(), |stats, (key, values)| ParamStats {
+            pairs: stats.pairs + values.len(),
+            keys: stats.keys + 1,
+            max_values: stats.max_values.max(values.len()),
+            decoded_bytes: stats.decoded_bytes + values.iter().map(|value| key.len() + value.len()).sum::<usize>(),
+            raw_bytes: None
+        })
+    }
+
+    /// Record that the parameters were parsed from `len` bytes.
+    pub fn raw_len(mut self, len: usize) -> ParamStats {
+        self.raw_bytes = Some(len);
+        self
+    }
+
+    /// How many raw bytes each decoded byte took, when the raw size is known
+    /// and there are decoded bytes. Separators count as raw bytes, and each
+    /// percent-encoded byte as three, so data encoded throughout, as
+    /// obfuscated payloads are, comes close to 3.
+    pub fn expansion_ratio(&self) -> Option<f64> {
+        match self.raw_bytes {
+            Some(raw) if self.decoded_bytes > 0 => Some(raw as f64 / self.decoded_bytes as f64),
+            _ => None
+        }
+    }
+}
+
+#[test]
+fn test_param_stats() {
+    use super::{parse_with, ParseOptions};
+
+    let data = "a=1&a=2&a=%C3%A9&long=abcdef";
+    let stats = ParamStats::of(&parse_with(data, &ParseOptions::new()).unwrap()).raw_len(data.len());
+    assert_eq!((stats.pairs, stats.keys, stats.max_values, stats.decoded_bytes), (4, 2, 3, 17));
+    assert_eq!(stats.expansion_ratio(), Some(28.0 / 17.0));
+    assert_eq!(ParamStats::of(&QueryMap::new()).expansion_ratio(), None);
+}
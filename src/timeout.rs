@@ -0,0 +1,115 @@
+//! Bounding how long reading a request body may take, against clients
+//! trickling their body in a byte at a time.
+
+use std::io::{self, Read};
+use std::time::{Duration, Instant};
+
+use bodyparser::{self, BodyError, BodyErrorCause, LimitReader};
+use iron::prelude::*;
+use iron::headers::ContentType;
+
+use super::UrlDecodingError;
+
+/// A reader failing with `TimedOut` once `timeout` has passed since it was
+/// created, however steadily the data comes in.
+///
+/// It can only check the time between reads: a client sending nothing at all
+/// is left to the read timeout of the server's sockets.
+pub struct TimedReader<R> {
+    inner: R,
+    deadline: Instant
+}
+
+impl<R: Read> TimedReader<R> {
+    /// Read from `inner` for at most `timeout`.
+    pub fn new(inner: R, timeout: Duration) -> TimedReader<R> {
+        TimedReader { inner, deadline: Instant::now() + timeout }
+    }
+}
+
+impl<R: Read> Read for TimedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if Instant::now() >= self.deadline {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "Body read timed out"));
+        }
+        self.inner.read(buf)
+    }
+}
+
+/// Whether reading failed because the body took too long, because of a
+/// `TimedReader` or of a socket read timeout.
+pub fn is_timeout(err: &io::Error) -> bool {
+    matches!(err.kind(), io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock)
+}
+
+/// bodyparser's limit for requests without a `MaxBodyLength`.
+const DEFAULT_BODY_LIMIT: usize = 100 * 1024 * 1024;
+
+/// The body limit bodyparser applies to `req`.
+#[cfg(not(feature = "persistent"))]
+pub fn body_limit(_: &Request) -> usize {
+    DEFAULT_BODY_LIMIT
+}
+
+/// The body limit bodyparser applies to `req`: its `MaxBodyLength`, when
+/// shared with `persistent::Read`.
+#[cfg(feature = "persistent")]
+pub fn body_limit(req: &Request) -> usize {
+    use persistent;
+
+    req.extensions.get::<persistent::Read<bodyparser::MaxBodyLength>>()
+        .map(|limit| **limit)
+        .unwrap_or(DEFAULT_BODY_LIMIT)
+}
+
+/// Read the body the way `bodyparser::Raw` does, within `timeout`, and cache
+/// it as the value of `Raw` for the plugins reading it afterwards.
+pub fn read_body_within(req: &mut Request, timeout: Duration) -> Result<String, UrlDecodingError> {
+    if req.headers.get::<ContentType>().is_none() {
+        return Ok(String::new());
+    }
+
+    let limit = body_limit(req);
+    let mut bytes = Vec::new();
+    LimitReader::new(TimedReader::new(req.body.by_ref(), timeout), limit).read_to_end(&mut bytes)
+        .map_err(|err| if is_timeout(&err) {
+            UrlDecodingError::BodyReadTimeout
+        } else {
+            UrlDecodingError::BodyError(BodyError {
+                detail: "Can't read request body".to_owned(),
+                cause: BodyErrorCause::IoError(err)
+            })
+        })?;
+    let body = String::from_utf8(bytes).map_err(|err| UrlDecodingError::BodyError(BodyError {
+        detail: "Invalid UTF-8 sequence".to_owned(),
+        cause: BodyErrorCause::Utf8Error(err.utf8_error())
+    }))?;
+
+    req.extensions.insert::<bodyparser::Raw>(Some(body.clone()));
+    Ok(body)
+}
+
+#[test]
+fn test_timed_reader() {
+    use std::thread;
+
+    struct Trickle(usize);
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            thread::sleep(Duration::from_millis(5));
+            if self.0 == 0 || buf.is_empty() {
+                return Ok(0);
+            }
+            self.0 -= 1;
+            buf[0] = b'a';
+            Ok(1)
+        }
+    }
+
+    let mut bytes = Vec::new();
+    TimedReader::new(Trickle(3), Duration::from_secs(5)).read_to_end(&mut bytes).unwrap();
+    assert_eq!(bytes, b"aaa");
+
+    let err = TimedReader::new(Trickle(1000), Duration::from_millis(30)).read_to_end(&mut bytes).unwrap_err();
+    assert!(is_timeout(&err));
+}
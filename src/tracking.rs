@@ -0,0 +1,123 @@
+//! Removing tracking parameters such as `utm_source` from URLs, so cached
+//! pages and analytics aren't split by the campaign links came from.
+
+#[cfg(feature = "iron")]
+use iron::prelude::*;
+#[cfg(feature = "iron")]
+use iron::BeforeMiddleware;
+
+use super::QueryMap;
+use super::encode::decode_component;
+#[cfg(feature = "iron")]
+use super::{RawQuery, UrlEncodedQuery};
+
+/// The parameters analytics and ad platforms add to links: Google Analytics'
+/// `utm_*` and `_ga`, and the click identifiers of Google, Meta, Microsoft,
+/// Mailchimp, Instagram and Yandex.
+pub const TRACKING_PARAMS: &[&str] = &["utm_*", "_ga", "gclid", "dclid", "gbraid", "wbraid", "fbclid", "msclkid",
+                                       "mc_cid", "mc_eid", "igshid", "yclid"];
+
+/// Whether `key` matches `pattern`, where `*` stands for any characters.
+pub fn glob_match(pattern: &str, key: &str) -> bool {
+    match pattern.find('*') {
+        None => pattern == key,
+        Some(star) => {
+            let (prefix, rest) = (&pattern[..star], &pattern[star + 1..]);
+            key.starts_with(prefix) && (star..=key.len())
+                .any(|at| key.is_char_boundary(at) && glob_match(rest, &key[at..]))
+        }
+    }
+}
+
+fn is_stripped(key: &str, patterns: &[&str]) -> bool {
+    patterns.iter().any(|pattern| glob_match(pattern, key))
+}
+
+/// Remove the keys of `params` matching one of `patterns`, such as
+/// `TRACKING_PARAMS`, where `*` stands for any characters. Returns how many
+/// keys were removed.
+pub fn strip_params(params: &mut QueryMap, patterns: &[&str]) -> usize {
+    let before = params.len();
+    params.retain(|key, _| !is_stripped(key, patterns));
+    before - params.len()
+}
+
+/// Remove the pairs of a still encoded query whose key matches one of
+/// `patterns`, leaving the others as they were written.
+pub fn strip_query(query: &str, patterns: &[&str]) -> String {
+    query.split('&')
+        .filter(|piece| !piece.is_empty())
+        .filter(|piece| !is_stripped(&decode_component(piece.split('=').next().unwrap_or("")), patterns))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// `BeforeMiddleware` removing tracking parameters from the query of
+/// incoming requests, so that `UrlEncodedQuery`, `RawQuery` and whatever
+/// reads `req.url` afterwards never see them. A query left empty is removed.
+///
+/// ```ignore
+/// chain.link_before(StripTracking::new().also(&["ref"]));
+/// ```
+#[cfg(feature = "iron")]
+#[derive(Clone, Debug)]
+pub struct StripTracking {
+    patterns: Vec<String>
+}
+
+#[cfg(feature = "iron")]
+impl Default for StripTracking {
+    fn default() -> StripTracking {
+        StripTracking { patterns: TRACKING_PARAMS.iter().map(|pattern| (*pattern).to_owned()).collect() }
+    }
+}
+
+#[cfg(feature = "iron")]
+impl StripTracking {
+    /// A middleware removing `TRACKING_PARAMS`.
+    pub fn new() -> StripTracking {
+        StripTracking::default()
+    }
+
+    /// Remove `patterns` instead of `TRACKING_PARAMS`.
+    pub fn patterns(mut self, patterns: &[&str]) -> StripTracking {
+        self.patterns = patterns.iter().map(|pattern| (*pattern).to_owned()).collect();
+        self
+    }
+
+    /// Also remove `patterns`.
+    pub fn also(mut self, patterns: &[&str]) -> StripTracking {
+        self.patterns.extend(patterns.iter().map(|pattern| (*pattern).to_owned()));
+        self
+    }
+}
+
+#[cfg(feature = "iron")]
+impl BeforeMiddleware for StripTracking {
+    fn before(&self, req: &mut Request) -> IronResult<()> {
+        let patterns: Vec<&str> = self.patterns.iter().map(|pattern| &**pattern).collect();
+        if let Some(query) = req.url.query.take() {
+            let query = strip_query(&query, &patterns);
+            req.url.query = if query.is_empty() { None } else { Some(query) };
+        }
+        req.extensions.remove::<UrlEncodedQuery>();
+        req.extensions.remove::<RawQuery>();
+        Ok(())
+    }
+}
+
+#[test]
+fn test_strip_params() {
+    use super::{parse_with, ParseOptions};
+
+    assert!(glob_match("utm_*", "utm_source") && glob_match("utm_*", "utm_"));
+    assert!(glob_match("*_id", "user_id") && glob_match("a*b*c", "aXbYc") && !glob_match("a*b*c", "acb"));
+    assert!(!glob_match("utm_*", "xutm_source") && !glob_match("gclid", "gclid2"));
+
+    let query = "utm_source=news&id=7&fbclid=abc&utm%5Fmedium=mail&page=2";
+    let mut params = parse_with(query, &ParseOptions::new()).unwrap();
+    assert_eq!(strip_params(&mut params, TRACKING_PARAMS), 3);
+    assert_eq!(params.len(), 2);
+    assert_eq!(strip_query(query, TRACKING_PARAMS), "id=7&page=2");
+    assert_eq!(strip_query("gclid=1", TRACKING_PARAMS), "");
+}
@@ -0,0 +1,162 @@
+//! Normalizations applied to parameter values as they are parsed.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use super::QueryMap;
+use super::values::fold_digits;
+
+/// A normalization applied to every value of a parameter.
+#[derive(Clone)]
+pub enum Transform {
+    /// Strip leading and trailing whitespace.
+    Trim,
+    /// Convert to lowercase.
+    Lowercase,
+    /// Convert to uppercase.
+    Uppercase,
+    /// Drop everything but ASCII digits, as for phone or card numbers.
+    DigitsOnly,
+    /// Replace every run of whitespace inside the value with a single space,
+    /// as for names typed with a double space. Combine with `Trim` to also
+    /// strip the ends.
+    CollapseWhitespace,
+    /// Fold full-width and other lookalike digits and signs to ASCII with
+    /// `fold_digits`, for numeric fields filled in with an input method.
+    FoldDigits,
+    /// Any other normalization.
+    Custom(Arc<dyn Fn(&str) -> String + Send + Sync>)
+}
+
+impl Transform {
+    /// A transform running `f` on each value.
+    pub fn custom<F>(f: F) -> Transform
+    where F: Fn(&str) -> String + Send + Sync + 'static {
+        Transform::Custom(Arc::new(f))
+    }
+
+    /// Apply the transform to a single value.
+    pub fn apply(&self, value: &str) -> String {
+        match *self {
+            Transform::Trim => value.trim().to_owned(),
+            Transform::Lowercase => value.to_lowercase(),
+            Transform::Uppercase => value.to_uppercase(),
+            Transform::DigitsOnly => value.chars().filter(|c| c.is_ascii_digit()).collect(),
+            Transform::CollapseWhitespace => collapse_whitespace(value),
+            Transform::FoldDigits => fold_digits(value),
+            Transform::Custom(ref f) => f(value)
+        }
+    }
+}
+
+impl fmt::Debug for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Transform::Trim => "Trim",
+            Transform::Lowercase => "Lowercase",
+            Transform::Uppercase => "Uppercase",
+            Transform::DigitsOnly => "DigitsOnly",
+            Transform::CollapseWhitespace => "CollapseWhitespace",
+            Transform::FoldDigits => "FoldDigits",
+            Transform::Custom(_) => "Custom"
+        })
+    }
+}
+
+/// Replace the whitespace runs of `value` with a single space, keeping a
+/// leading or trailing one.
+fn collapse_whitespace(value: &str) -> String {
+    let mut collapsed = String::with_capacity(value.len());
+    let mut in_space = false;
+    for c in value.chars() {
+        if c.is_whitespace() {
+            if !in_space {
+                collapsed.push(' ');
+            }
+            in_space = true;
+        } else {
+            collapsed.push(c);
+            in_space = false;
+        }
+    }
+    collapsed
+}
+
+/// The transforms registered for each parameter, applied in registration
+/// order, after the ones registered for every parameter.
+#[derive(Clone, Debug, Default)]
+pub struct Transforms {
+    all: Vec<Transform>,
+    by_key: HashMap<String, Vec<Transform>>
+}
+
+impl Transforms {
+    /// No transforms at all.
+    pub fn new() -> Transforms {
+        Transforms::default()
+    }
+
+    /// Run `transform` on every value of `key`, after the transforms already
+    /// registered for it.
+    pub fn add(&mut self, key: &str, transform: Transform) {
+        self.by_key.entry(key.to_owned()).or_default().push(transform);
+    }
+
+    /// Run `transform` on every value of every key, before the transforms
+    /// registered for the key, after the ones already registered for all.
+    pub fn add_all(&mut self, transform: Transform) {
+        self.all.push(transform);
+    }
+
+    /// Whether no transform is registered.
+    pub fn is_empty(&self) -> bool {
+        self.all.is_empty() && self.by_key.is_empty()
+    }
+
+    /// Transform the values of `params` in place.
+    pub fn apply(&self, params: &mut QueryMap) {
+        if !self.all.is_empty() {
+            for value in params.values_mut().flatten() {
+                *value = self.all.iter().fold(value.clone(), |value, t| t.apply(&value));
+            }
+        }
+        for (key, transforms) in &self.by_key {
+            if let Some(values) = params.get_mut(key) {
+                for value in values.iter_mut() {
+                    *value = transforms.iter().fold(value.clone(), |value, t| t.apply(&value));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_transforms() {
+    let mut transforms = Transforms::new();
+    transforms.add("email", Transform::Trim);
+    transforms.add("email", Transform::Lowercase);
+    transforms.add("phone", Transform::DigitsOnly);
+    transforms.add("code", Transform::custom(|value| value.replace('-', "")));
+
+    let mut params = QueryMap::new();
+    params.insert("email".to_owned(), vec!["  Jane@Example.COM ".to_owned()]);
+    params.insert("phone".to_owned(), vec!["+1 (555) 010-9999".to_owned()]);
+    params.insert("code".to_owned(), vec!["AB-12".to_owned(), "CD-34".to_owned()]);
+    params.insert("other".to_owned(), vec![" As Is ".to_owned()]);
+    transforms.apply(&mut params);
+
+    assert_eq!(params["email"], vec!["jane@example.com".to_owned()]);
+    assert_eq!(params["phone"], vec!["15550109999".to_owned()]);
+    assert_eq!(params["code"], vec!["AB12".to_owned(), "CD34".to_owned()]);
+    assert_eq!(params["other"], vec![" As Is ".to_owned()]);
+
+    transforms.add_all(Transform::Trim);
+    transforms.add_all(Transform::CollapseWhitespace);
+    params.insert("name".to_owned(), vec!["\tAnn \n  Lee  ".to_owned()]);
+    params.insert("code".to_owned(), vec![" AB-12 ".to_owned()]);
+    transforms.apply(&mut params);
+    assert_eq!(params["name"], vec!["Ann Lee".to_owned()]);
+    assert_eq!(params["code"], vec!["AB12".to_owned()]);
+    assert_eq!(Transform::CollapseWhitespace.apply("  a  b "), " a b ");
+}
@@ -0,0 +1,724 @@
+//! Parsing and validation of single parameter values, behind the typed
+//! accessors of `QueryMapExt`.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::Hash;
+use std::str::FromStr;
+use std::time::Duration;
+
+use rustc_serialize::base64::FromBase64;
+
+use url::{punycode, Url};
+
+use super::UrlDecodingError;
+
+/// The base64 alphabets accepted by `QueryMapExt::get_base64`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Base64Alphabet {
+    /// `+` and `/`, as in RFC 4648 section 4.
+    Standard,
+    /// `-` and `_`, as in RFC 4648 section 5.
+    UrlSafe
+}
+
+fn invalid(key: &str, message: &str) -> UrlDecodingError {
+    UrlDecodingError::InvalidParameter { key: key.to_owned(), message: message.to_owned() }
+}
+
+/// Decode a base64 value of at most `max_len` bytes once decoded.
+///
+/// Padding is optional. In the standard alphabet spaces are read as `+`,
+/// since an unescaped `+` in form data already decoded to a space.
+pub fn parse_base64(key: &str, value: &str, alphabet: Base64Alphabet, max_len: usize)
+                    -> Result<Vec<u8>, UrlDecodingError> {
+    let value = value.trim_end_matches('=');
+    if value.len() / 4 * 3 > max_len {
+        return Err(UrlDecodingError::ValueTooLong { key: key.to_owned(), limit: max_len });
+    }
+
+    let value = match alphabet {
+        Base64Alphabet::Standard => value.replace(' ', "+"),
+        Base64Alphabet::UrlSafe => value.to_owned()
+    };
+    let valid = value.bytes().all(|byte| match byte {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => true,
+        b'+' | b'/' => alphabet == Base64Alphabet::Standard,
+        b'-' | b'_' => alphabet == Base64Alphabet::UrlSafe,
+        _ => false
+    });
+    if !valid {
+        return Err(invalid(key, "is not valid base64"));
+    }
+
+    let bytes = value.from_base64().map_err(|_| invalid(key, "is not valid base64"))?;
+    if bytes.len() > max_len {
+        return Err(UrlDecodingError::ValueTooLong { key: key.to_owned(), limit: max_len });
+    }
+
+    Ok(bytes)
+}
+
+/// A type taking one of a fixed set of values, for `QueryMapExt::get_enum`.
+///
+/// ```ignore
+/// impl ParamEnum for Status {
+///     fn allowed_values() -> &'static [&'static str] { &["active", "archived"] }
+/// }
+/// ```
+pub trait ParamEnum: FromStr {
+    /// The values `from_str` accepts, as listed in error messages.
+    fn allowed_values() -> &'static [&'static str];
+}
+
+/// Parse one of the values of a `ParamEnum`, listing them all on failure.
+pub fn parse_enum<T: ParamEnum>(key: &str, value: &str) -> Result<T, UrlDecodingError> {
+    value.parse().map_err(|_| {
+        let message = format!("must be one of: {}", T::allowed_values().join(", "));
+        invalid(key, &message)
+    })
+}
+
+/// How `QueryMapExt::get_number` reads numbers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NumberFormat {
+    /// Only what `FromStr` accepts, such as `1234.56`.
+    Strict,
+    /// A decimal point, with thousands optionally grouped by `,` or spaces:
+    /// `1,234.56`.
+    DecimalPoint,
+    /// A decimal comma, with thousands optionally grouped by `.`, `'` or
+    /// spaces: `1.234,56`.
+    DecimalComma
+}
+
+/// Fold the characters numbers are commonly typed with on other keyboards to
+/// ASCII: full-width digits and signs, as East Asian input methods produce,
+/// Arabic-Indic digits, the Unicode minus sign and the ideographic space.
+/// Other characters are kept.
+pub fn fold_digits(value: &str) -> String {
+    value.chars().map(|c| match c {
+        '\u{ff10}'..='\u{ff19}' => char::from(b'0' + (c as u32 - 0xff10) as u8),
+        '\u{0660}'..='\u{0669}' => char::from(b'0' + (c as u32 - 0x0660) as u8),
+        '\u{06f0}'..='\u{06f9}' => char::from(b'0' + (c as u32 - 0x06f0) as u8),
+        '\u{ff0b}' => '+',
+        '\u{ff0d}' | '\u{2212}' | '\u{2013}' | '\u{fe63}' => '-',
+        '\u{ff0e}' | '\u{3002}' => '.',
+        '\u{ff0c}' | '\u{3001}' => ',',
+        '\u{ff07}' => '\'',
+        '\u{3000}' => ' ',
+        _ => c
+    }).collect()
+}
+
+/// Rewrite a localized number to the form `FromStr` expects, checking that
+/// grouping separators only ever split the integer part into groups of three.
+fn normalize_number(value: &str, format: NumberFormat) -> Option<String> {
+    let (decimal, is_group): (char, fn(char) -> bool) = match format {
+        NumberFormat::Strict => return Some(value.to_owned()),
+        NumberFormat::DecimalPoint => ('.', |c| c == ',' || c == ' ' || c == '\u{a0}' || c == '\u{202f}'),
+        NumberFormat::DecimalComma =>
+            (',', |c| c == '.' || c == '\'' || c == ' ' || c == '\u{a0}' || c == '\u{202f}')
+    };
+
+    let value = value.trim();
+    let (sign, unsigned) = match value.chars().next() {
+        Some(c @ '-') | Some(c @ '+') => (Some(c), &value[1..]),
+        _ => (None, value)
+    };
+    let (integer, fraction) = match unsigned.find(decimal) {
+        Some(position) => (&unsigned[..position], Some(&unsigned[position + 1..])),
+        None => (unsigned, None)
+    };
+
+    let groups: Vec<&str> = integer.split(is_group).collect();
+    let grouped_correctly = groups.len() == 1 || groups.iter().enumerate().all(|(i, group)| {
+        let digits = group.chars().count();
+        if i == 0 { (1..=3).contains(&digits) } else { digits == 3 }
+    });
+    if !grouped_correctly {
+        return None;
+    }
+
+    let mut normalized: String = sign.into_iter().collect();
+    normalized.extend(groups);
+    if let Some(fraction) = fraction {
+        normalized.push('.');
+        normalized.push_str(fraction);
+    }
+    Some(normalized)
+}
+
+/// Parse a number written in the given format.
+pub fn parse_number<T: FromStr>(key: &str, value: &str, format: NumberFormat)
+                                -> Result<T, UrlDecodingError> {
+    normalize_number(value, format)
+        .and_then(|normalized| normalized.parse().ok())
+        .ok_or_else(|| invalid(key, "is not a valid number"))
+}
+
+/// Parse every value of a checkbox group into a set.
+///
+/// Each value must be listed in `allowed`, if given, and may only be sent once.
+pub fn parse_set<T>(key: &str, values: &[String], allowed: Option<&[&str]>)
+                    -> Result<HashSet<T>, UrlDecodingError>
+where T: FromStr + Hash + Eq {
+    let mut set = HashSet::with_capacity(values.len());
+
+    for value in values {
+        if let Some(allowed) = allowed {
+            if !allowed.contains(&&**value) {
+                let message = format!("must only contain: {}", allowed.join(", "));
+                return Err(invalid(key, &message));
+            }
+        }
+
+        let parsed = value.parse()
+            .map_err(|_| invalid(key, &format!("contains an invalid value `{}`", value)))?;
+        if !set.insert(parsed) {
+            return Err(invalid(key, &format!("contains `{}` more than once", value)));
+        }
+    }
+
+    Ok(set)
+}
+
+/// Parse every value of a bulk operation's ids, keeping the first occurrence
+/// of each in order. Every value that fails to parse is listed in the error,
+/// and at most `max` distinct ids are accepted.
+pub fn parse_ids<T>(key: &str, values: &[String], max: usize) -> Result<Vec<T>, UrlDecodingError>
+where T: FromStr + Hash + Eq + Clone {
+    let mut seen = HashSet::with_capacity(values.len());
+    let mut ids = Vec::with_capacity(values.len());
+    let mut failed = Vec::new();
+
+    for value in values {
+        match value.parse::<T>() {
+            Ok(id) => if seen.insert(id.clone()) {
+                ids.push(id);
+            },
+            Err(_) => failed.push(format!("`{}`", value))
+        }
+    }
+
+    if !failed.is_empty() {
+        return Err(invalid(key, &format!("contains invalid values: {}", failed.join(", "))));
+    }
+    if ids.len() > max {
+        return Err(invalid(key, &format!("must have at most {} values", max)));
+    }
+    Ok(ids)
+}
+
+fn hex_digit(byte: u8) -> Option<u8> {
+    match byte {
+        b'0'..=b'9' => Some(byte - b'0'),
+        b'a'..=b'f' => Some(byte - b'a' + 10),
+        b'A'..=b'F' => Some(byte - b'A' + 10),
+        _ => None
+    }
+}
+
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+    if !value.len().is_multiple_of(2) {
+        return None;
+    }
+    value.as_bytes().chunks(2)
+        .map(|pair| Some(hex_digit(pair[0])? << 4 | hex_digit(pair[1])?))
+        .collect()
+}
+
+/// Decode a hex value of exactly `expected_len` bytes, such as a signature
+/// or a digest, in either case.
+pub fn parse_hex(key: &str, value: &str, expected_len: usize) -> Result<Vec<u8>, UrlDecodingError> {
+    if value.len() != expected_len * 2 {
+        return Err(invalid(key, &format!("must be {} hex digits long", expected_len * 2)));
+    }
+    decode_hex(value).ok_or_else(|| invalid(key, "may only contain hex digits"))
+}
+
+/// An sRGB color, as sent by `<input type="color">`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Color {
+    /// The red component.
+    pub red: u8,
+    /// The green component.
+    pub green: u8,
+    /// The blue component.
+    pub blue: u8,
+    /// The opacity, 255 unless the color was written with 4 or 8 digits.
+    pub alpha: u8
+}
+
+/// Formats as `#rrggbb`, or `#rrggbbaa` for translucent colors.
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.red, self.green, self.blue)?;
+        if self.alpha != 255 {
+            write!(f, "{:02x}", self.alpha)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse a CSS hex color: `#` followed by 3, 4, 6 or 8 hex digits.
+pub fn parse_color(key: &str, value: &str) -> Result<Color, UrlDecodingError> {
+    let digits = value.strip_prefix('#').filter(|digits| matches!(digits.len(), 3 | 4 | 6 | 8));
+    let expanded = match digits {
+        Some(digits) if digits.len() <= 4 => digits.chars().flat_map(|c| vec![c, c]).collect(),
+        Some(digits) => digits.to_owned(),
+        None => return Err(invalid(key, "is not a hex color such as #ff8800"))
+    };
+    match decode_hex(&expanded).as_deref() {
+        Some(&[red, green, blue]) => Ok(Color { red, green, blue, alpha: 255 }),
+        Some(&[red, green, blue, alpha]) => Ok(Color { red, green, blue, alpha }),
+        _ => Err(invalid(key, "is not a hex color such as #ff8800"))
+    }
+}
+
+/// A JSON Web Token whose structure has been checked, but not its signature.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JwtParts {
+    /// The token as it was sent.
+    pub token: String,
+    /// The decoded JOSE header, a JSON object.
+    pub header: Vec<u8>,
+    /// The decoded claims.
+    pub payload: Vec<u8>,
+    /// The decoded signature. Empty for unsecured tokens.
+    pub signature: Vec<u8>
+}
+
+impl JwtParts {
+    /// The `header.payload` part of the token, which the signature covers.
+    pub fn signing_input(&self) -> &str {
+        match self.token.rfind('.') {
+            Some(position) => &self.token[..position],
+            None => &self.token
+        }
+    }
+}
+
+/// Check that a value is a compact JWT of at most `max_len` bytes: three
+/// unpadded base64url segments, the first of which decodes to a JSON object.
+pub fn parse_jwt(key: &str, value: &str, max_len: usize) -> Result<JwtParts, UrlDecodingError> {
+    if value.len() > max_len {
+        return Err(UrlDecodingError::ValueTooLong { key: key.to_owned(), limit: max_len });
+    }
+
+    let segments: Vec<&str> = value.split('.').collect();
+    if segments.len() != 3 || segments[0].is_empty() || segments[1].is_empty() ||
+       value.contains('=') {
+        return Err(invalid(key, "is not a JSON Web Token"));
+    }
+
+    let decode = |segment| parse_base64(key, segment, Base64Alphabet::UrlSafe, max_len)
+        .map_err(|_| invalid(key, "is not a JSON Web Token"));
+    let header = decode(segments[0])?;
+    if header.first() != Some(&b'{') {
+        return Err(invalid(key, "is not a JSON Web Token"));
+    }
+
+    Ok(JwtParts {
+        token: value.to_owned(),
+        header,
+        payload: decode(segments[1])?,
+        signature: decode(segments[2])?
+    })
+}
+
+/// How `QueryMapExt::get_email` normalizes the addresses it accepts.
+///
+/// By default addresses are only trimmed.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EmailOptions {
+    lowercase: bool,
+    idn_to_ascii: bool
+}
+
+impl EmailOptions {
+    /// Options that only trim addresses.
+    pub fn new() -> EmailOptions {
+        EmailOptions::default()
+    }
+
+    /// Lowercase the whole address. Strictly speaking only the domain is case
+    /// insensitive, but practically every mail provider ignores case.
+    pub fn lowercase(mut self, enabled: bool) -> EmailOptions {
+        self.lowercase = enabled;
+        self
+    }
+
+    /// Convert internationalized domain names to their `xn--` ASCII form.
+    pub fn idn_to_ascii(mut self, enabled: bool) -> EmailOptions {
+        self.idn_to_ascii = enabled;
+        self
+    }
+}
+
+/// Check that a value looks like a deliverable email address, and normalize it.
+///
+/// The check is pragmatic rather than a full RFC 5322 parser: quoted local
+/// parts, comments and IP literals are rejected, and the domain needs a dot.
+pub fn parse_email(key: &str, value: &str, options: EmailOptions) -> Result<String, UrlDecodingError> {
+    let value = value.trim();
+    let (local, domain) = match value.find('@') {
+        Some(position) if !value[position + 1..].contains('@') =>
+            (&value[..position], &value[position + 1..]),
+        _ => return Err(invalid(key, "must contain a single `@`"))
+    };
+
+    let local_char = |c: char| c.is_alphanumeric() || "!#$%&'*+/=?^_`{|}~-.".contains(c);
+    if local.is_empty() || local.len() > 64 || !local.chars().all(local_char) ||
+       local.starts_with('.') || local.ends_with('.') || local.contains("..") {
+        return Err(invalid(key, "has an invalid part before the `@`"));
+    }
+
+    let label_char = |c: char| c.is_alphanumeric() || c == '-';
+    let labels: Vec<&str> = domain.split('.').collect();
+    let valid_domain = domain.len() <= 253 && labels.len() >= 2 &&
+        labels.iter().all(|label| {
+            !label.is_empty() && label.len() <= 63 && label.chars().all(label_char) &&
+            !label.starts_with('-') && !label.ends_with('-')
+        }) &&
+        !labels[labels.len() - 1].chars().all(|c| c.is_ascii_digit());
+    if !valid_domain {
+        return Err(invalid(key, "has an invalid domain"));
+    }
+
+    let mut domain = if options.lowercase { domain.to_lowercase() } else { domain.to_owned() };
+    if options.idn_to_ascii && !domain.is_ascii() {
+        let ascii: Option<Vec<String>> = domain.split('.').map(|label| {
+            if label.is_ascii() {
+                Some(label.to_owned())
+            } else {
+                punycode::encode_str(&label.to_lowercase()).map(|encoded| format!("xn--{}", encoded))
+            }
+        }).collect();
+        domain = match ascii {
+            Some(labels) => labels.join("."),
+            None => return Err(invalid(key, "has an invalid domain"))
+        };
+    }
+
+    let local = if options.lowercase { local.to_lowercase() } else { local.to_owned() };
+    let address = format!("{}@{}", local, domain);
+    if address.len() > 254 {
+        return Err(UrlDecodingError::ValueTooLong { key: key.to_owned(), limit: 254 });
+    }
+
+    Ok(address)
+}
+
+/// Which URLs `QueryMapExt::get_url` accepts. By default any absolute URL.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UrlOptions {
+    schemes: Option<Vec<String>>,
+    hosts: Option<Vec<String>>
+}
+
+impl UrlOptions {
+    /// Options accepting any absolute URL.
+    pub fn new() -> UrlOptions {
+        UrlOptions::default()
+    }
+
+    /// Only accept URLs with one of these schemes, such as `["https"]`.
+    pub fn schemes(mut self, schemes: &[&str]) -> UrlOptions {
+        self.schemes = Some(schemes.iter().map(|scheme| scheme.to_lowercase()).collect());
+        self
+    }
+
+    /// Only accept URLs pointing to one of these hosts. `*.example.com` allows
+    /// every subdomain of `example.com`, but not `example.com` itself.
+    ///
+    /// Restricting hosts is what keeps a `redirect_uri` or `next` parameter
+    /// from turning into an open redirect.
+    pub fn hosts(mut self, hosts: &[&str]) -> UrlOptions {
+        self.hosts = Some(hosts.iter().map(|host| host.to_lowercase()).collect());
+        self
+    }
+}
+
+/// Parse an absolute URL and check it against `options`.
+pub fn parse_url(key: &str, value: &str, options: &UrlOptions) -> Result<Url, UrlDecodingError> {
+    let url = Url::parse(value).map_err(|_| invalid(key, "is not a valid URL"))?;
+
+    if let Some(ref schemes) = options.schemes {
+        if !schemes.contains(&url.scheme) {
+            let message = format!("must use one of the schemes: {}", schemes.join(", "));
+            return Err(invalid(key, &message));
+        }
+    }
+
+    if let Some(ref hosts) = options.hosts {
+        let host = url.serialize_host().unwrap_or_default().to_lowercase();
+        let allowed = hosts.iter().any(|pattern| match pattern.strip_prefix("*.") {
+            Some(parent) => host.ends_with(&format!(".{}", parent)),
+            None => *pattern == host
+        });
+        if !allowed {
+            return Err(invalid(key, "points to a host that is not allowed"));
+        }
+    }
+
+    Ok(url)
+}
+
+/// The units `parse_duration` understands, with their length in milliseconds.
+const DURATION_UNITS: &[(&str, u64)] = &[("ms", 1), ("s", 1_000), ("m", 60_000), ("h", 3_600_000),
+                                        ("d", 86_400_000), ("w", 604_800_000)];
+
+/// Which durations `QueryMapExt::get_duration` accepts. By default any
+/// duration written with units, such as `30s` or `1h30m`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DurationOptions {
+    units: Vec<String>,
+    max: Option<Duration>,
+    bare_unit: Option<String>
+}
+
+impl Default for DurationOptions {
+    fn default() -> DurationOptions {
+        DurationOptions {
+            units: DURATION_UNITS.iter().map(|&(unit, _)| unit.to_owned()).collect(),
+            max: None,
+            bare_unit: None
+        }
+    }
+}
+
+impl DurationOptions {
+    /// Options accepting every unit: `ms`, `s`, `m`, `h`, `d` and `w`.
+    pub fn new() -> DurationOptions {
+        DurationOptions::default()
+    }
+
+    /// Only accept these units, such as `["s", "m"]` for a timeout.
+    pub fn units(mut self, units: &[&str]) -> DurationOptions {
+        self.units = units.iter().map(|unit| (*unit).to_owned()).collect();
+        self
+    }
+
+    /// Refuse durations longer than `max`.
+    pub fn max(mut self, max: Duration) -> DurationOptions {
+        self.max = Some(max);
+        self
+    }
+
+    /// Read numbers without a unit, such as `30`, in `unit`.
+    pub fn bare_unit(mut self, unit: &str) -> DurationOptions {
+        self.bare_unit = Some(unit.to_owned());
+        self
+    }
+}
+
+/// Parse a duration made of whole numbers followed by their unit, such as
+/// `500ms`, `30s` or `1h30m`, and check it against `options`.
+pub fn parse_duration(key: &str, value: &str, options: &DurationOptions) -> Result<Duration, UrlDecodingError> {
+    let malformed = || {
+        let message = format!("is not a duration such as 30{}", options.units.first().map_or("s", |unit| &**unit));
+        invalid(key, &message)
+    };
+
+    let value = value.trim();
+    let bare = options.bare_unit.as_ref().filter(|_| !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()));
+    let value = match bare {
+        Some(unit) => format!("{}{}", value, unit),
+        None => value.to_owned()
+    };
+    if value.is_empty() {
+        return Err(malformed());
+    }
+
+    let mut millis: u64 = 0;
+    let mut rest = &value[..];
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        let letters = rest[digits..].find(|c: char| !c.is_ascii_alphabetic()).map_or(rest.len(), |end| digits + end);
+        let (number, unit) = (&rest[..digits], &rest[digits..letters]);
+        if number.is_empty() || unit.is_empty() {
+            return Err(malformed());
+        }
+        if !options.units.iter().any(|allowed| allowed == unit) {
+            return Err(invalid(key, &format!("must use one of the units: {}", options.units.join(", "))));
+        }
+
+        let scale = DURATION_UNITS.iter().find(|&&(name, _)| name == unit).map(|&(_, scale)| scale)
+            .ok_or_else(malformed)?;
+        millis = number.parse::<u64>().ok()
+            .and_then(|number| number.checked_mul(scale))
+            .and_then(|part| millis.checked_add(part))
+            .ok_or_else(|| invalid(key, "is too long"))?;
+        rest = &rest[letters..];
+    }
+
+    let duration = Duration::from_millis(millis);
+    if let Some(max) = options.max {
+        if duration > max {
+            return Err(invalid(key, &format!("must be at most {}s", max.as_secs())));
+        }
+    }
+    Ok(duration)
+}
+
+#[test]
+fn test_fold_digits() {
+    assert_eq!(fold_digits("\u{ff11}\u{ff12}\u{ff13}\u{ff0e}\u{ff15}"), "123.5");
+    assert_eq!(fold_digits("\u{2212}\u{0664}\u{0662} \u{ff41}"), "-42 \u{ff41}");
+    assert_eq!(parse_number::<f64>("n", &fold_digits("\u{ff11}\u{ff0c}\u{ff12}\u{ff13}\u{ff14}"),
+                                   NumberFormat::DecimalPoint).unwrap(), 1234.0);
+}
+
+#[test]
+fn test_parse_base64() {
+    use self::Base64Alphabet::*;
+
+    assert_eq!(parse_base64("k", "aGk/Pz8=", Standard, 100).unwrap(), b"hi???".to_vec());
+    assert_eq!(parse_base64("k", "aGk/Pz8", Standard, 100).unwrap(), b"hi???".to_vec());
+    assert_eq!(parse_base64("k", "aGk_Pz8", UrlSafe, 100).unwrap(), b"hi???".to_vec());
+    assert_eq!(parse_base64("k", "+/8", Standard, 100).unwrap(), vec![0xfb, 0xff]);
+    assert_eq!(parse_base64("k", " /8", Standard, 100).unwrap(), vec![0xfb, 0xff]);
+    assert!(parse_base64("k", "aGk_Pz8", Standard, 100).is_err());
+    assert!(parse_base64("k", "aGk/Pz8", UrlSafe, 100).is_err());
+    assert!(parse_base64("k", "aGk/Pz8=", Standard, 4).is_err());
+    assert!(parse_base64("k", "a", Standard, 100).is_err());
+}
+
+#[test]
+fn test_parse_hex() {
+    assert_eq!(parse_hex("sig", "00fFa1", 3).unwrap(), vec![0x00, 0xff, 0xa1]);
+    assert!(parse_hex("sig", "00ff", 3).is_err());
+    assert!(parse_hex("sig", "00ffg1", 3).is_err());
+    assert!(parse_hex("sig", "+0ffa1", 3).is_err());
+
+    let orange = Color { red: 0xff, green: 0x88, blue: 0x00, alpha: 255 };
+    assert_eq!(parse_color("accent", "#FF8800").unwrap(), orange);
+    assert_eq!(parse_color("accent", "#f80").unwrap(), orange);
+    assert_eq!(parse_color("accent", "#f808").unwrap().to_string(), "#ff880088");
+    assert_eq!(orange.to_string(), "#ff8800");
+    assert!(parse_color("accent", "ff8800").is_err());
+    assert!(parse_color("accent", "#ff880").is_err());
+    assert!(parse_color("accent", "#gg8800").is_err());
+}
+
+#[test]
+fn test_parse_duration() {
+    let any = DurationOptions::new();
+    assert_eq!(parse_duration("timeout", "30s", &any).unwrap(), Duration::from_secs(30));
+    assert_eq!(parse_duration("window", "1h30m", &any).unwrap(), Duration::from_secs(5400));
+    assert_eq!(parse_duration("poll", "250ms", &any).unwrap(), Duration::from_millis(250));
+    assert!(parse_duration("timeout", "30", &any).is_err());
+    assert!(parse_duration("timeout", "s", &any).is_err());
+    assert!(parse_duration("timeout", "1.5h", &any).is_err());
+    assert!(parse_duration("timeout", "3y", &any).is_err());
+    assert!(parse_duration("timeout", "99999999999999999999d", &any).is_err());
+
+    let timeout = DurationOptions::new().units(&["s", "m"]).max(Duration::from_secs(300)).bare_unit("s");
+    assert_eq!(parse_duration("timeout", "45", &timeout).unwrap(), Duration::from_secs(45));
+    assert_eq!(parse_duration("timeout", "5m", &timeout).unwrap(), Duration::from_secs(300));
+    assert!(parse_duration("timeout", "6m", &timeout).is_err());
+    assert!(parse_duration("timeout", "1h", &timeout).is_err());
+}
+
+#[test]
+fn test_parse_jwt() {
+    let token = "eyJhbGciOiJIUzI1NiJ9.eyJzdWIiOiIxIn0.c2ln";
+    let jwt = parse_jwt("token", token, 100).unwrap();
+    assert_eq!(jwt.header, br#"{"alg":"HS256"}"#.to_vec());
+    assert_eq!(jwt.payload, br#"{"sub":"1"}"#.to_vec());
+    assert_eq!(jwt.signature, b"sig".to_vec());
+    assert_eq!(jwt.signing_input(), "eyJhbGciOiJIUzI1NiJ9.eyJzdWIiOiIxIn0");
+
+    assert!(parse_jwt("token", token, 20).is_err());
+    assert!(parse_jwt("token", "eyJhbGciOiJIUzI1NiJ9.eyJzdWIiOiIxIn0", 100).is_err());
+    assert!(parse_jwt("token", "bm90IGpzb24.eyJzdWIiOiIxIn0.c2ln", 100).is_err());
+    assert!(parse_jwt("token", "eyJhbGciOiJIUzI1NiJ9.eyJzdWIiOiIxIn0=.c2ln", 100).is_err());
+}
+
+#[test]
+fn test_parse_enum() {
+    #[derive(Debug, PartialEq)]
+    enum Status { Active, Archived }
+
+    impl FromStr for Status {
+        type Err = ();
+        fn from_str(s: &str) -> Result<Status, ()> {
+            match s {
+                "active" => Ok(Status::Active),
+                "archived" => Ok(Status::Archived),
+                _ => Err(())
+            }
+        }
+    }
+
+    impl ParamEnum for Status {
+        fn allowed_values() -> &'static [&'static str] { &["active", "archived"] }
+    }
+
+    assert_eq!(parse_enum::<Status>("status", "archived").unwrap(), Status::Archived);
+    assert_eq!(parse_enum::<Status>("status", "deleted").unwrap_err().to_string(),
+               "`status` must be one of: active, archived.");
+}
+
+#[test]
+fn test_parse_number() {
+    use self::NumberFormat::*;
+
+    assert_eq!(parse_number::<f64>("n", "1234.5", Strict).unwrap(), 1234.5);
+    assert!(parse_number::<f64>("n", "1,234.5", Strict).is_err());
+    assert_eq!(parse_number::<f64>("n", "1,234.5", DecimalPoint).unwrap(), 1234.5);
+    assert_eq!(parse_number::<f64>("n", "1.234,56", DecimalComma).unwrap(), 1234.56);
+    assert_eq!(parse_number::<f64>("n", "-12 345,5", DecimalComma).unwrap(), -12345.5);
+    assert_eq!(parse_number::<i64>("n", "1\u{a0}000\u{a0}000", DecimalPoint).unwrap(), 1000000);
+    assert_eq!(parse_number::<f64>("n", "0,5", DecimalComma).unwrap(), 0.5);
+    assert!(parse_number::<f64>("n", "12,34.5", DecimalPoint).is_err());
+    assert!(parse_number::<f64>("n", "1.234.5", DecimalComma).is_err());
+    assert!(parse_number::<i64>("n", "1,5", DecimalComma).is_err());
+}
+
+#[test]
+fn test_parse_email() {
+    let plain = EmailOptions::new();
+    assert_eq!(parse_email("email", " Jane.Doe+tag@Example.com ", plain).unwrap(),
+               "Jane.Doe+tag@Example.com");
+    assert_eq!(parse_email("email", "Jane@Example.COM", plain.lowercase(true)).unwrap(),
+               "jane@example.com");
+    assert_eq!(parse_email("email", "user@bücher.de", plain.idn_to_ascii(true)).unwrap(),
+               "user@xn--bcher-kva.de");
+
+    for bad in &["plain", "a@b@c.com", "@example.com", "a..b@example.com", "a@localhost",
+                 "a@-example.com", "a@example.123", "a b@example.com"] {
+        assert!(parse_email("email", bad, plain).is_err(), "accepted {}", bad);
+    }
+}
+
+#[test]
+fn test_parse_url() {
+    let any = UrlOptions::new();
+    assert_eq!(parse_url("next", "http://example.com/a?b=c", &any).unwrap().serialize(),
+               "http://example.com/a?b=c");
+    assert!(parse_url("next", "/relative", &any).is_err());
+
+    let strict = UrlOptions::new().schemes(&["https"]).hosts(&["example.com", "*.example.org"]);
+    assert!(parse_url("next", "https://EXAMPLE.com/", &strict).is_ok());
+    assert!(parse_url("next", "https://app.example.org/", &strict).is_ok());
+    assert!(parse_url("next", "http://example.com/", &strict).is_err());
+    assert!(parse_url("next", "https://example.org/", &strict).is_err());
+    assert!(parse_url("next", "https://evilexample.org/", &strict).is_err());
+    assert!(parse_url("next", "https://example.com.evil.net/", &strict).is_err());
+    assert!(parse_url("next", "javascript:alert(1)", &strict).is_err());
+}
+
+#[test]
+fn test_parse_set() {
+    let values = vec!["read".to_owned(), "write".to_owned()];
+    let set: HashSet<String> = parse_set("perm", &values, Some(&["read", "write", "admin"])).unwrap();
+    assert!(set.contains("read") && set.contains("write") && set.len() == 2);
+
+    assert!(parse_set::<String>("perm", &values, Some(&["read"])).is_err());
+    let twice = vec!["read".to_owned(), "read".to_owned()];
+    assert!(parse_set::<String>("perm", &twice, None).is_err());
+    let numbers = vec!["1".to_owned(), "x".to_owned()];
+    assert!(parse_set::<u32>("ids", &numbers, None).is_err());
+}
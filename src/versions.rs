@@ -0,0 +1,124 @@
+//! Forms whose layout changes between releases, validated against the
+//! schema of the version the client submitted.
+
+use std::collections::BTreeMap;
+
+#[cfg(feature = "iron")]
+use iron::prelude::*;
+
+use super::{QueryMap, UrlDecodingError};
+use super::named::select_schema;
+use super::schema::{FormData, FormSchema};
+#[cfg(feature = "iron")]
+use super::form_params;
+
+/// A form that passed the validation of the schema of its version.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VersionedForm {
+    /// The version the form was validated as.
+    pub version: String,
+    /// The converted values of the form.
+    pub data: FormData
+}
+
+/// The schemas of the versions of a form and the parameter naming the
+/// version submitted, `_v` by default.
+///
+/// During a rolling upgrade, pages served before the new release keep
+/// posting the previous layout: registering both versions accepts either,
+/// and `VersionedForm::version` tells the handler which one it got.
+///
+/// ```ignore
+/// let profile = FormVersions::new()
+///     .version("1", profile_v1())
+///     .version("2", profile_v2())
+///     .fallback("1");
+/// // <input type="hidden" name="_v" value="2">
+/// let form = profile.from_request(req)?;
+/// ```
+#[derive(Clone, Debug)]
+pub struct FormVersions {
+    param: String,
+    versions: BTreeMap<String, FormSchema>,
+    fallback: Option<String>
+}
+
+impl Default for FormVersions {
+    fn default() -> FormVersions {
+        FormVersions { param: "_v".to_owned(), versions: BTreeMap::new(), fallback: None }
+    }
+}
+
+impl FormVersions {
+    /// No versions yet, named in `_v`.
+    pub fn new() -> FormVersions {
+        FormVersions::default()
+    }
+
+    /// Read the version from `param`, such as `form_version`, instead of `_v`.
+    pub fn param(mut self, param: &str) -> FormVersions {
+        self.param = param.to_owned();
+        self
+    }
+
+    /// Validate the forms of `version` against `schema`.
+    pub fn version(mut self, version: &str, schema: FormSchema) -> FormVersions {
+        self.versions.insert(version.to_owned(), schema);
+        self
+    }
+
+    /// Validate forms without a version as `version`, for the clients that
+    /// predate the parameter. Without a fallback they are refused.
+    pub fn fallback(mut self, version: &str) -> FormVersions {
+        self.fallback = Some(version.to_owned());
+        self
+    }
+
+    /// Validate `params` against the schema of their version.
+    pub fn validate(&self, params: &QueryMap) -> Result<VersionedForm, UrlDecodingError> {
+        let (version, schema) = match (params.get(&self.param), &self.fallback) {
+            (None, Some(fallback)) => {
+                let schema = self.versions.get(fallback)
+                    .ok_or_else(|| UrlDecodingError::MissingParameter(self.param.clone()))?;
+                (&**fallback, schema)
+            }
+            _ => select_schema(params, &self.param, &self.versions)?
+        };
+        let data = schema.validate(params).map_err(UrlDecodingError::ValidationFailed)?;
+        Ok(VersionedForm { version: version.to_owned(), data })
+    }
+
+    /// Like `validate`, for the submitted form of `req`: the body of
+    /// form-encoded requests, the query string otherwise.
+    #[cfg(feature = "iron")]
+    pub fn from_request(&self, req: &mut Request) -> Result<VersionedForm, UrlDecodingError> {
+        match form_params(req) {
+            Ok(params) => self.validate(&params),
+            Err(UrlDecodingError::EmptyQuery) => self.validate(&QueryMap::new()),
+            Err(err) => Err(err)
+        }
+    }
+}
+
+#[test]
+fn test_form_versions() {
+    use super::{parse_with, ParseOptions};
+    use super::schema::{Field, FieldType};
+
+    let versions = FormVersions::new()
+        .version("1", FormSchema::new().field(Field::new("name", FieldType::Text).required()))
+        .version("2", FormSchema::new()
+            .field(Field::new("first_name", FieldType::Text).required())
+            .field(Field::new("last_name", FieldType::Text).required()));
+    let parse = |data: &str| parse_with(data, &ParseOptions::new()).unwrap();
+
+    let form = versions.validate(&parse("_v=2&first_name=Ada&last_name=Lovelace")).unwrap();
+    assert_eq!((&*form.version, form.data.str("last_name")), ("2", Some("Lovelace")));
+    assert!(matches!(versions.validate(&parse("_v=2&name=Ada")), Err(UrlDecodingError::ValidationFailed(_))));
+    assert!(matches!(versions.validate(&parse("name=Ada")), Err(UrlDecodingError::MissingParameter(_))));
+    assert_eq!(versions.validate(&parse("_v=3")).unwrap_err().to_string(), "`_v` must be one of: 1, 2.");
+
+    let versions = versions.param("form_version").fallback("1");
+    assert_eq!(versions.validate(&parse("name=Ada")).unwrap().version, "1");
+    assert_eq!(versions.validate(&parse("form_version=2&first_name=A&last_name=L")).unwrap().version, "2");
+}
@@ -0,0 +1,270 @@
+//! Verifying the signature of webhook deliveries sent as
+//! `application/x-www-form-urlencoded` data, as Slack and Twilio send them.
+
+use std::sync::Arc;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "iron")]
+use iron::prelude::*;
+use rustc_serialize::base64::FromBase64;
+use rustc_serialize::hex::FromHex;
+
+use super::{QueryMap, UrlDecodingError, ParseOptions, parse_with};
+#[cfg(feature = "iron")]
+use super::{UrlEncodedBody, RawFormBody, request_config};
+use super::signing::{Signer, constant_time_eq};
+
+/// What the provider signs.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WebhookPayload {
+    /// The raw body.
+    Body,
+    /// `<version>:<timestamp>:<body>`, with the signature written as
+    /// `<version>=<signature>`, as Slack signs with version `v0`.
+    Timestamped(String),
+    /// The request URL followed by the name and value of every parameter,
+    /// sorted by name, as Twilio signs.
+    UrlAndParams
+}
+
+/// How the provider writes signatures.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SignatureEncoding {
+    /// Lowercase or uppercase hexadecimal.
+    Hex,
+    /// Standard base64.
+    Base64
+}
+
+/// Checks that a webhook delivery was signed with a shared secret before
+/// handing out its parameters.
+///
+/// The signature is computed by `signer`, typically an HMAC with the hash
+/// function of the provider, over the raw body kept by `UrlEncodedBody`, so
+/// it is checked against exactly the bytes that were sent.
+///
+/// ```ignore
+/// let slack = WebhookVerifier::slack(hmac_sha256, SIGNING_SECRET);
+/// let params = slack.verify_request(req).map_err(|err| err.into_iron_error(req))?;
+/// ```
+#[derive(Clone)]
+pub struct WebhookVerifier {
+    signer: Arc<dyn Signer + Send + Sync>,
+    secret: Vec<u8>,
+    payload: WebhookPayload,
+    encoding: SignatureEncoding,
+    signature_header: String,
+    timestamp_header: Option<String>,
+    tolerance: u64
+}
+
+impl WebhookVerifier {
+    /// Check the hexadecimal signature of the body in `signature_header`.
+    pub fn new<S>(signer: S, secret: &[u8], signature_header: &str) -> WebhookVerifier
+    where S: Signer + Send + Sync + 'static {
+        WebhookVerifier {
+            signer: Arc::new(signer),
+            secret: secret.to_vec(),
+            payload: WebhookPayload::Body,
+            encoding: SignatureEncoding::Hex,
+            signature_header: signature_header.to_owned(),
+            timestamp_header: None,
+            tolerance: 300
+        }
+    }
+
+    /// Check Slack requests, signed with HMAC-SHA256 and at most five minutes
+    /// old.
+    pub fn slack<S>(signer: S, signing_secret: &[u8]) -> WebhookVerifier
+    where S: Signer + Send + Sync + 'static {
+        WebhookVerifier::new(signer, signing_secret, "X-Slack-Signature")
+            .payload(WebhookPayload::Timestamped("v0".to_owned()))
+            .timestamp_header("X-Slack-Request-Timestamp")
+    }
+
+    /// Check Twilio requests, signed with HMAC-SHA1.
+    ///
+    /// Twilio signs the URL it was configured with: behind a proxy rewriting
+    /// URLs, use `verify` with that URL instead of `verify_request`.
+    pub fn twilio<S>(signer: S, auth_token: &[u8]) -> WebhookVerifier
+    where S: Signer + Send + Sync + 'static {
+        WebhookVerifier::new(signer, auth_token, "X-Twilio-Signature")
+            .payload(WebhookPayload::UrlAndParams)
+            .encoding(SignatureEncoding::Base64)
+    }
+
+    /// Check a signature of `payload` instead of the body.
+    pub fn payload(mut self, payload: WebhookPayload) -> WebhookVerifier {
+        self.payload = payload;
+        self
+    }
+
+    /// Read signatures written in `encoding` instead of hexadecimal.
+    pub fn encoding(mut self, encoding: SignatureEncoding) -> WebhookVerifier {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Require a Unix timestamp in `header`, within the tolerance of the
+    /// current time, to reject replayed deliveries.
+    pub fn timestamp_header(mut self, header: &str) -> WebhookVerifier {
+        self.timestamp_header = Some(header.to_owned());
+        self
+    }
+
+    /// Accept timestamps at most `seconds` away from the current time, 300 by
+    /// default.
+    pub fn tolerance(mut self, seconds: u64) -> WebhookVerifier {
+        self.tolerance = seconds;
+        self
+    }
+
+    /// Check the signature of a delivery and parse its body.
+    ///
+    /// `url` is the URL the provider sent the delivery to, and `signature` and
+    /// `timestamp` the values of the configured headers.
+    ///
+    /// Not available on `wasm32-unknown-unknown`, which has no clock.
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub fn verify(&self, url: &str, body: &str, signature: Option<&str>, timestamp: Option<&str>,
+                  options: &ParseOptions) -> Result<QueryMap, UrlDecodingError> {
+        let params = parse_body(parse_with(body, options))?;
+        self.check(url, body, &params, signature, timestamp, unix_now())?;
+        Ok(params)
+    }
+
+    /// Check the signature of the delivery in `req` and parse its body with
+    /// `UrlEncodedBody`.
+    ///
+    /// The signature is checked against the raw body, and the parameters
+    /// parsed from it without the configured transforms, as the provider
+    /// signed them; the parameters returned have the transforms applied.
+    /// Spooled and multipart bodies aren't kept as `RawFormBody`, so their
+    /// deliveries fail with `InvalidParameter` for the signature header.
+    #[cfg(feature = "iron")]
+    pub fn verify_request(&self, req: &mut Request) -> Result<QueryMap, UrlDecodingError> {
+        let params = parse_body(req.get::<UrlEncodedBody>())?;
+        let body = req.extensions.get::<RawFormBody>().cloned();
+        let (body, signed) = self.signed_params(body, request_config(req).options())?;
+        let header = |name: &str| req.headers.get_raw(name)
+            .and_then(|values| values.first())
+            .and_then(|value| String::from_utf8(value.clone()).ok());
+        let signature = header(&self.signature_header);
+        let timestamp = self.timestamp_header.as_ref().and_then(|name| header(name));
+
+        self.check(&req.url.to_string(), &body, &signed, signature.as_deref(), timestamp.as_deref(),
+                   unix_now())?;
+        Ok(params)
+    }
+
+    /// The raw body of a delivery and the parameters parsed from it as sent.
+    #[cfg(feature = "iron")]
+    fn signed_params(&self, body: Option<String>, options: &ParseOptions)
+                     -> Result<(String, QueryMap), UrlDecodingError> {
+        let body = body.ok_or_else(|| UrlDecodingError::InvalidParameter {
+            key: self.signature_header.clone(),
+            message: "can't be checked without the raw body, which spooled and multipart bodies don't keep"
+                .to_owned()
+        })?;
+        let params = parse_body(parse_with(&body, options))?;
+        Ok((body, params))
+    }
+
+    fn check(&self, url: &str, body: &str, params: &QueryMap, signature: Option<&str>,
+             timestamp: Option<&str>, now: u64) -> Result<(), UrlDecodingError> {
+        if let Some(ref header) = self.timestamp_header {
+            match timestamp.and_then(|timestamp| timestamp.trim().parse::<u64>().ok()) {
+                Some(timestamp) if timestamp.max(now) - timestamp.min(now) <= self.tolerance => (),
+                _ => return Err(UrlDecodingError::InvalidParameter {
+                    key: header.clone(),
+                    message: "is missing or too far from the current time".to_owned()
+                })
+            }
+        }
+
+        let (message, signature) = match self.payload {
+            WebhookPayload::Body => (body.to_owned(), signature),
+            WebhookPayload::Timestamped(ref version) => {
+                let message = format!("{}:{}:{}", version, timestamp.unwrap_or(""), body);
+                (message, signature.and_then(|signature| signature.strip_prefix(&format!("{}=", version))))
+            },
+            WebhookPayload::UrlAndParams => {
+                let mut pairs: Vec<(&str, &str)> = params.iter()
+                    .flat_map(|(key, values)| values.iter().map(move |value| (&**key, &**value)))
+                    .collect();
+                pairs.sort_by(|a, b| a.0.cmp(b.0));
+                let message = pairs.iter().fold(url.to_owned(), |mut message, &(key, value)| {
+                    message.push_str(key);
+                    message.push_str(value);
+                    message
+                });
+                (message, signature)
+            }
+        };
+
+        let decoded = signature.and_then(|signature| match self.encoding {
+            SignatureEncoding::Hex => signature.from_hex().ok(),
+            SignatureEncoding::Base64 => signature.from_base64().ok()
+        });
+        let expected = self.signer.sign(&self.secret, message.as_bytes());
+        match decoded {
+            Some(ref decoded) if constant_time_eq(decoded, &expected) => Ok(()),
+            _ => Err(UrlDecodingError::InvalidParameter {
+                key: self.signature_header.clone(),
+                message: "is missing or doesn't match the payload".to_owned()
+            })
+        }
+    }
+}
+
+/// An empty body is an empty delivery, which still has to be signed.
+fn parse_body(result: Result<QueryMap, UrlDecodingError>) -> Result<QueryMap, UrlDecodingError> {
+    match result {
+        Err(UrlDecodingError::EmptyQuery) => Ok(QueryMap::new()),
+        result => result
+    }
+}
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_secs()).unwrap_or(0)
+}
+
+#[test]
+fn test_webhook_verifier() {
+    use rustc_serialize::base64::{self, ToBase64};
+    use rustc_serialize::hex::ToHex;
+
+    let signer = super::signing::test_signer;
+    let body = "command=%2Fdeploy&text=prod";
+    let params = parse_with(body, &ParseOptions::new()).unwrap();
+
+    let slack = WebhookVerifier::slack(signer, b"secret");
+    let signature = format!("v0={}", signer(b"secret", format!("v0:1000:{}", body).as_bytes()).to_hex());
+    assert!(slack.check("", body, &params, Some(&signature), Some("1000"), 1100).is_ok());
+    assert!(slack.check("", body, &params, Some(&signature), Some("1000"), 1400).is_err());
+    assert!(slack.check("", body, &params, Some(&signature), None, 1100).is_err());
+    assert!(slack.check("", "command=%2Fdeploy&text=dev", &params, Some(&signature), Some("1000"), 1100).is_err());
+    assert!(slack.check("", body, &params, None, Some("1000"), 1100).is_err());
+
+    let twilio = WebhookVerifier::twilio(signer, b"token");
+    let url = "https://example.com/sms";
+    let signature = signer(b"token", b"https://example.com/smscommand/deploytextprod").to_base64(base64::STANDARD);
+    assert!(twilio.check(url, body, &params, Some(&signature), None, 0).is_ok());
+    assert!(twilio.check("https://example.com/other", body, &params, Some(&signature), None, 0).is_err());
+
+    // Transforms change what handlers see, not what was signed.
+    let mut transformed = params.clone();
+    let mut transforms = super::Transforms::new();
+    transforms.add_all(super::Transform::Uppercase);
+    transforms.apply(&mut transformed);
+    assert!(twilio.check(url, body, &transformed, Some(&signature), None, 0).is_err());
+    #[cfg(feature = "iron")]
+    {
+        let (raw, signed) = twilio.signed_params(Some(body.to_owned()), &ParseOptions::new()).unwrap();
+        assert!(twilio.check(url, &raw, &signed, Some(&signature), None, 0).is_ok());
+        assert!(matches!(twilio.signed_params(None, &ParseOptions::new()),
+                         Err(UrlDecodingError::InvalidParameter { ref key, .. }) if key == "X-Twilio-Signature"));
+    }
+}
@@ -0,0 +1,187 @@
+//! Multi-step forms, whose steps are validated one at a time and
+//! accumulated in a signed hidden field until the last one.
+
+use std::collections::BTreeMap;
+
+#[cfg(feature = "iron")]
+use iron::prelude::*;
+
+use super::{QueryMap, UrlDecodingError};
+use super::hidden::SignedFields;
+use super::named::select_schema;
+use super::schema::{FormData, FormSchema};
+#[cfg(feature = "iron")]
+use super::form_params;
+
+/// The key of the accumulated state listing the completed steps.
+const COMPLETED: &str = "_completed";
+
+/// The values accumulated by a `Wizard` so far.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WizardState {
+    /// The values submitted for every completed step, as they were sent.
+    pub params: QueryMap,
+    /// The steps completed, in the order of the wizard.
+    pub completed: Vec<String>,
+    /// The steps that remain, in the order of the wizard.
+    pub remaining: Vec<String>
+}
+
+impl WizardState {
+    /// Whether every step was completed.
+    pub fn is_complete(&self) -> bool {
+        self.remaining.is_empty()
+    }
+
+    /// The next step to show, if any remain.
+    pub fn next_step(&self) -> Option<&str> {
+        self.remaining.first().map(|step| &**step)
+    }
+}
+
+/// The steps of a multi-step form, each with its schema, and the signed
+/// hidden field carrying the values of the completed steps from one step to
+/// the next. The step submitted is named in `_step`.
+///
+/// Each submission is validated against the schema of its step only, then
+/// merged into the accumulated values: going back and submitting a step
+/// again replaces its values.
+///
+/// ```ignore
+/// let signup = Wizard::new(SignedFields::new(hmac_sha256, SECRET))
+///     .step("account", account_schema())
+///     .step("profile", profile_schema());
+/// let state = signup.from_request(req)?;
+/// match state.next_step() {
+///     Some(step) => render(step, &signup.encode(&state)?),
+///     None => create_account(signup.finish(&state)?)
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Wizard {
+    fields: SignedFields,
+    param: String,
+    steps: Vec<(String, FormSchema)>
+}
+
+impl Wizard {
+    /// A wizard without steps yet, carrying its values in `fields`.
+    pub fn new(fields: SignedFields) -> Wizard {
+        Wizard { fields, param: "_step".to_owned(), steps: Vec::new() }
+    }
+
+    /// Read the name of the step submitted from `param` instead of `_step`.
+    pub fn param(mut self, param: &str) -> Wizard {
+        self.param = param.to_owned();
+        self
+    }
+
+    /// Add a step called `name`, whose fields `schema` validates.
+    pub fn step(mut self, name: &str, schema: FormSchema) -> Wizard {
+        self.steps.push((name.to_owned(), schema));
+        self
+    }
+
+    fn state(&self, mut params: QueryMap) -> WizardState {
+        let done = params.remove(COMPLETED).unwrap_or_default();
+        let (completed, remaining) = self.steps.iter()
+            .map(|(name, _)| name.clone())
+            .partition(|name| done.contains(name));
+        WizardState { params, completed, remaining }
+    }
+
+    /// Validate the step submitted in `params` and merge it into the values
+    /// accumulated in the hidden field, which is absent until a first step
+    /// was completed.
+    pub fn accumulate(&self, params: &QueryMap) -> Result<WizardState, UrlDecodingError> {
+        let mut accumulated: QueryMap = match self.fields.decode(params) {
+            Ok(accumulated) => accumulated,
+            Err(UrlDecodingError::MissingParameter(_)) => QueryMap::new(),
+            Err(err) => return Err(err)
+        };
+
+        let steps: BTreeMap<String, FormSchema> = self.steps.iter().cloned().collect();
+        let (step, schema) = select_schema(params, &self.param, &steps)?;
+        schema.validate(params).map_err(UrlDecodingError::ValidationFailed)?;
+
+        for field in schema.fields() {
+            match params.get(field.name()) {
+                Some(values) => accumulated.insert(field.name().to_owned(), values.clone()),
+                None => accumulated.remove(field.name())
+            };
+        }
+        let completed = accumulated.entry(COMPLETED.to_owned()).or_default();
+        if !completed.iter().any(|done| done == step) {
+            completed.push(step.to_owned());
+        }
+        Ok(self.state(accumulated))
+    }
+
+    /// Like `accumulate`, for the submitted form of `req`: the body of
+    /// form-encoded requests, the query string otherwise.
+    #[cfg(feature = "iron")]
+    pub fn from_request(&self, req: &mut Request) -> Result<WizardState, UrlDecodingError> {
+        match form_params(req) {
+            Ok(params) => self.accumulate(&params),
+            Err(UrlDecodingError::EmptyQuery) => self.accumulate(&QueryMap::new()),
+            Err(err) => Err(err)
+        }
+    }
+
+    /// The value of the hidden field carrying `state` to the next step.
+    pub fn encode(&self, state: &WizardState) -> Result<String, UrlDecodingError> {
+        let mut params = state.params.clone();
+        params.insert(COMPLETED.to_owned(), state.completed.clone());
+        self.fields.encode(&params)
+    }
+
+    /// The values of every step, by step name, validated again as a whole.
+    /// Fails while steps remain.
+    pub fn finish(&self, state: &WizardState) -> Result<BTreeMap<String, FormData>, UrlDecodingError> {
+        if !state.is_complete() {
+            return Err(UrlDecodingError::InvalidParameter {
+                key: self.param.clone(),
+                message: format!("must still go through the steps: {}", state.remaining.join(", "))
+            });
+        }
+
+        let mut data = BTreeMap::new();
+        let mut errors = Vec::new();
+        for (name, schema) in &self.steps {
+            match schema.validate(&state.params) {
+                Ok(step) => { data.insert(name.clone(), step); },
+                Err(step_errors) => errors.extend(step_errors)
+            }
+        }
+        if errors.is_empty() { Ok(data) } else { Err(UrlDecodingError::ValidationFailed(errors)) }
+    }
+}
+
+#[test]
+fn test_wizard() {
+    use super::{parse_with, ParseOptions};
+    use super::schema::{Field, FieldType};
+
+    let signer = super::signing::test_signer;
+    let wizard = Wizard::new(SignedFields::new(signer, b"secret"))
+        .step("account", FormSchema::new().field(Field::new("email", FieldType::Email).required()))
+        .step("profile", FormSchema::new().field(Field::new("name", FieldType::Text).required()));
+    let parse = |data: &str| parse_with(data, &ParseOptions::new()).unwrap();
+
+    let state = wizard.accumulate(&parse("_step=account&email=ada%40example.com")).unwrap();
+    assert_eq!((state.completed.clone(), state.next_step()), (vec!["account".to_owned()], Some("profile")));
+    assert!(wizard.finish(&state).is_err());
+
+    let carried = wizard.encode(&state).unwrap();
+    assert!(matches!(wizard.accumulate(&parse(&format!("_step=profile&_state={}", carried))),
+                     Err(UrlDecodingError::ValidationFailed(_))));
+    let state = wizard.accumulate(&parse(&format!("_step=profile&name=Ada&_state={}", carried))).unwrap();
+    assert!(state.is_complete());
+    let data = wizard.finish(&state).unwrap();
+    assert_eq!(data["account"].str("email"), Some("ada@example.com"));
+    assert_eq!(data["profile"].str("name"), Some("Ada"));
+
+    let tampered = format!("{}{}", if carried.starts_with('A') { 'B' } else { 'A' }, &carried[1..]);
+    assert!(wizard.accumulate(&parse(&format!("_step=profile&name=Ada&_state={}", tampered))).is_err());
+    assert!(wizard.accumulate(&parse("_step=billing")).is_err());
+}